@@ -0,0 +1,509 @@
+use std::fmt::Display;
+
+use crate::ast::*;
+use crate::lexer::parse_number;
+use crate::parser::{TreeNode, EMPTY_SYMBOL};
+use crate::token::{Literal, Point, Span};
+
+// 语法树到AST的降级
+// 文法中右递归的 *Rest / *Opt 辅助非终结符在这里被展开，
+// 表达式的 *Rest 链按左结合重新折叠
+
+#[derive(Debug)]
+pub struct LowerError {
+  pub message: String,
+  pub error_span: Option<Span>,
+}
+
+impl Display for LowerError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{}", self.message)
+  }
+}
+
+type LowerResult<T> = Result<T, LowerError>;
+
+pub fn lower(tree: &TreeNode) -> LowerResult<CompUnit> {
+  let root = match name(tree) {
+    "CompUnit'" => child(tree, 0)?,
+    _ => tree,
+  };
+  expect(root, "CompUnit")?;
+  let mut items = Vec::new();
+  for node in rest_items(root, "CompUnitOpt") {
+    comp_unit_item(node, &mut items)?;
+  }
+  Ok(CompUnit { items })
+}
+
+fn error<T>(node: &TreeNode, message: String) -> LowerResult<T> {
+  Err(LowerError {
+    message,
+    error_span: first_span(node),
+  })
+}
+
+fn name(node: &TreeNode) -> &str {
+  node.element.get_value()
+}
+
+fn children(node: &TreeNode) -> &[TreeNode] {
+  node.children.as_deref().unwrap_or(&[])
+}
+
+fn child(node: &TreeNode, index: usize) -> LowerResult<&TreeNode> {
+  match children(node).get(index) {
+    Some(child) => Ok(child),
+    None => error(node, format!("'{}' is missing child {}", name(node), index)),
+  }
+}
+
+fn expect(node: &TreeNode, expected: &str) -> LowerResult<()> {
+  if name(node) == expected {
+    Ok(())
+  } else {
+    error(node, format!("expected '{}', found '{}'", expected, name(node)))
+  }
+}
+
+fn is_empty(node: &TreeNode) -> bool {
+  let children = children(node);
+  children.is_empty() || (children.len() == 1 && children[0].element == *EMPTY_SYMBOL)
+}
+
+fn first_pos(node: &TreeNode) -> Option<Point> {
+  if let Some(pos) = node.element.get_pos() {
+    return Some(*pos);
+  }
+  children(node).iter().find_map(first_pos)
+}
+
+fn pos(node: &TreeNode) -> Point {
+  first_pos(node).unwrap_or_else(|| Point::new(0, 0))
+}
+
+// 第一个词素的区间；手工构造的词素没有区间时取其位置上的空区间
+fn first_span(node: &TreeNode) -> Option<Span> {
+  if let Some(pos) = node.element.get_pos() {
+    return Some(node.element.get_span().unwrap_or(Span::new(0, 0, *pos, *pos)));
+  }
+  children(node).iter().find_map(first_span)
+}
+
+fn span(node: &TreeNode) -> Span {
+  first_span(node).unwrap_or_default()
+}
+
+// 展开右递归的列表：X: A Rest，Rest: ε | sep... A Rest
+// 返回除分隔符和递归尾之外的所有子节点
+fn rest_items<'a>(node: &'a TreeNode, rest: &str) -> Vec<&'a TreeNode> {
+  let mut items = Vec::new();
+  let mut current = node;
+  loop {
+    let mut next = None;
+    for child in children(current) {
+      if name(child) == rest {
+        next = Some(child);
+      } else if child.element.is_not_terminal() {
+        items.push(child);
+      }
+    }
+    match next {
+      Some(node) if !is_empty(node) => current = node,
+      _ => break,
+    }
+  }
+  items
+}
+
+fn comp_unit_item(node: &TreeNode, items: &mut Vec<GlobalItem>) -> LowerResult<()> {
+  let node = match name(node) {
+    "DeclOrFuncDef" | "TopLevelItem" => child(node, 0)?,
+    _ => node,
+  };
+  match name(node) {
+    "Decl" | "ConstDecl" | "VarDecl" => items.push(GlobalItem::Decl(decl(node)?)),
+    "FuncDef" => items.push(GlobalItem::FuncDef(func_def(node)?)),
+    other => return error(node, format!("unexpected '{}' at top level", other)),
+  }
+  Ok(())
+}
+
+fn btype(node: &TreeNode) -> LowerResult<BType> {
+  let keyword = match name(node) {
+    "BType" => name(child(node, 0)?),
+    other => other,
+  };
+  match keyword {
+    "int" => Ok(BType::Int),
+    "float" => Ok(BType::Float),
+    other => error(node, format!("unknown type '{}'", other)),
+  }
+}
+
+fn ident(node: &TreeNode) -> LowerResult<Ident> {
+  if !node.element.is_terminal() || node.element.get_pos().is_none() {
+    return error(node, format!("expected identifier, found '{}'", name(node)));
+  }
+  Ok(Ident {
+    name: name(node).to_string(),
+    span: span(node),
+  })
+}
+
+fn decl(node: &TreeNode) -> LowerResult<Decl> {
+  let node = match name(node) {
+    "Decl" => child(node, 0)?,
+    _ => node,
+  };
+  match name(node) {
+    "ConstDecl" => const_decl(node),
+    "VarDecl" => var_decl(node),
+    other => error(node, format!("unexpected '{}' in declaration", other)),
+  }
+}
+
+// ConstDecl: const BType ConstDef ConstDeclRest ;
+fn const_decl(node: &TreeNode) -> LowerResult<Decl> {
+  let ty = btype(child(node, 1)?)?;
+  let mut defs = Vec::new();
+  for def in rest_items(node, "ConstDeclRest") {
+    if name(def) == "ConstDef" {
+      defs.push(const_def(def)?);
+    }
+  }
+  Ok(Decl { is_const: true, ty, defs })
+}
+
+// ConstDef: Ident ConstDefArray = ConstInitVal
+fn const_def(node: &TreeNode) -> LowerResult<Def> {
+  Ok(Def {
+    ident: ident(child(node, 0)?)?,
+    dims: dims(child(node, 1)?)?,
+    init: Some(init_val(child(node, 3)?)?),
+  })
+}
+
+// VarDecl: BTypeAndIdent VarDefArray InitValOpt VarDeclRest ;
+// VarDeclRest: ε | , Ident VarDefArray InitValOpt VarDeclRest
+// 兼容 g.txt 中 VarDecl: BType VarDef VarDeclRest ;
+fn var_decl(node: &TreeNode) -> LowerResult<Decl> {
+  let first = child(node, 0)?;
+  let mut defs = Vec::new();
+  if name(first) != "BTypeAndIdent" {
+    for def in rest_items(node, "VarDeclRest") {
+      if name(def) == "VarDef" {
+        defs.push(var_def(def)?);
+      }
+    }
+    return Ok(Decl { is_const: false, ty: btype(first)?, defs });
+  }
+
+  defs.push(Def {
+    ident: ident(child(first, 1)?)?,
+    dims: dims(child(node, 1)?)?,
+    init: init_val_opt(child(node, 2)?)?,
+  });
+  let mut rest = child(node, 3)?;
+  while !is_empty(rest) {
+    defs.push(Def {
+      ident: ident(child(rest, 1)?)?,
+      dims: dims(child(rest, 2)?)?,
+      init: init_val_opt(child(rest, 3)?)?,
+    });
+    rest = child(rest, 4)?;
+  }
+  Ok(Decl { is_const: false, ty: btype(child(first, 0)?)?, defs })
+}
+
+// VarDef: Ident VarDefArray InitValOpt
+fn var_def(node: &TreeNode) -> LowerResult<Def> {
+  Ok(Def {
+    ident: ident(child(node, 0)?)?,
+    dims: dims(child(node, 1)?)?,
+    init: init_val_opt(child(node, 2)?)?,
+  })
+}
+
+fn init_val_opt(node: &TreeNode) -> LowerResult<Option<InitVal>> {
+  if is_empty(node) {
+    return Ok(None);
+  }
+  Ok(Some(init_val(child(node, 1)?)?))
+}
+
+// XDefArray: ε | [ ConstExp ] XDefArray
+fn dims(node: &TreeNode) -> LowerResult<Vec<Exp>> {
+  let mut result = Vec::new();
+  let mut current = node;
+  while !is_empty(current) {
+    result.push(exp(child(current, 1)?)?);
+    current = child(current, 3)?;
+  }
+  Ok(result)
+}
+
+// InitVal: Exp | { InitValList }
+fn init_val(node: &TreeNode) -> LowerResult<InitVal> {
+  let first = child(node, 0)?;
+  if name(first) != "{" {
+    return Ok(InitVal::Exp(exp(first)?));
+  }
+  let list = child(node, 1)?;
+  let mut items = Vec::new();
+  if !is_empty(list) {
+    let rest = format!("{}Rest", name(list));
+    for item in rest_items(list, &rest) {
+      items.push(init_val(item)?);
+    }
+  }
+  Ok(InitVal::List(items, span(first)))
+}
+
+// FuncDef: void Ident ( FuncFParams ) Block | BTypeAndIdent ( FuncFParams ) Block
+// 兼容 FuncDef: FuncType Ident ( FuncFParams ) Block
+fn func_def(node: &TreeNode) -> LowerResult<FuncDef> {
+  let first = child(node, 0)?;
+  let (func_type, ident_node) = match name(first) {
+    "BTypeAndIdent" => (btype(child(first, 0)?)?.into(), child(first, 1)?),
+    "FuncType" => (func_type(child(first, 0)?)?, child(node, 1)?),
+    _ => (func_type(first)?, child(node, 1)?),
+  };
+
+  let mut params = Vec::new();
+  let mut body = None;
+  for part in children(node) {
+    match name(part) {
+      "FuncFParams" if !is_empty(part) => {
+        for param in rest_items(part, "FuncFParamsRest") {
+          params.push(func_f_param(param)?);
+        }
+      }
+      "Block" => body = Some(block(part)?),
+      _ => {}
+    }
+  }
+
+  match body {
+    Some(body) => Ok(FuncDef {
+      func_type,
+      ident: ident(ident_node)?,
+      params,
+      body,
+    }),
+    None => error(node, "function without a body".to_string()),
+  }
+}
+
+fn func_type(node: &TreeNode) -> LowerResult<FuncType> {
+  match name(node) {
+    "void" => Ok(FuncType::Void),
+    _ => Ok(btype(node)?.into()),
+  }
+}
+
+impl From<BType> for FuncType {
+  fn from(ty: BType) -> Self {
+    match ty {
+      BType::Int => FuncType::Int,
+      BType::Float => FuncType::Float,
+    }
+  }
+}
+
+// FuncFParam: BType Ident FuncFParamArray
+// FuncFParamArray: ε | [ ] FuncFParamArrayExp
+// FuncFParamArrayExp: ε | [ Exp ] FuncFParamArrayExp
+fn func_f_param(node: &TreeNode) -> LowerResult<FuncFParam> {
+  let array = child(node, 2)?;
+  let dims = if is_empty(array) {
+    None
+  } else {
+    Some(dims(child(array, 2)?)?)
+  };
+  Ok(FuncFParam {
+    ty: btype(child(node, 0)?)?,
+    ident: ident(child(node, 1)?)?,
+    dims,
+  })
+}
+
+// Block: { BlockItems }
+// BlockItems: ε | BlockItem BlockItems
+fn block(node: &TreeNode) -> LowerResult<Block> {
+  let mut items = Vec::new();
+  let mut current = child(node, 1)?;
+  while !is_empty(current) {
+    let item = child(current, 0)?;
+    let inner = child(item, 0)?;
+    match name(inner) {
+      "Stmt" => items.push(BlockItem::Stmt(Box::new(stmt(inner)?))),
+      "Decl" | "ConstDecl" | "VarDecl" | "TopLevelItem" => items.push(BlockItem::Decl(decl(inner)?)),
+      other => return error(inner, format!("unexpected '{}' in block", other)),
+    }
+    current = child(current, 1)?;
+  }
+  Ok(Block {
+    items,
+    pos: pos(node),
+  })
+}
+
+fn exp_opt(node: &TreeNode) -> LowerResult<Option<Exp>> {
+  if is_empty(node) {
+    Ok(None)
+  } else {
+    Ok(Some(exp(child(node, 0)?)?))
+  }
+}
+
+fn stmt(node: &TreeNode) -> LowerResult<Stmt> {
+  let first = child(node, 0)?;
+  match name(first) {
+    "LVal" => Ok(Stmt::Assign(lval(first)?, exp(child(node, 2)?)?)),
+    "ExpOpt" => Ok(Stmt::Exp(exp_opt(first)?)),
+    "Block" => Ok(Stmt::Block(block(first)?)),
+    "if" => {
+      let cond = exp(child(node, 2)?)?;
+      let then = stmt(child(node, 4)?)?;
+      let stmt_else = child(node, 5)?;
+      let otherwise = if is_empty(stmt_else) {
+        None
+      } else {
+        Some(Box::new(stmt(child(stmt_else, 1)?)?))
+      };
+      Ok(Stmt::If(cond, Box::new(then), otherwise))
+    }
+    "while" => Ok(Stmt::While(exp(child(node, 2)?)?, Box::new(stmt(child(node, 4)?)?))),
+    "break" => Ok(Stmt::Break(span(first))),
+    "continue" => Ok(Stmt::Continue(span(first))),
+    "return" => Ok(Stmt::Return(exp_opt(child(node, 1)?)?, span(first))),
+    other => error(node, format!("unexpected '{}' in statement", other)),
+  }
+}
+
+// LVal: Ident LValArray
+// LValArray: ε | [ Exp ] LValArray
+fn lval(node: &TreeNode) -> LowerResult<LVal> {
+  Ok(LVal {
+    ident: ident(child(node, 0)?)?,
+    indices: dims(child(node, 1)?)?,
+  })
+}
+
+fn exp(node: &TreeNode) -> LowerResult<Exp> {
+  match name(node) {
+    "Exp" | "ConstExp" | "Cond" => exp(child(node, 0)?),
+    "AddExp" | "MulExp" | "RelExp" | "EqExp" | "LAndExp" | "LOrExp" => binary_exp(node),
+    "UnaryExp" => unary_exp(node),
+    "PrimaryExp" => primary_exp(node),
+    "Number" => number(child(node, 0)?),
+    "LVal" => {
+      let lval = lval(node)?;
+      let span = lval.ident.span;
+      Ok(Exp::new(ExpKind::LVal(lval), span))
+    }
+    other => error(node, format!("unexpected '{}' in expression", other)),
+  }
+}
+
+// XExp: YExp XExpRest
+// XExpRest: ε | XOp YExp XExpRest
+// 右递归链按左结合折叠：a - b - c => (a - b) - c
+fn binary_exp(node: &TreeNode) -> LowerResult<Exp> {
+  let mut lhs = exp(child(node, 0)?)?;
+  let mut rest = child(node, 1)?;
+  while !is_empty(rest) {
+    let op_node = child(rest, 0)?;
+    let op_token = match op_node.children.as_deref() {
+      Some([token]) => token,
+      _ => op_node,
+    };
+    let op = binary_op(op_token)?;
+    let rhs = exp(child(rest, 1)?)?;
+    lhs = Exp::new(ExpKind::Binary(op, Box::new(lhs), Box::new(rhs)), span(op_token));
+    rest = child(rest, 2)?;
+  }
+  Ok(lhs)
+}
+
+fn binary_op(node: &TreeNode) -> LowerResult<BinaryOp> {
+  match name(node) {
+    "+" => Ok(BinaryOp::Add),
+    "-" => Ok(BinaryOp::Sub),
+    "*" => Ok(BinaryOp::Mul),
+    "/" => Ok(BinaryOp::Div),
+    "%" => Ok(BinaryOp::Mod),
+    "<" => Ok(BinaryOp::Lt),
+    ">" => Ok(BinaryOp::Gt),
+    "<=" => Ok(BinaryOp::Le),
+    ">=" => Ok(BinaryOp::Ge),
+    "==" => Ok(BinaryOp::Eq),
+    "!=" => Ok(BinaryOp::Ne),
+    "&&" => Ok(BinaryOp::And),
+    "||" => Ok(BinaryOp::Or),
+    other => error(node, format!("unknown binary operator '{}'", other)),
+  }
+}
+
+// UnaryExp: PrimaryExp | Ident ( FuncRParams ) | UnaryOp UnaryExp
+fn unary_exp(node: &TreeNode) -> LowerResult<Exp> {
+  let first = child(node, 0)?;
+  match name(first) {
+    "PrimaryExp" => primary_exp(first),
+    "UnaryOp" => {
+      let op_token = child(first, 0)?;
+      let op = match name(op_token) {
+        "+" => UnaryOp::Plus,
+        "-" => UnaryOp::Minus,
+        "!" => UnaryOp::Not,
+        other => return error(op_token, format!("unknown unary operator '{}'", other)),
+      };
+      let operand = exp(child(node, 1)?)?;
+      Ok(Exp::new(ExpKind::Unary(op, Box::new(operand)), span(op_token)))
+    }
+    _ => {
+      let mut func = ident(first)?;
+      let params = child(node, 2)?;
+      let mut args = Vec::new();
+      if !is_empty(params) {
+        for arg in rest_items(params, "FuncRParamsRest") {
+          args.push(exp(arg)?);
+        }
+      }
+      // 未经预处理的starttime和stoptime与sylib.h中的宏相同，改为调用带行号参数的运行库函数
+      if func.name == "starttime" || func.name == "stoptime" {
+        args.push(Exp::new(ExpKind::IntConst(*func.pos().get_row() as i32), func.span));
+        func.name = format!("_sysy_{}", func.name);
+      }
+      let span = func.span;
+      Ok(Exp::new(ExpKind::Call(func, args), span))
+    }
+  }
+}
+
+// PrimaryExp: ( Exp ) | LVal | Number
+fn primary_exp(node: &TreeNode) -> LowerResult<Exp> {
+  let first = child(node, 0)?;
+  match name(first) {
+    "(" => exp(child(node, 1)?),
+    _ => exp(first),
+  }
+}
+
+// 词法分析时已经解析出值；手工构造的词素没有附带值时再解析一次
+fn number(node: &TreeNode) -> LowerResult<Exp> {
+  let text = name(node);
+  let literal = match node.element.get_literal() {
+    Some(literal) => literal,
+    None => match parse_number(text) {
+      Ok(literal) => literal,
+      Err(err) => return error(node, format!("{} '{}'", err, text)),
+    },
+  };
+  let kind = match literal {
+    Literal::Int(value) => ExpKind::IntConst(value),
+    Literal::Float(bits) => ExpKind::FloatConst(f32::from_bits(bits)),
+  };
+  Ok(Exp::new(kind, span(node)))
+}
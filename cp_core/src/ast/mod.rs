@@ -0,0 +1,323 @@
+mod lower;
+
+use crate::token::{Point, Span};
+
+pub use lower::{lower, LowerError};
+
+// SysY抽象语法树，由LR1Parser产生的语法树降级得到
+// 各处的Span是诊断信息中标出的词素区间
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompUnit {
+  pub items: Vec<GlobalItem>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum GlobalItem {
+  Decl(Decl),
+  FuncDef(FuncDef),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BType {
+  Int,
+  Float,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FuncType {
+  Void,
+  Int,
+  Float,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ident {
+  pub name: String,
+  pub span: Span,
+}
+
+impl Ident {
+  pub fn pos(&self) -> Point {
+    *self.span.get_start_point()
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Decl {
+  pub is_const: bool,
+  pub ty: BType,
+  pub defs: Vec<Def>,
+}
+
+// 常量和变量定义共用，常量的init一定存在
+#[derive(Debug, Clone, PartialEq)]
+pub struct Def {
+  pub ident: Ident,
+  pub dims: Vec<Exp>,
+  pub init: Option<InitVal>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum InitVal {
+  Exp(Exp),
+  List(Vec<InitVal>, Span),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FuncDef {
+  pub func_type: FuncType,
+  pub ident: Ident,
+  pub params: Vec<FuncFParam>,
+  pub body: Block,
+}
+
+// 数组形参的第一维省略，dims中只有后续维度
+#[derive(Debug, Clone, PartialEq)]
+pub struct FuncFParam {
+  pub ty: BType,
+  pub ident: Ident,
+  pub dims: Option<Vec<Exp>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Block {
+  pub items: Vec<BlockItem>,
+  pub pos: Point,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BlockItem {
+  Decl(Decl),
+  Stmt(Box<Stmt>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Stmt {
+  Assign(LVal, Exp),
+  Exp(Option<Exp>),
+  Block(Block),
+  If(Exp, Box<Stmt>, Option<Box<Stmt>>),
+  While(Exp, Box<Stmt>),
+  Break(Span),
+  Continue(Span),
+  Return(Option<Exp>, Span),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LVal {
+  pub ident: Ident,
+  pub indices: Vec<Exp>,
+}
+
+// span取表达式中最能代表该表达式的词素：字面量、标识符或运算符
+// ty在类型检查之后才有值
+#[derive(Debug, Clone, PartialEq)]
+pub struct Exp {
+  pub kind: ExpKind,
+  pub span: Span,
+  pub ty: Option<Type>,
+}
+
+// 表达式的类型，数组只记录元素类型和剩余的维数
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Type {
+  Int,
+  Float,
+  Void,
+  Array(BType, usize),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExpKind {
+  IntConst(i32),
+  FloatConst(f32),
+  LVal(LVal),
+  Call(Ident, Vec<Exp>),
+  Unary(UnaryOp, Box<Exp>),
+  Binary(BinaryOp, Box<Exp>, Box<Exp>),
+  // 类型检查插入的int和float之间的转换
+  Cast(BType, Box<Exp>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnaryOp {
+  Plus,
+  Minus,
+  Not,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+  Add,
+  Sub,
+  Mul,
+  Div,
+  Mod,
+  Lt,
+  Gt,
+  Le,
+  Ge,
+  Eq,
+  Ne,
+  And,
+  Or,
+}
+
+impl Exp {
+  pub fn new(kind: ExpKind, span: Span) -> Self {
+    Self { kind, span, ty: None }
+  }
+
+  pub fn pos(&self) -> Point {
+    *self.span.get_start_point()
+  }
+}
+
+impl Def {
+  // 常量求值之后各维的长度
+  pub fn shape(&self) -> Vec<usize> {
+    dims_shape(&self.dims)
+  }
+}
+
+impl FuncFParam {
+  // 常量求值之后省略第一维的各维长度，非数组形参返回None
+  pub fn shape(&self) -> Option<Vec<usize>> {
+    self.dims.as_ref().map(|dims| dims_shape(dims))
+  }
+}
+
+fn dims_shape(dims: &[Exp]) -> Vec<usize> {
+  dims
+    .iter()
+    .map(|dim| match dim.kind {
+      ExpKind::IntConst(len) => len as usize,
+      _ => panic!("array dimension has not been evaluated"),
+    })
+    .collect()
+}
+
+impl From<BType> for Type {
+  fn from(ty: BType) -> Self {
+    match ty {
+      BType::Int => Type::Int,
+      BType::Float => Type::Float,
+    }
+  }
+}
+
+impl From<FuncType> for Type {
+  fn from(ty: FuncType) -> Self {
+    match ty {
+      FuncType::Void => Type::Void,
+      FuncType::Int => Type::Int,
+      FuncType::Float => Type::Float,
+    }
+  }
+}
+
+impl Type {
+  // 标量类型对应的基本类型
+  pub fn as_btype(&self) -> Option<BType> {
+    match self {
+      Type::Int => Some(BType::Int),
+      Type::Float => Some(BType::Float),
+      _ => None,
+    }
+  }
+}
+
+impl BinaryOp {
+  pub fn is_logical(&self) -> bool {
+    matches!(self, BinaryOp::And | BinaryOp::Or)
+  }
+
+  pub fn is_relational(&self) -> bool {
+    matches!(
+      self,
+      BinaryOp::Lt | BinaryOp::Gt | BinaryOp::Le | BinaryOp::Ge | BinaryOp::Eq | BinaryOp::Ne
+    )
+  }
+}
+
+// 单元测试
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::test_util::lower_source;
+
+  fn main_body(unit: &CompUnit) -> &Vec<BlockItem> {
+    match unit.items.last() {
+      Some(GlobalItem::FuncDef(func)) => &func.body.items,
+      _ => panic!("expected a function"),
+    }
+  }
+
+  fn as_stmt(item: &BlockItem) -> &Stmt {
+    match item {
+      BlockItem::Stmt(stmt) => stmt,
+      _ => panic!("expected a statement"),
+    }
+  }
+
+  fn return_exp(unit: &CompUnit) -> &Exp {
+    match main_body(unit).last().map(as_stmt) {
+      Some(Stmt::Return(Some(exp), _)) => exp,
+      _ => panic!("expected a return statement"),
+    }
+  }
+
+  // 减法和除法应当左结合
+  #[test]
+  fn test_left_associative() {
+    let unit = lower_source("int main() { return 10 - 2 - 3 * 4 / 2; }");
+    let exp = return_exp(&unit);
+    let ExpKind::Binary(BinaryOp::Sub, lhs, rhs) = &exp.kind else {
+      panic!("expected subtraction, got {:?}", exp);
+    };
+    assert!(matches!(lhs.kind, ExpKind::Binary(BinaryOp::Sub, _, _)));
+    let ExpKind::Binary(BinaryOp::Div, mul, _) = &rhs.kind else {
+      panic!("expected division, got {:?}", rhs);
+    };
+    assert!(matches!(mul.kind, ExpKind::Binary(BinaryOp::Mul, _, _)));
+  }
+
+  #[test]
+  fn test_decls_and_positions() {
+    let unit = lower_source("const int N = 2, M[2] = {1, 2};\nint a[N][3] = {{1}, 2}, b;\nvoid f(int x[][3]) {}\nint main() { return 0x1f; }");
+    assert_eq!(unit.items.len(), 4);
+    let GlobalItem::Decl(consts) = &unit.items[0] else { panic!() };
+    assert!(consts.is_const);
+    assert_eq!(consts.defs.len(), 2);
+    assert_eq!(consts.defs[1].dims.len(), 1);
+
+    let GlobalItem::Decl(vars) = &unit.items[1] else { panic!() };
+    assert_eq!(vars.defs[0].ident.name, "a");
+    assert_eq!(vars.defs[0].ident.pos(), Point::new(2, 5));
+    assert_eq!(vars.defs[0].ident.span.get_end_point(), &Point::new(2, 6));
+    assert_eq!(vars.defs[0].dims.len(), 2);
+    assert!(matches!(&vars.defs[0].init, Some(InitVal::List(items, _)) if items.len() == 2));
+    assert!(vars.defs[1].init.is_none());
+
+    let GlobalItem::FuncDef(f) = &unit.items[2] else { panic!() };
+    assert_eq!(f.func_type, FuncType::Void);
+    assert_eq!(f.params[0].dims.as_ref().map(Vec::len), Some(1));
+
+    assert_eq!(return_exp(&unit).kind, ExpKind::IntConst(31));
+  }
+
+  #[test]
+  fn test_stmts() {
+    let unit = lower_source(
+      "int main() { int i = 0; while (i < 10 && i != 5) { if (i) i = i + 1; else break; } f(i, 2); return -i; }",
+    );
+    let body = main_body(&unit);
+    let Stmt::While(cond, stmt) = as_stmt(&body[1]) else { panic!() };
+    assert!(matches!(cond.kind, ExpKind::Binary(BinaryOp::And, _, _)));
+    let Stmt::Block(block) = stmt.as_ref() else { panic!() };
+    assert!(matches!(as_stmt(&block.items[0]), Stmt::If(_, _, Some(_))));
+    assert!(matches!(as_stmt(&body[2]), Stmt::Exp(Some(Exp { kind: ExpKind::Call(_, args), .. })) if args.len() == 2));
+    assert!(matches!(return_exp(&unit).kind, ExpKind::Unary(UnaryOp::Minus, _)));
+  }
+}
@@ -0,0 +1,619 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use crate::ast::*;
+
+// LLVM IR文本，直接由语义检查之后的AST生成，用作参考后端
+// 使用不透明指针（ptr），局部变量都在入口块中alloca，由LLVM自己提升到寄存器
+pub fn emit(unit: &CompUnit) -> String {
+  let mut out = String::new();
+  let mut globals = BTreeMap::new();
+  for item in &unit.items {
+    if let GlobalItem::Decl(decl) = item {
+      for def in &decl.defs {
+        let dims = def.shape();
+        let init = match &def.init {
+          Some(InitVal::Exp(exp)) => literal(decl.ty, exp),
+          Some(InitVal::List(items, _)) => {
+            let values: Vec<_> = items
+              .iter()
+              .map(|item| match item {
+                InitVal::Exp(exp) => literal(decl.ty, exp),
+                InitVal::List(..) => panic!("initializer list has not been flattened"),
+              })
+              .collect();
+            aggregate(decl.ty, &dims, &values)
+          }
+          None if dims.is_empty() => zero(decl.ty).to_string(),
+          None => "zeroinitializer".to_string(),
+        };
+        let kind = if decl.is_const { "constant" } else { "global" };
+        writeln!(
+          out,
+          "@{} = {} {} {}",
+          def.ident.name,
+          kind,
+          array_type(decl.ty, &dims),
+          init
+        )
+        .unwrap();
+        globals.insert(
+          def.ident.name.clone(),
+          VarInfo {
+            addr: format!("@{}", def.ident.name),
+            ty: decl.ty,
+            dims,
+            is_param: false,
+          },
+        );
+      }
+    }
+  }
+  if !globals.is_empty() {
+    writeln!(out).unwrap();
+  }
+
+  for item in &unit.items {
+    if let GlobalItem::FuncDef(func) = item {
+      let mut emitter = FunctionEmitter::new(&globals);
+      emitter.func_def(func);
+      out.push_str(&emitter.finish());
+      writeln!(out).unwrap();
+    }
+  }
+  out.push_str(RUNTIME_DECLS);
+  out
+}
+
+const RUNTIME_DECLS: &str = "\
+declare i32 @getint()
+declare i32 @getch()
+declare float @getfloat()
+declare i32 @getarray(ptr)
+declare i32 @getfarray(ptr)
+declare void @putint(i32)
+declare void @putch(i32)
+declare void @putfloat(float)
+declare void @putarray(i32, ptr)
+declare void @putfarray(i32, ptr)
+declare void @_sysy_starttime(i32)
+declare void @_sysy_stoptime(i32)
+declare void @llvm.memset.p0.i64(ptr, i8, i64, i1)
+";
+
+fn scalar_type(ty: BType) -> &'static str {
+  match ty {
+    BType::Int => "i32",
+    BType::Float => "float",
+  }
+}
+
+fn array_type(ty: BType, dims: &[usize]) -> String {
+  dims
+    .iter()
+    .rev()
+    .fold(scalar_type(ty).to_string(), |inner, len| format!("[{} x {}]", len, inner))
+}
+
+fn zero(ty: BType) -> &'static str {
+  match ty {
+    BType::Int => "0",
+    BType::Float => "0.0",
+  }
+}
+
+// float常量需要写成与其值相等的double的十六进制形式
+fn float_literal(v: f32) -> String {
+  format!("0x{:016X}", (v as f64).to_bits())
+}
+
+fn literal(ty: BType, exp: &Exp) -> String {
+  match (ty, &exp.kind) {
+    (BType::Int, ExpKind::IntConst(v)) => v.to_string(),
+    (BType::Float, ExpKind::FloatConst(v)) => float_literal(*v),
+    _ => panic!("global initializer has not been evaluated"),
+  }
+}
+
+// 由展开后的初始值构造嵌套的聚合常量，全为0的子数组写成zeroinitializer
+fn aggregate(ty: BType, dims: &[usize], values: &[String]) -> String {
+  if values.iter().all(|v| v == "0" || v == &float_literal(0.0)) {
+    return "zeroinitializer".to_string();
+  }
+  let inner_type = array_type(ty, &dims[1..]);
+  let stride = values.len() / dims[0];
+  let elems: Vec<_> = values
+    .chunks(stride)
+    .map(|chunk| {
+      let elem = if dims.len() == 1 {
+        chunk[0].clone()
+      } else {
+        aggregate(ty, &dims[1..], chunk)
+      };
+      format!("{} {}", inner_type, elem)
+    })
+    .collect();
+  format!("[{}]", elems.join(", "))
+}
+
+#[derive(Debug, Clone)]
+struct VarInfo {
+  // 变量的地址，数组形参为指向第一个元素的指针
+  addr: String,
+  ty: BType,
+  // 数组形参不含省略的第一维
+  dims: Vec<usize>,
+  is_param: bool,
+}
+
+// 表达式的值及其LLVM类型
+#[derive(Debug, Clone)]
+struct Value {
+  ty: &'static str,
+  repr: String,
+}
+
+impl Value {
+  fn new(ty: &'static str, repr: impl Into<String>) -> Self {
+    Self { ty, repr: repr.into() }
+  }
+}
+
+struct FunctionEmitter<'a> {
+  globals: &'a BTreeMap<String, VarInfo>,
+  scopes: Vec<BTreeMap<String, VarInfo>>,
+  allocas: String,
+  body: String,
+  reg_count: usize,
+  label_count: usize,
+  // 当前基本块已经以跳转或返回结束
+  terminated: bool,
+  // 循环的continue和break目标
+  loops: Vec<(String, String)>,
+  header: String,
+}
+
+impl<'a> FunctionEmitter<'a> {
+  fn new(globals: &'a BTreeMap<String, VarInfo>) -> Self {
+    Self {
+      globals,
+      scopes: Vec::new(),
+      allocas: String::new(),
+      body: String::new(),
+      reg_count: 0,
+      label_count: 0,
+      terminated: false,
+      loops: Vec::new(),
+      header: String::new(),
+    }
+  }
+
+  fn finish(self) -> String {
+    format!("{}\nentry:\n{}  br label %body\nbody:\n{}}}\n", self.header, self.allocas, self.body)
+  }
+
+  fn reg(&mut self) -> String {
+    self.reg_count += 1;
+    format!("%t{}", self.reg_count)
+  }
+
+  fn label(&mut self) -> String {
+    self.label_count += 1;
+    format!("L{}", self.label_count)
+  }
+
+  fn line(&mut self, text: impl AsRef<str>) {
+    self.body.push_str("  ");
+    self.body.push_str(text.as_ref());
+    self.body.push('\n');
+  }
+
+  fn terminate(&mut self, text: impl AsRef<str>) {
+    self.line(text);
+    self.terminated = true;
+  }
+
+  // 开始新的基本块，上一个块没有结束时直接落入新块
+  fn start_block(&mut self, label: &str) {
+    if !self.terminated {
+      self.line(format!("br label %{}", label));
+    }
+    self.body.push_str(&format!("{}:\n", label));
+    self.terminated = false;
+  }
+
+  fn alloca(&mut self, ty: String) -> String {
+    let reg = self.reg();
+    writeln!(self.allocas, "  {} = alloca {}", reg, ty).unwrap();
+    reg
+  }
+
+  fn declare(&mut self, name: &str, info: VarInfo) {
+    self.scopes.last_mut().unwrap().insert(name.to_string(), info);
+  }
+
+  fn lookup(&self, name: &str) -> &VarInfo {
+    self
+      .scopes
+      .iter()
+      .rev()
+      .find_map(|scope| scope.get(name))
+      .or_else(|| self.globals.get(name))
+      .expect("undeclared variable after semantic check")
+  }
+
+  fn func_def(&mut self, func: &FuncDef) {
+    self.scopes.push(BTreeMap::new());
+    let mut params = Vec::new();
+    for (index, param) in func.params.iter().enumerate() {
+      let arg = format!("%arg{}", index);
+      match param.shape() {
+        Some(dims) => {
+          params.push(format!("ptr {}", arg));
+          self.declare(
+            &param.ident.name,
+            VarInfo {
+              addr: arg,
+              ty: param.ty,
+              dims,
+              is_param: true,
+            },
+          );
+        }
+        None => {
+          let ty = scalar_type(param.ty);
+          params.push(format!("{} {}", ty, arg));
+          let addr = self.alloca(ty.to_string());
+          self.line(format!("store {} {}, ptr {}", ty, arg, addr));
+          self.declare(
+            &param.ident.name,
+            VarInfo {
+              addr,
+              ty: param.ty,
+              dims: Vec::new(),
+              is_param: false,
+            },
+          );
+        }
+      }
+    }
+    self.header = format!(
+      "define {} @{}({}) {{",
+      ret_type(func.func_type),
+      func.ident.name,
+      params.join(", ")
+    );
+
+    self.block_items(&func.body);
+    // 补上函数末尾的返回，非void函数返回0
+    if !self.terminated {
+      match func.func_type {
+        FuncType::Void => self.terminate("ret void"),
+        FuncType::Int => self.terminate("ret i32 0"),
+        FuncType::Float => self.terminate("ret float 0.0"),
+      }
+    }
+    self.scopes.pop();
+  }
+
+  fn block_items(&mut self, block: &Block) {
+    for item in &block.items {
+      match item {
+        BlockItem::Decl(decl) => self.decl(decl),
+        BlockItem::Stmt(stmt) => self.stmt(stmt),
+      }
+    }
+  }
+
+  fn decl(&mut self, decl: &Decl) {
+    for def in &decl.defs {
+      let dims = def.shape();
+      let addr = self.alloca(array_type(decl.ty, &dims));
+      match &def.init {
+        Some(InitVal::Exp(exp)) => {
+          let value = self.exp(exp);
+          self.line(format!("store {} {}, ptr {}", value.ty, value.repr, addr));
+        }
+        Some(InitVal::List(items, _)) => {
+          let size = items.len() * 4;
+          self.line(format!(
+            "call void @llvm.memset.p0.i64(ptr {}, i8 0, i64 {}, i1 false)",
+            addr, size
+          ));
+          for (index, item) in items.iter().enumerate() {
+            let InitVal::Exp(exp) = item else {
+              panic!("initializer list has not been flattened");
+            };
+            if matches!(exp.kind, ExpKind::IntConst(0)) || matches!(exp.kind, ExpKind::FloatConst(v) if v.to_bits() == 0) {
+              continue;
+            }
+            let value = self.exp(exp);
+            let elem = self.reg();
+            self.line(format!(
+              "{} = getelementptr {}, ptr {}, i32 {}",
+              elem,
+              scalar_type(decl.ty),
+              addr,
+              index
+            ));
+            self.line(format!("store {} {}, ptr {}", value.ty, value.repr, elem));
+          }
+        }
+        None => {}
+      }
+      // 初始化表达式中的同名标识符指向外层变量，所以定义完成后再声明
+      self.declare(
+        &def.ident.name,
+        VarInfo {
+          addr,
+          ty: decl.ty,
+          dims,
+          is_param: false,
+        },
+      );
+    }
+  }
+
+  fn stmt(&mut self, stmt: &Stmt) {
+    match stmt {
+      Stmt::Assign(lval, exp) => {
+        let addr = self.lval_addr(lval);
+        let value = self.exp(exp);
+        self.line(format!("store {} {}, ptr {}", value.ty, value.repr, addr));
+      }
+      Stmt::Exp(Some(exp)) => {
+        self.exp(exp);
+      }
+      Stmt::Exp(None) => {}
+      Stmt::Block(block) => {
+        self.scopes.push(BTreeMap::new());
+        self.block_items(block);
+        self.scopes.pop();
+      }
+      Stmt::If(cond, then, otherwise) => {
+        let (then_label, else_label, end) = (self.label(), self.label(), self.label());
+        let false_label = if otherwise.is_some() { &else_label } else { &end };
+        self.cond(cond, &then_label, false_label);
+        self.start_block(&then_label);
+        self.stmt(then);
+        if let Some(otherwise) = otherwise {
+          if !self.terminated {
+            self.terminate(format!("br label %{}", end));
+          }
+          self.start_block(&else_label);
+          self.stmt(otherwise);
+        }
+        self.start_block(&end);
+      }
+      Stmt::While(cond, body) => {
+        let (cond_label, body_label, end) = (self.label(), self.label(), self.label());
+        self.start_block(&cond_label);
+        self.cond(cond, &body_label, &end);
+        self.start_block(&body_label);
+        self.loops.push((cond_label.clone(), end.clone()));
+        self.stmt(body);
+        self.loops.pop();
+        if !self.terminated {
+          self.terminate(format!("br label %{}", cond_label));
+        }
+        self.start_block(&end);
+      }
+      Stmt::Break(_) | Stmt::Continue(_) => {
+        let (cond, end) = self.loops.last().cloned().expect("jump outside of a loop");
+        let target = if matches!(stmt, Stmt::Break(_)) { end } else { cond };
+        self.terminate(format!("br label %{}", target));
+        // 跳转之后的代码不可达，放在新的基本块中
+        let label = self.label();
+        self.start_block(&label);
+      }
+      Stmt::Return(exp, _) => {
+        match exp {
+          Some(exp) => {
+            let value = self.exp(exp);
+            self.terminate(format!("ret {} {}", value.ty, value.repr));
+          }
+          None => self.terminate("ret void"),
+        }
+        let label = self.label();
+        self.start_block(&label);
+      }
+    }
+  }
+
+  // 条件表达式，&&和||短路求值
+  fn cond(&mut self, exp: &Exp, then: &str, otherwise: &str) {
+    match &exp.kind {
+      ExpKind::Binary(BinaryOp::And, lhs, rhs) => {
+        let mid = self.label();
+        self.cond(lhs, &mid, otherwise);
+        self.start_block(&mid);
+        self.cond(rhs, then, otherwise);
+      }
+      ExpKind::Binary(BinaryOp::Or, lhs, rhs) => {
+        let mid = self.label();
+        self.cond(lhs, then, &mid);
+        self.start_block(&mid);
+        self.cond(rhs, then, otherwise);
+      }
+      ExpKind::Unary(UnaryOp::Not, operand) => self.cond(operand, otherwise, then),
+      _ => {
+        let value = self.exp(exp);
+        let value = self.as_bool(value);
+        self.terminate(format!("br i1 {}, label %{}, label %{}", value.repr, then, otherwise));
+      }
+    }
+  }
+
+  fn as_bool(&mut self, value: Value) -> Value {
+    let reg = self.reg();
+    match value.ty {
+      "i1" => return value,
+      "float" => self.line(format!("{} = fcmp une float {}, 0.0", reg, value.repr)),
+      _ => self.line(format!("{} = icmp ne i32 {}, 0", reg, value.repr)),
+    }
+    Value::new("i1", reg)
+  }
+
+  fn as_i32(&mut self, value: Value) -> Value {
+    if value.ty != "i1" {
+      return value;
+    }
+    let reg = self.reg();
+    self.line(format!("{} = zext i1 {} to i32", reg, value.repr));
+    Value::new("i32", reg)
+  }
+
+  // 计算左值的地址，下标不足时得到子数组的地址
+  fn lval_addr(&mut self, lval: &LVal) -> String {
+    let mut indices = Vec::new();
+    for index in &lval.indices {
+      let value = self.exp(index);
+      indices.push(format!("i32 {}", value.repr));
+    }
+    let info = self.lookup(&lval.ident.name).clone();
+    if indices.is_empty() {
+      return info.addr;
+    }
+    // 数组形参本身就是指向子数组的指针，不需要前导的0
+    if !info.is_param {
+      indices.insert(0, "i32 0".to_string());
+    }
+    let ty = array_type(info.ty, &info.dims);
+    let reg = self.reg();
+    self.line(format!(
+      "{} = getelementptr {}, ptr {}, {}",
+      reg,
+      ty,
+      info.addr,
+      indices.join(", ")
+    ));
+    reg
+  }
+
+  fn exp(&mut self, exp: &Exp) -> Value {
+    match &exp.kind {
+      ExpKind::IntConst(v) => Value::new("i32", v.to_string()),
+      ExpKind::FloatConst(v) => Value::new("float", float_literal(*v)),
+      ExpKind::LVal(lval) => {
+        let addr = self.lval_addr(lval);
+        let ty = match exp.ty {
+          Some(Type::Array(..)) => return Value::new("ptr", addr),
+          Some(Type::Float) => "float",
+          _ => "i32",
+        };
+        let reg = self.reg();
+        self.line(format!("{} = load {}, ptr {}", reg, ty, addr));
+        Value::new(ty, reg)
+      }
+      ExpKind::Call(func, args) => {
+        let mut values = Vec::new();
+        for arg in args {
+          let value = self.exp(arg);
+          let value = self.as_i32(value);
+          values.push(format!("{} {}", value.ty, value.repr));
+        }
+        let name = &func.name;
+        let ty = match exp.ty {
+          Some(Type::Int) => "i32",
+          Some(Type::Float) => "float",
+          _ => {
+            self.line(format!("call void @{}({})", name, values.join(", ")));
+            return Value::new("i32", "0");
+          }
+        };
+        let reg = self.reg();
+        self.line(format!("{} = call {} @{}({})", reg, ty, name, values.join(", ")));
+        Value::new(ty, reg)
+      }
+      ExpKind::Unary(UnaryOp::Plus, operand) => self.exp(operand),
+      ExpKind::Unary(UnaryOp::Minus, operand) => {
+        let value = self.exp(operand);
+        let value = self.as_i32(value);
+        let reg = self.reg();
+        if value.ty == "float" {
+          self.line(format!("{} = fneg float {}", reg, value.repr));
+        } else {
+          self.line(format!("{} = sub i32 0, {}", reg, value.repr));
+        }
+        Value::new(value.ty, reg)
+      }
+      ExpKind::Unary(UnaryOp::Not, operand) => {
+        let value = self.exp(operand);
+        let value = self.as_bool(value);
+        let reg = self.reg();
+        self.line(format!("{} = xor i1 {}, true", reg, value.repr));
+        Value::new("i1", reg)
+      }
+      ExpKind::Binary(op, ..) if op.is_logical() => {
+        let (then, otherwise, end) = (self.label(), self.label(), self.label());
+        self.cond(exp, &then, &otherwise);
+        self.start_block(&then);
+        self.terminate(format!("br label %{}", end));
+        self.start_block(&otherwise);
+        self.terminate(format!("br label %{}", end));
+        self.start_block(&end);
+        let reg = self.reg();
+        self.line(format!("{} = phi i1 [ true, %{} ], [ false, %{} ]", reg, then, otherwise));
+        Value::new("i1", reg)
+      }
+      ExpKind::Binary(op, lhs, rhs) => {
+        let lhs = self.exp(lhs);
+        let lhs = self.as_i32(lhs);
+        let rhs = self.exp(rhs);
+        let rhs = self.as_i32(rhs);
+        let is_float = lhs.ty == "float";
+        let (inst, result) = match (op, is_float) {
+          (BinaryOp::Add, false) => ("add", "i32"),
+          (BinaryOp::Sub, false) => ("sub", "i32"),
+          (BinaryOp::Mul, false) => ("mul", "i32"),
+          (BinaryOp::Div, false) => ("sdiv", "i32"),
+          (BinaryOp::Mod, false) => ("srem", "i32"),
+          (BinaryOp::Lt, false) => ("icmp slt", "i1"),
+          (BinaryOp::Gt, false) => ("icmp sgt", "i1"),
+          (BinaryOp::Le, false) => ("icmp sle", "i1"),
+          (BinaryOp::Ge, false) => ("icmp sge", "i1"),
+          (BinaryOp::Eq, false) => ("icmp eq", "i1"),
+          (BinaryOp::Ne, false) => ("icmp ne", "i1"),
+          (BinaryOp::Add, true) => ("fadd", "float"),
+          (BinaryOp::Sub, true) => ("fsub", "float"),
+          (BinaryOp::Mul, true) => ("fmul", "float"),
+          (BinaryOp::Div, true) => ("fdiv", "float"),
+          (BinaryOp::Lt, true) => ("fcmp olt", "i1"),
+          (BinaryOp::Gt, true) => ("fcmp ogt", "i1"),
+          (BinaryOp::Le, true) => ("fcmp ole", "i1"),
+          (BinaryOp::Ge, true) => ("fcmp oge", "i1"),
+          (BinaryOp::Eq, true) => ("fcmp oeq", "i1"),
+          (BinaryOp::Ne, true) => ("fcmp une", "i1"),
+          (BinaryOp::Mod, true) => unreachable!("'%' on float is rejected by the type checker"),
+          (BinaryOp::And | BinaryOp::Or, _) => unreachable!(),
+        };
+        let reg = self.reg();
+        self.line(format!("{} = {} {} {}, {}", reg, inst, lhs.ty, lhs.repr, rhs.repr));
+        Value::new(result, reg)
+      }
+      ExpKind::Cast(to, operand) => {
+        let value = self.exp(operand);
+        let value = self.as_i32(value);
+        let reg = self.reg();
+        match to {
+          BType::Float => {
+            self.line(format!("{} = sitofp i32 {} to float", reg, value.repr));
+            Value::new("float", reg)
+          }
+          BType::Int => {
+            self.line(format!("{} = fptosi float {} to i32", reg, value.repr));
+            Value::new("i32", reg)
+          }
+        }
+      }
+    }
+  }
+}
+
+fn ret_type(ty: FuncType) -> &'static str {
+  match ty {
+    FuncType::Void => "void",
+    FuncType::Int => "i32",
+    FuncType::Float => "float",
+  }
+}
@@ -0,0 +1,98 @@
+pub mod llvm;
+pub mod riscv;
+
+use crate::ast::CompUnit;
+use crate::ir;
+
+// 代码生成的目标
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target {
+  Riscv64,
+  Llvm,
+}
+
+impl Target {
+  pub fn from_name(name: &str) -> Option<Self> {
+    match name {
+      "riscv64" | "rv64" => Some(Target::Riscv64),
+      "llvm" | "ll" => Some(Target::Llvm),
+      _ => None,
+    }
+  }
+
+  // 汇编后端从三地址码生成，LLVM IR保留了数组类型，直接从AST生成
+  pub fn emit(&self, unit: &CompUnit) -> String {
+    match self {
+      Target::Riscv64 => riscv::emit(&ir::generate(unit)),
+      Target::Llvm => llvm::emit(unit),
+    }
+  }
+}
+
+// 单元测试
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::test_util::check_source;
+
+  fn riscv(source: &str) -> String {
+    Target::Riscv64.emit(&check_source(source))
+  }
+
+  #[test]
+  fn test_riscv_globals() {
+    let asm = riscv("int a[4] = {0, 7}; float b[2]; int main() { return a[1]; }");
+    assert!(asm.contains("  .data\n  .globl a\n  .p2align 2\na:\n  .zero 4\n  .word 7\n  .zero 8\n"));
+    assert!(asm.contains("  .bss\n  .globl b\n  .p2align 2\nb:\n  .zero 8\n"));
+  }
+
+  #[test]
+  fn test_riscv_calling_convention() {
+    let asm = riscv(
+      "float f(int a, float b, int c[]) { return a + b; }
+       int main() { int c[2]; float x = f(1, 2.5, c); putint(x); return 0; }",
+    );
+    // int和指针参数使用a寄存器，float使用fa寄存器
+    assert!(asm.contains("  sw a0, -24(s0)\n  fsw fa0, -32(s0)\n  sd a1, -40(s0)\n"));
+    assert!(asm.contains("  call f\n  fsw fa0,"));
+    // float到int的转换向0舍入
+    assert!(asm.contains("fcvt.w.s t0, ft0, rtz"));
+    assert!(asm.contains("  call putint\n"));
+  }
+
+  // 两个后端的计时函数都传入调用所在的行号
+  #[test]
+  fn test_timing_line() {
+    let source = "int main() {\n  starttime();\n  stoptime();\n  return 0;\n}";
+    let asm = riscv(source);
+    assert!(asm.contains("  li a0, 2\n  call _sysy_starttime\n"));
+    assert!(asm.contains("  li a0, 3\n  call _sysy_stoptime\n"));
+    let ll = Target::Llvm.emit(&check_source(source));
+    assert!(ll.contains("call void @_sysy_starttime(i32 2)\n"));
+    assert!(ll.contains("call void @_sysy_stoptime(i32 3)\n"));
+  }
+
+  #[test]
+  fn test_llvm_globals() {
+    let ll = Target::Llvm.emit(&check_source("const int a[2][2] = {{1}, {}}; float b = 0.5; int main() { return a[0][0]; }"));
+    assert!(ll.contains("@a = constant [2 x [2 x i32]] [[2 x i32] [i32 1, i32 0], [2 x i32] zeroinitializer]\n"));
+    assert!(ll.contains("@b = global float 0x3FE0000000000000\n"));
+    assert!(ll.contains("declare void @putint(i32)\n"));
+  }
+
+  #[test]
+  fn test_llvm_function() {
+    let ll = Target::Llvm.emit(&check_source(
+      "int f(int v[][3], float x) { int a[4] = {0, 2}; return v[1][2] + x; }
+       int main() { int v[2][3]; starttime(); return f(v, 1); }",
+    ));
+    assert!(ll.contains("define i32 @f(ptr %arg0, float %arg1) {\nentry:\n"));
+    // 数组形参的下标不需要前导的0
+    assert!(ll.contains("getelementptr [3 x i32], ptr %arg0, i32 1, i32 2\n"));
+    assert!(ll.contains("call void @llvm.memset.p0.i64(ptr %t2, i8 0, i64 16, i1 false)\n"));
+    assert!(ll.contains("sitofp i32"));
+    assert!(ll.contains("fptosi float"));
+    assert!(ll.contains("call void @_sysy_starttime(i32 2)\n"));
+    assert!(ll.contains("call i32 @f(ptr %t"));
+  }
+}
@@ -0,0 +1,494 @@
+use std::fmt::Write;
+
+use crate::ast::{BType, FuncType};
+use crate::ir::*;
+use crate::semantic::ConstValue;
+
+// RV64GC汇编，输出可直接交给GNU as
+// 不做寄存器分配：每个临时变量在栈帧中占8字节，指令执行前从栈上取出操作数，结果写回栈上
+//
+// 栈帧布局（s0为进入函数时的sp，向下增长）：
+//   s0 + 0 ...           调用者通过栈传递的参数
+//   s0 - 8               ra
+//   s0 - 16              调用者的s0
+//   s0 - 16 - 8 * (i+1)  临时变量ti
+//   ...                  局部变量
+//   sp + 0 ...           调用其他函数时通过栈传递的参数
+pub fn emit(program: &Program) -> String {
+  let mut out = String::new();
+  for global in &program.globals {
+    emit_global(&mut out, global);
+  }
+  for function in &program.functions {
+    FunctionEmitter::new(function).emit(&mut out);
+  }
+  out
+}
+
+const INT_ARGS: [&str; 8] = ["a0", "a1", "a2", "a3", "a4", "a5", "a6", "a7"];
+const FLOAT_ARGS: [&str; 8] = ["fa0", "fa1", "fa2", "fa3", "fa4", "fa5", "fa6", "fa7"];
+
+fn emit_global(out: &mut String, global: &Global) {
+  let size = global.size() * 4;
+  let values = global
+    .init
+    .as_ref()
+    .filter(|init| init.iter().any(|value| !is_zero(value)));
+  match values {
+    Some(values) => {
+      writeln!(out, "  .data").unwrap();
+      writeln!(out, "  .globl {}", global.name).unwrap();
+      writeln!(out, "  .p2align 2").unwrap();
+      writeln!(out, "{}:", global.name).unwrap();
+      // 连续的0合并为.zero
+      let mut zeros = 0;
+      for value in values {
+        if is_zero(value) {
+          zeros += 1;
+          continue;
+        }
+        if zeros > 0 {
+          writeln!(out, "  .zero {}", zeros * 4).unwrap();
+          zeros = 0;
+        }
+        writeln!(out, "  .word {}", word(value)).unwrap();
+      }
+      if zeros > 0 {
+        writeln!(out, "  .zero {}", zeros * 4).unwrap();
+      }
+    }
+    None => {
+      writeln!(out, "  .bss").unwrap();
+      writeln!(out, "  .globl {}", global.name).unwrap();
+      writeln!(out, "  .p2align 2").unwrap();
+      writeln!(out, "{}:", global.name).unwrap();
+      writeln!(out, "  .zero {}", size).unwrap();
+    }
+  }
+  writeln!(out).unwrap();
+}
+
+fn is_zero(value: &ConstValue) -> bool {
+  match value {
+    ConstValue::Int(v) => *v == 0,
+    ConstValue::Float(v) => v.to_bits() == 0,
+  }
+}
+
+fn word(value: &ConstValue) -> u32 {
+  match value {
+    ConstValue::Int(v) => *v as u32,
+    ConstValue::Float(v) => v.to_bits(),
+  }
+}
+
+// 参数的传递位置，按LP64D约定分配
+enum ArgLoc {
+  Int(&'static str),
+  Float(&'static str),
+  // 相对于栈顶的偏移
+  Stack(i64),
+}
+
+// float参数在浮点寄存器用完后使用整数寄存器，都用完后通过栈传递，每个栈上的参数占8字节
+fn arg_locations(types: &[IrType]) -> (Vec<ArgLoc>, i64) {
+  let (mut next_int, mut next_float, mut stack) = (0, 0, 0);
+  let mut locs = Vec::new();
+  for ty in types {
+    let loc = match ty {
+      IrType::Float if next_float < FLOAT_ARGS.len() => {
+        next_float += 1;
+        ArgLoc::Float(FLOAT_ARGS[next_float - 1])
+      }
+      _ if next_int < INT_ARGS.len() => {
+        next_int += 1;
+        ArgLoc::Int(INT_ARGS[next_int - 1])
+      }
+      _ => {
+        stack += 8;
+        ArgLoc::Stack(stack - 8)
+      }
+    };
+    locs.push(loc);
+  }
+  (locs, stack)
+}
+
+struct FunctionEmitter<'a> {
+  function: &'a Function,
+  // 每个局部变量相对于s0的偏移
+  local_offsets: Vec<i64>,
+  frame_size: i64,
+  out: String,
+}
+
+impl<'a> FunctionEmitter<'a> {
+  fn new(function: &'a Function) -> Self {
+    let mut offset = 16 + 8 * function.temps.len() as i64;
+    let mut local_offsets = Vec::new();
+    for local in &function.locals {
+      offset += (local.size as i64 * 4 + 7) / 8 * 8;
+      local_offsets.push(-offset);
+    }
+    // 为调用其他函数时栈上的参数留出空间
+    let outgoing = function
+      .code
+      .iter()
+      .filter_map(|inst| match inst {
+        Inst::Call { args, .. } => {
+          let types: Vec<_> = args.iter().map(|arg| function.temp_type(arg)).collect();
+          Some(arg_locations(&types).1)
+        }
+        _ => None,
+      })
+      .max()
+      .unwrap_or(0);
+    let frame_size = (offset + outgoing + 15) / 16 * 16;
+    Self {
+      function,
+      local_offsets,
+      frame_size,
+      out: String::new(),
+    }
+  }
+
+  fn line(&mut self, text: impl AsRef<str>) {
+    self.out.push_str("  ");
+    self.out.push_str(text.as_ref());
+    self.out.push('\n');
+  }
+
+  fn temp_offset(&self, temp: Temp) -> i64 {
+    -16 - 8 * (temp.0 as i64 + 1)
+  }
+
+  // 访问base + offset处的内存，偏移超出12位立即数时借助t3计算地址
+  fn mem(&mut self, op: &str, reg: &str, base: &str, offset: i64) {
+    if (-2048..2048).contains(&offset) {
+      self.line(format!("{} {}, {}({})", op, reg, offset, base));
+    } else {
+      self.line(format!("li t3, {}", offset));
+      self.line(format!("add t3, {}, t3", base));
+      self.line(format!("{} {}, 0(t3)", op, reg));
+    }
+  }
+
+  fn add_imm(&mut self, dst: &str, src: &str, imm: i64) {
+    if (-2048..2048).contains(&imm) {
+      self.line(format!("addi {}, {}, {}", dst, src, imm));
+    } else {
+      self.line(format!("li t3, {}", imm));
+      self.line(format!("add {}, {}, t3", dst, src));
+    }
+  }
+
+  fn load_temp(&mut self, reg: &str, temp: Temp) {
+    let offset = self.temp_offset(temp);
+    let op = match self.function.temps[temp.0] {
+      IrType::Int => "lw",
+      IrType::Float => "flw",
+      IrType::Ptr => "ld",
+    };
+    self.mem(op, reg, "s0", offset);
+  }
+
+  fn store_temp(&mut self, reg: &str, temp: Temp) {
+    let offset = self.temp_offset(temp);
+    let op = match self.function.temps[temp.0] {
+      IrType::Int => "sw",
+      IrType::Float => "fsw",
+      IrType::Ptr => "sd",
+    };
+    self.mem(op, reg, "s0", offset);
+  }
+
+  // 把int或指针操作数放入整数寄存器
+  fn load_int(&mut self, reg: &str, operand: &Operand) {
+    match operand {
+      Operand::Temp(temp) => self.load_temp(reg, *temp),
+      Operand::Int(v) => self.line(format!("li {}, {}", reg, v)),
+      Operand::Float(v) => self.line(format!("li {}, {}", reg, v.to_bits() as i32)),
+    }
+  }
+
+  fn load_float(&mut self, reg: &str, operand: &Operand) {
+    match operand {
+      Operand::Temp(temp) => self.load_temp(reg, *temp),
+      Operand::Float(v) => {
+        self.line(format!("li t2, {}", v.to_bits() as i32));
+        self.line(format!("fmv.w.x {}, t2", reg));
+      }
+      Operand::Int(v) => {
+        self.line(format!("li t2, {}", v));
+        self.line(format!("fcvt.s.w {}, t2", reg));
+      }
+    }
+  }
+
+  // 把操作数的原始位放入整数寄存器，float以其位模式表示
+  fn load_bits(&mut self, reg: &str, operand: &Operand) {
+    match operand {
+      Operand::Temp(temp) if self.function.temps[temp.0] == IrType::Float => {
+        let offset = self.temp_offset(*temp);
+        self.mem("lw", reg, "s0", offset);
+      }
+      operand => self.load_int(reg, operand),
+    }
+  }
+
+  fn emit(mut self, out: &mut String) {
+    let function = self.function;
+    writeln!(out, "  .text").unwrap();
+    writeln!(out, "  .globl {}", function.name).unwrap();
+    writeln!(out, "  .p2align 1").unwrap();
+    writeln!(out, "  .type {}, @function", function.name).unwrap();
+    writeln!(out, "{}:", function.name).unwrap();
+
+    self.line("addi sp, sp, -16");
+    self.line("sd ra, 8(sp)");
+    self.line("sd s0, 0(sp)");
+    self.line("addi s0, sp, 16");
+    let rest = self.frame_size - 16;
+    self.add_imm("sp", "sp", -rest);
+
+    // 把形参保存到对应的临时变量中
+    let (locs, _) = arg_locations(&function.params);
+    for (index, loc) in locs.iter().enumerate() {
+      let temp = Temp(index);
+      let offset = self.temp_offset(temp);
+      let store = if function.params[index] == IrType::Ptr { "sd" } else { "sw" };
+      match loc {
+        ArgLoc::Int(reg) => self.mem(store, reg, "s0", offset),
+        ArgLoc::Float(reg) => self.store_temp(reg, temp),
+        ArgLoc::Stack(stack) => {
+          let load = if function.params[index] == IrType::Ptr { "ld" } else { "lw" };
+          self.mem(load, "t0", "s0", *stack);
+          self.mem(store, "t0", "s0", offset);
+        }
+      }
+    }
+
+    for inst in &function.code {
+      self.inst(inst);
+    }
+    writeln!(out, "{}", self.out.trim_end_matches('\n')).unwrap();
+    writeln!(out, "  .size {}, .-{}", function.name, function.name).unwrap();
+    writeln!(out).unwrap();
+  }
+
+  fn epilogue(&mut self) {
+    self.line("addi sp, s0, -16");
+    self.line("ld ra, 8(sp)");
+    self.line("ld s0, 0(sp)");
+    self.line("addi sp, sp, 16");
+    self.line("ret");
+  }
+
+  fn inst(&mut self, inst: &Inst) {
+    match inst {
+      Inst::Binary {
+        op,
+        ty: BType::Int,
+        dst,
+        lhs,
+        rhs,
+      } => {
+        self.load_int("t0", lhs);
+        self.load_int("t1", rhs);
+        match op {
+          BinOp::Add => self.line("addw t0, t0, t1"),
+          BinOp::Sub => self.line("subw t0, t0, t1"),
+          BinOp::Mul => self.line("mulw t0, t0, t1"),
+          BinOp::Div => self.line("divw t0, t0, t1"),
+          BinOp::Mod => self.line("remw t0, t0, t1"),
+          BinOp::Lt => self.line("slt t0, t0, t1"),
+          BinOp::Gt => self.line("slt t0, t1, t0"),
+          BinOp::Le => {
+            self.line("slt t0, t1, t0");
+            self.line("xori t0, t0, 1");
+          }
+          BinOp::Ge => {
+            self.line("slt t0, t0, t1");
+            self.line("xori t0, t0, 1");
+          }
+          BinOp::Eq => {
+            self.line("subw t0, t0, t1");
+            self.line("seqz t0, t0");
+          }
+          BinOp::Ne => {
+            self.line("subw t0, t0, t1");
+            self.line("snez t0, t0");
+          }
+        }
+        self.store_temp("t0", *dst);
+      }
+      Inst::Binary {
+        op,
+        ty: BType::Float,
+        dst,
+        lhs,
+        rhs,
+      } => {
+        self.load_float("ft0", lhs);
+        self.load_float("ft1", rhs);
+        let (text, result) = match op {
+          BinOp::Add => ("fadd.s ft0, ft0, ft1", "ft0"),
+          BinOp::Sub => ("fsub.s ft0, ft0, ft1", "ft0"),
+          BinOp::Mul => ("fmul.s ft0, ft0, ft1", "ft0"),
+          BinOp::Div => ("fdiv.s ft0, ft0, ft1", "ft0"),
+          BinOp::Mod => unreachable!("'%' on float is rejected by the type checker"),
+          BinOp::Lt => ("flt.s t0, ft0, ft1", "t0"),
+          BinOp::Gt => ("flt.s t0, ft1, ft0", "t0"),
+          BinOp::Le => ("fle.s t0, ft0, ft1", "t0"),
+          BinOp::Ge => ("fle.s t0, ft1, ft0", "t0"),
+          BinOp::Eq | BinOp::Ne => ("feq.s t0, ft0, ft1", "t0"),
+        };
+        self.line(text);
+        if *op == BinOp::Ne {
+          self.line("xori t0, t0, 1");
+        }
+        self.store_temp(result, *dst);
+      }
+      Inst::Unary {
+        op,
+        ty: BType::Int,
+        dst,
+        src,
+      } => {
+        self.load_int("t0", src);
+        match op {
+          UnOp::Neg => self.line("negw t0, t0"),
+          UnOp::Not => self.line("seqz t0, t0"),
+        }
+        self.store_temp("t0", *dst);
+      }
+      Inst::Unary {
+        op,
+        ty: BType::Float,
+        dst,
+        src,
+      } => {
+        self.load_float("ft0", src);
+        match op {
+          UnOp::Neg => {
+            self.line("fneg.s ft0, ft0");
+            self.store_temp("ft0", *dst);
+          }
+          UnOp::Not => {
+            self.line("fmv.w.x ft1, zero");
+            self.line("feq.s t0, ft0, ft1");
+            self.store_temp("t0", *dst);
+          }
+        }
+      }
+      Inst::Convert {
+        to: BType::Float,
+        dst,
+        src,
+      } => {
+        self.load_int("t0", src);
+        self.line("fcvt.s.w ft0, t0");
+        self.store_temp("ft0", *dst);
+      }
+      Inst::Convert {
+        to: BType::Int,
+        dst,
+        src,
+      } => {
+        self.load_float("ft0", src);
+        // 向0舍入，与C的转换一致
+        self.line("fcvt.w.s t0, ft0, rtz");
+        self.store_temp("t0", *dst);
+      }
+      Inst::Addr { dst, var } => {
+        match var {
+          Var::Global(name) => self.line(format!("la t0, {}", name)),
+          Var::Local(id) => {
+            let offset = self.local_offsets[*id];
+            self.add_imm("t0", "s0", offset);
+          }
+        }
+        self.store_temp("t0", *dst);
+      }
+      Inst::Offset {
+        dst,
+        base,
+        index,
+        scale,
+      } => {
+        self.load_int("t0", base);
+        self.load_int("t1", index);
+        self.line(format!("li t2, {}", scale * 4));
+        self.line("mul t1, t1, t2");
+        self.line("add t0, t0, t1");
+        self.store_temp("t0", *dst);
+      }
+      Inst::Load { dst, addr } => {
+        self.load_int("t0", addr);
+        let reg = match self.function.temps[dst.0] {
+          IrType::Float => {
+            self.line("flw ft0, 0(t0)");
+            "ft0"
+          }
+          _ => {
+            self.line("lw t1, 0(t0)");
+            "t1"
+          }
+        };
+        self.store_temp(reg, *dst);
+      }
+      Inst::Store { src, addr } => {
+        self.load_int("t0", addr);
+        self.load_bits("t1", src);
+        self.line("sw t1, 0(t0)");
+      }
+      Inst::Call { dst, func, args } => self.call(*dst, func, args),
+      Inst::Label(label) => self.out.push_str(&format!(".L{}:\n", label.0)),
+      Inst::Jump(label) => self.line(format!("j .L{}", label.0)),
+      Inst::Branch { cond, then, otherwise } => {
+        // 条件跳转的范围有限，用无条件跳转到达目标
+        self.load_int("t0", cond);
+        self.line("bnez t0, 1f");
+        self.line(format!("j .L{}", otherwise.0));
+        self.out.push_str("1:\n");
+        self.line(format!("j .L{}", then.0));
+      }
+      Inst::Return(value) => {
+        match (value, self.function.ret) {
+          (Some(value), FuncType::Float) => self.load_float("fa0", value),
+          (Some(value), _) => self.load_int("a0", value),
+          (None, _) => {}
+        }
+        self.epilogue();
+      }
+    }
+  }
+
+  fn call(&mut self, dst: Option<Temp>, func: &str, args: &[Operand]) {
+    let types: Vec<_> = args.iter().map(|arg| self.function.temp_type(arg)).collect();
+    let (locs, _) = arg_locations(&types);
+    // 先写栈上的参数，再填寄存器，避免寄存器被覆盖
+    for (arg, loc) in args.iter().zip(&locs) {
+      if let ArgLoc::Stack(offset) = loc {
+        self.load_bits("t0", arg);
+        let store = if self.function.temp_type(arg) == IrType::Ptr { "sd" } else { "sw" };
+        self.mem(store, "t0", "sp", *offset);
+      }
+    }
+    for (arg, loc) in args.iter().zip(&locs) {
+      match loc {
+        ArgLoc::Int(reg) => self.load_bits(reg, arg),
+        ArgLoc::Float(reg) => self.load_float(reg, arg),
+        ArgLoc::Stack(_) => {}
+      }
+    }
+    self.line(format!("call {}", func));
+    if let Some(dst) = dst {
+      let reg = match self.function.temps[dst.0] {
+        IrType::Float => "fa0",
+        _ => "a0",
+      };
+      self.store_temp(reg, dst);
+    }
+  }
+}
@@ -0,0 +1,102 @@
+use std::fmt::Write;
+
+// 超过这个规模的输出不再求最长公共子序列，中间部分整体视为不同
+const MAX_LCS_CELLS: usize = 4_000_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+  Equal,
+  Delete,
+  Insert,
+}
+
+// 按行比较，输出每一段连续差异，行号为期望输出中的位置；相同时返回空串
+pub fn diff(expected: &str, actual: &str) -> String {
+  let old: Vec<_> = expected.lines().collect();
+  let new: Vec<_> = actual.lines().collect();
+  let ops = edit_script(&old, &new);
+  if ops.iter().all(|op| *op == Op::Equal) {
+    return String::new();
+  }
+
+  let mut out = String::from("--- expected\n+++ actual\n");
+  let (mut i, mut j) = (0, 0);
+  let mut in_hunk = false;
+  for op in ops {
+    match op {
+      Op::Equal => {
+        i += 1;
+        j += 1;
+        in_hunk = false;
+        continue;
+      }
+      _ if !in_hunk => {
+        writeln!(out, "@@ line {} @@", i + 1).unwrap();
+        in_hunk = true;
+      }
+      _ => {}
+    }
+    if op == Op::Delete {
+      writeln!(out, "-{}", old[i]).unwrap();
+      i += 1;
+    } else {
+      writeln!(out, "+{}", new[j]).unwrap();
+      j += 1;
+    }
+  }
+  out
+}
+
+fn edit_script(old: &[&str], new: &[&str]) -> Vec<Op> {
+  let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+  let suffix = old[prefix..]
+    .iter()
+    .rev()
+    .zip(new[prefix..].iter().rev())
+    .take_while(|(a, b)| a == b)
+    .count();
+  let old_mid = &old[prefix..old.len() - suffix];
+  let new_mid = &new[prefix..new.len() - suffix];
+
+  let mut ops = vec![Op::Equal; prefix];
+  if (old_mid.len() + 1) * (new_mid.len() + 1) > MAX_LCS_CELLS {
+    ops.extend(std::iter::repeat_n(Op::Delete, old_mid.len()));
+    ops.extend(std::iter::repeat_n(Op::Insert, new_mid.len()));
+  } else {
+    ops.extend(lcs_script(old_mid, new_mid));
+  }
+  ops.extend(std::iter::repeat_n(Op::Equal, suffix));
+  ops
+}
+
+// lcs[i][j]为old[i..]和new[j..]的最长公共子序列长度
+fn lcs_script(old: &[&str], new: &[&str]) -> Vec<Op> {
+  let (n, m) = (old.len(), new.len());
+  let mut lcs = vec![vec![0usize; m + 1]; n + 1];
+  for i in (0..n).rev() {
+    for j in (0..m).rev() {
+      lcs[i][j] = if old[i] == new[j] {
+        lcs[i + 1][j + 1] + 1
+      } else {
+        lcs[i + 1][j].max(lcs[i][j + 1])
+      };
+    }
+  }
+
+  let mut ops = Vec::new();
+  let (mut i, mut j) = (0, 0);
+  while i < n || j < m {
+    if i < n && j < m && old[i] == new[j] {
+      ops.push(Op::Equal);
+      i += 1;
+      j += 1;
+    } else if j == m || (i < n && lcs[i + 1][j] >= lcs[i][j + 1]) {
+      ops.push(Op::Delete);
+      i += 1;
+    } else {
+      ops.push(Op::Insert);
+      j += 1;
+    }
+  }
+  ops
+}
@@ -0,0 +1,310 @@
+mod diff;
+
+use std::fmt::Display;
+use std::fs;
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+
+use crate::ast::{self, CompUnit};
+use crate::interpreter;
+use crate::lexer::{LexerKind, TokenStream};
+use crate::parser::LR1Parser;
+use crate::preprocess::{Preprocessed, Preprocessor};
+use crate::semantic;
+
+pub use diff::diff;
+
+// SysY测试集的运行器：每个用例由同名的.sy源程序、可选的.in输入和.out期望输出组成，
+// .out为程序的标准输出，之后另起一行是main返回值的低8位
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TestCase {
+  pub name: String,
+  pub source: PathBuf,
+  pub input: Option<PathBuf>,
+  pub expected: PathBuf,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+  Pass,
+  // 输出或返回值与期望不一致
+  Mismatch { expected: String, actual: String },
+  CompileError(String),
+  RuntimeError(String),
+  // 用例文件无法读取
+  Broken(String),
+}
+
+impl Outcome {
+  pub fn is_pass(&self) -> bool {
+    matches!(self, Outcome::Pass)
+  }
+}
+
+impl Display for Outcome {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Outcome::Pass => write!(f, "ok"),
+      Outcome::Mismatch { expected, actual } => write!(f, "output mismatch\n{}", diff(expected, actual)),
+      Outcome::CompileError(message) => write!(f, "compile error\n{}", message),
+      Outcome::RuntimeError(message) => write!(f, "runtime error: {}", message),
+      Outcome::Broken(message) => write!(f, "{}", message),
+    }
+  }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Summary {
+  pub passed: usize,
+  pub failed: usize,
+}
+
+impl Summary {
+  pub fn add(&mut self, outcome: &Outcome) {
+    if outcome.is_pass() {
+      self.passed += 1;
+    } else {
+      self.failed += 1;
+    }
+  }
+
+  pub fn total(&self) -> usize {
+    self.passed + self.failed
+  }
+}
+
+impl Display for Summary {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{} passed, {} failed, {} total", self.passed, self.failed, self.total())
+  }
+}
+
+// 递归查找dir下所有带.out的.sy文件，按路径排序；用例名为相对dir的路径去掉扩展名
+pub fn discover(dir: &Path) -> io::Result<Vec<TestCase>> {
+  let mut cases = Vec::new();
+  walk(dir, dir, &mut cases)?;
+  cases.sort_by(|a, b| a.source.cmp(&b.source));
+  Ok(cases)
+}
+
+fn walk(root: &Path, dir: &Path, cases: &mut Vec<TestCase>) -> io::Result<()> {
+  for entry in fs::read_dir(dir)? {
+    let path = entry?.path();
+    if path.is_dir() {
+      walk(root, &path, cases)?;
+      continue;
+    }
+    if path.extension().is_none_or(|ext| ext != "sy") {
+      continue;
+    }
+    let expected = path.with_extension("out");
+    if !expected.is_file() {
+      continue;
+    }
+    let input = Some(path.with_extension("in")).filter(|input| input.is_file());
+    let name = path
+      .strip_prefix(root)
+      .unwrap_or(&path)
+      .with_extension("")
+      .to_string_lossy()
+      .into_owned();
+    cases.push(TestCase {
+      name,
+      source: path,
+      input,
+      expected,
+    });
+  }
+  Ok(())
+}
+
+// 期望输出的格式：标准输出不以换行结尾时先补一个换行，再输出返回值
+pub fn format_output(stdout: &str, ret: i32) -> String {
+  let mut output = stdout.to_string();
+  if !output.is_empty() && !output.ends_with('\n') {
+    output.push('\n');
+  }
+  output.push_str(&format!("{}\n", ret as u8));
+  output
+}
+
+// 忽略换行符的差异和末尾的空白
+fn normalize(text: &str) -> String {
+  text.replace("\r\n", "\n").trim_end().to_string()
+}
+
+pub struct Runner {
+  tables: LR1Parser,
+  lexer: LexerKind,
+  // #include <...> 的搜索路径，与其他命令的-I相同
+  include_dirs: Vec<PathBuf>,
+}
+
+impl Runner {
+  // 分析表由调用者按选定的冲突策略构造一次，所有用例共用
+  pub fn new(tables: LR1Parser, lexer: LexerKind, include_dirs: Vec<PathBuf>) -> Self {
+    Self {
+      tables,
+      lexer,
+      include_dirs,
+    }
+  }
+
+  pub fn run_case(&self, case: &TestCase) -> Outcome {
+    let read = |path: &Path| fs::read(path).map_err(|e| format!("cannot read `{}`: {}", path.display(), e));
+    let files = read(&case.source).and_then(|source| {
+      let input = match &case.input {
+        Some(path) => read(path)?,
+        None => Vec::new(),
+      };
+      Ok((String::from_utf8_lossy(&source).into_owned(), input, read(&case.expected)?))
+    });
+    let (source, input, expected) = match files {
+      Ok(files) => files,
+      Err(message) => return Outcome::Broken(message),
+    };
+
+    // 相对路径的 #include 先在用例所在的目录中查找；每个用例的宏定义互不影响
+    let mut preprocessor = Preprocessor::new();
+    for dir in &self.include_dirs {
+      preprocessor.add_include_dir(dir);
+    }
+    let source = match preprocessor.process(&case.source.display().to_string(), &source) {
+      Ok(source) => source,
+      Err(err) => return Outcome::CompileError(err.to_string()),
+    };
+    let unit = match self.compile(&source) {
+      Ok(unit) => unit,
+      Err(errors) => return Outcome::CompileError(errors.join("\n")),
+    };
+
+    // 解释器的内部错误不应中断整个测试集
+    let mut stdout = Vec::new();
+    let result = panic::catch_unwind(AssertUnwindSafe(|| interpreter::run(&unit, &input[..], &mut stdout)));
+    let ret = match result {
+      Ok(Ok(ret)) => ret,
+      Ok(Err(err)) => {
+        return Outcome::RuntimeError(match err.error_pos {
+          Some(pos) => format!("{}: {}", source.line_map.locate(*pos.get_row(), *pos.get_col()), err),
+          None => err.to_string(),
+        })
+      }
+      Err(_) => return Outcome::RuntimeError("interpreter panicked".to_string()),
+    };
+
+    let expected = normalize(&String::from_utf8_lossy(&expected));
+    let actual = normalize(&format_output(&String::from_utf8_lossy(&stdout), ret));
+    if expected == actual {
+      Outcome::Pass
+    } else {
+      Outcome::Mismatch { expected, actual }
+    }
+  }
+
+  // 完整的前端：词法、语法、AST降级和语义检查，错误信息带原文件中的位置
+  fn compile(&self, source: &Preprocessed) -> Result<CompUnit, Vec<String>> {
+    let lines = &source.line_map;
+    let mut lr1 = LR1Parser::new();
+    lr1.action_table = self.tables.action_table.clone();
+    lr1.goto_table = self.tables.goto_table.clone();
+    let mut lexer_errors = Vec::new();
+    let tokens = self
+      .lexer
+      .stream(&source.text, &mut lexer_errors)
+      .skip_trivia()
+      .filter(|token| !token.is_error());
+    let lr1 = lr1.parse_stream(tokens);
+    if !lexer_errors.is_empty() {
+      return Err(lexer_errors.iter().map(|err| format!("{}: {}", lines.locate(err.row, err.col), err)).collect());
+    }
+    let Some(tree) = lr1.get_tree() else {
+      return Err(
+        lr1
+          .get_errors()
+          .iter()
+          .map(|err| format!("{}: {}", lines.locate(*err.error_pos.get_row(), *err.error_pos.get_col()), err))
+          .collect(),
+      );
+    };
+
+    let mut unit = ast::lower(tree).map_err(|err| {
+      let pos = err.error_span.map(|span| *span.get_start_point());
+      let (row, col) = pos.map_or((0, 0), |p| (*p.get_row(), *p.get_col()));
+      vec![format!("{}: {}", lines.locate(row, col), err)]
+    })?;
+    let errors = semantic::check(&mut unit);
+    if errors.is_empty() {
+      Ok(unit)
+    } else {
+      Err(
+        errors
+          .iter()
+          .map(|err| format!("{}: {}", lines.locate(*err.pos().get_row(), *err.pos().get_col()), err))
+          .collect(),
+      )
+    }
+  }
+}
+
+// 单元测试
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::parser::Grammar;
+  use crate::test_util::PARSER_LOCK;
+
+  #[test]
+  fn test_format_output() {
+    assert_eq!(format_output("", 0), "0\n");
+    assert_eq!(format_output("5", 3), "5\n3\n");
+    assert_eq!(format_output("1 2\n", -1), "1 2\n255\n");
+  }
+
+  #[test]
+  fn test_diff() {
+    let text = diff("a\nb\nc\nd", "a\nx\nc\nd\ne");
+    assert_eq!(text, "--- expected\n+++ actual\n@@ line 2 @@\n-b\n+x\n@@ line 5 @@\n+e\n");
+    assert_eq!(diff("same", "same"), "");
+  }
+
+  #[test]
+  fn test_testcases() {
+    let cases = discover(Path::new("../testcases")).unwrap();
+    assert!(!cases.is_empty());
+    let tables = {
+      let _guard = PARSER_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+      let mut grammar = Grammar::new();
+      grammar.grammar_load("../g3.txt");
+      let mut lr1 = LR1Parser::new();
+      lr1.compute_lr1_item_sets(&grammar);
+      lr1.construct_parsing_table(&grammar).expect("conflicts are resolved by default");
+      lr1
+    };
+    // 每个词法分析器都应通过全部用例
+    for name in ["cursor", "relexer", "generated"] {
+      let mut copy = LR1Parser::new();
+      copy.action_table = tables.action_table.clone();
+      copy.goto_table = tables.goto_table.clone();
+      let runner = Runner::new(copy, LexerKind::from_name(name, "../g3.txt").unwrap(), Vec::new());
+      for case in &cases {
+        let outcome = runner.run_case(case);
+        assert!(outcome.is_pass(), "{} {}: {}", name, case.name, outcome);
+      }
+    }
+
+    // #include <...> 在给出的目录中查找
+    let dir = std::env::temp_dir().join(format!("rcp_conformance_{}", std::process::id()));
+    fs::create_dir_all(dir.join("inc")).unwrap();
+    fs::write(dir.join("inc/k.h"), "#define K 3\n").unwrap();
+    fs::write(dir.join("a.sy"), "#include <k.h>\nint main() { return K; }\n").unwrap();
+    fs::write(dir.join("a.out"), "3\n").unwrap();
+    let case = &discover(&dir).unwrap()[0];
+    let runner = Runner::new(tables, LexerKind::Cursor, vec![dir.join("inc")]);
+    assert_eq!(runner.run_case(case), Outcome::Pass);
+    let runner = Runner::new(runner.tables, LexerKind::Cursor, Vec::new());
+    assert!(matches!(runner.run_case(case), Outcome::CompileError(message) if message.contains("`k.h`")));
+    fs::remove_dir_all(&dir).unwrap();
+  }
+}
@@ -0,0 +1,370 @@
+mod render;
+
+use std::fmt::Display;
+
+use crate::ast::LowerError;
+use crate::interpreter::RuntimeError;
+use crate::lexer::{LexerError, LexerErrorKind};
+use crate::parser::{Conflict, GrammarIssue, IssueKind, ParserError};
+use crate::preprocess::PreprocessError;
+use crate::semantic::{self, SemanticError};
+use crate::token::{Point, Span};
+
+pub use render::{Format, Renderer};
+
+// 各阶段共用的诊断信息：级别、错误码、消息、位置，以及附加的说明和修改建议。
+// 错误码按阶段分段：E00xx预处理，E01xx词法，E02xx语法，E03xx AST，E04xx语义，E05xx运行时，E06xx文法文件
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+  Error,
+  Warning,
+  Note,
+}
+
+impl Display for Severity {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Severity::Error => write!(f, "error"),
+      Severity::Warning => write!(f, "warning"),
+      Severity::Note => write!(f, "note"),
+    }
+  }
+}
+
+// 起点的行列号（从1开始）和下划线覆盖的字符数
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Location {
+  pub row: usize,
+  pub col: usize,
+  pub len: usize,
+}
+
+impl Location {
+  pub fn point(point: &Point) -> Self {
+    Self {
+      row: *point.get_row(),
+      col: *point.get_col(),
+      len: 1,
+    }
+  }
+
+  // 跨行的区间只标出第一行中的部分
+  pub fn span(span: &Span) -> Self {
+    let start = span.get_start_point();
+    let end = span.get_end_point();
+    let len = if end.get_row() == start.get_row() {
+      end.get_col().saturating_sub(*start.get_col()).max(1)
+    } else {
+      usize::MAX
+    };
+    Self { len, ..Self::point(start) }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+  pub severity: Severity,
+  pub code: Option<&'static str>,
+  pub message: String,
+  pub location: Option<Location>,
+  // 位置不在渲染的源文件中时（如被包含的文件）给出文件名，此时不显示源代码行
+  pub file: Option<String>,
+  pub notes: Vec<String>,
+  pub help: Vec<String>,
+}
+
+impl Diagnostic {
+  pub fn new(severity: Severity, message: impl Into<String>) -> Self {
+    Self {
+      severity,
+      code: None,
+      message: message.into(),
+      location: None,
+      file: None,
+      notes: Vec::new(),
+      help: Vec::new(),
+    }
+  }
+
+  pub fn error(message: impl Into<String>) -> Self {
+    Self::new(Severity::Error, message)
+  }
+
+  pub fn warning(message: impl Into<String>) -> Self {
+    Self::new(Severity::Warning, message)
+  }
+
+  pub fn with_code(mut self, code: &'static str) -> Self {
+    self.code = Some(code);
+    self
+  }
+
+  pub fn with_location(mut self, location: Location) -> Self {
+    self.location = Some(location);
+    self
+  }
+
+  pub fn with_file(mut self, file: impl Into<String>) -> Self {
+    self.file = Some(file.into());
+    self
+  }
+
+  pub fn with_note(mut self, note: impl Into<String>) -> Self {
+    self.notes.push(note.into());
+    self
+  }
+
+  pub fn with_help(mut self, help: impl Into<String>) -> Self {
+    self.help.push(help.into());
+    self
+  }
+
+  pub fn is_error(&self) -> bool {
+    self.severity == Severity::Error
+  }
+}
+
+impl From<&PreprocessError> for Diagnostic {
+  fn from(err: &PreprocessError) -> Self {
+    let diagnostic = Diagnostic::error(&err.message).with_code("E0001").with_file(&err.file);
+    match err.line {
+      0 => diagnostic,
+      line => diagnostic.with_location(Location { row: line, col: 1, len: 0 }),
+    }
+  }
+}
+
+impl From<&LexerError> for Diagnostic {
+  fn from(err: &LexerError) -> Self {
+    let code = match err.kind {
+      LexerErrorKind::MalformedNumber => "E0101",
+      LexerErrorKind::IntegerOverflow => "E0102",
+      LexerErrorKind::FloatOverflow => "E0103",
+      LexerErrorKind::MalformedIdentifier => "E0104",
+      LexerErrorKind::MalformedCharacter => "E0105",
+      LexerErrorKind::UnterminatedString => "E0106",
+      LexerErrorKind::UnterminatedBlockComment => "E0107",
+      LexerErrorKind::InvalidEscape => "E0108",
+      LexerErrorKind::UnknownCharacter => "E0109",
+    };
+    // 没有记录区间的错误只标出起点
+    let location = if err.span.is_empty() {
+      Location::point(&Point::new(err.row, err.col))
+    } else {
+      Location::span(&err.span)
+    };
+    let diagnostic = Diagnostic::error(err.to_string()).with_code(code).with_location(location);
+    match err.kind {
+      LexerErrorKind::IntegerOverflow => diagnostic.with_note("integer constants must fit in 32 bits"),
+      LexerErrorKind::UnterminatedString => diagnostic.with_help("add the closing `\"`"),
+      LexerErrorKind::UnterminatedBlockComment => diagnostic.with_help("add `*/` to close the comment"),
+      _ => diagnostic,
+    }
+  }
+}
+
+impl From<&ParserError> for Diagnostic {
+  fn from(err: &ParserError) -> Self {
+    let location = err.error_span.map_or(Location::point(&err.error_pos), |span| Location::span(&span));
+    Diagnostic::error(err.to_string()).with_code("E0201").with_location(location)
+  }
+}
+
+impl From<&LowerError> for Diagnostic {
+  fn from(err: &LowerError) -> Self {
+    let diagnostic = Diagnostic::error(err.to_string()).with_code("E0301");
+    match &err.error_span {
+      Some(span) => diagnostic.with_location(Location::span(span)),
+      None => diagnostic,
+    }
+  }
+}
+
+impl From<&SemanticError> for Diagnostic {
+  fn from(err: &SemanticError) -> Self {
+    use semantic::ErrorType::*;
+    let code = match &err.error_type {
+      Undeclared(_) => "E0401",
+      Redeclared(..) => "E0402",
+      NotAFunction(_) => "E0403",
+      NotAVariable(_) => "E0404",
+      ArgumentCount { .. } => "E0405",
+      AssignToConst(_) => "E0406",
+      DimensionMismatch { .. } => "E0407",
+      ArrayAsValue(_) => "E0408",
+      BreakOutsideLoop => "E0409",
+      ContinueOutsideLoop => "E0410",
+      ModOnFloat => "E0411",
+      VoidValue(_) => "E0412",
+      ReturnValueInVoid(_) => "E0413",
+      MissingReturnValue(_) => "E0414",
+      ArgumentType { .. } => "E0415",
+      NonIntegerIndex => "E0416",
+      NotConstant => "E0417",
+      NonPositiveDimension(_) => "E0418",
+      TooManyInitializers(_) => "E0419",
+      InvalidInitializer(_) => "E0420",
+    };
+    let location = Location::span(&err.error_span);
+    match &err.error_type {
+      // 之前的声明位置作为说明，不放在消息中
+      Redeclared(name, Some(pos)) => Diagnostic::error(format!("redeclaration of '{}'", name))
+        .with_code(code)
+        .with_location(location)
+        .with_note(format!("'{}' was previously declared at {}:{}", name, pos.get_row(), pos.get_col())),
+      ModOnFloat => Diagnostic::error(err.to_string())
+        .with_code(code)
+        .with_location(location)
+        .with_help("`%` is only defined for int operands"),
+      BreakOutsideLoop | ContinueOutsideLoop => Diagnostic::error(err.to_string())
+        .with_code(code)
+        .with_location(location)
+        .with_note("SysY only has `while` loops"),
+      _ => Diagnostic::error(err.to_string()).with_code(code).with_location(location),
+    }
+  }
+}
+
+impl From<&RuntimeError> for Diagnostic {
+  fn from(err: &RuntimeError) -> Self {
+    let diagnostic = Diagnostic::error(err.to_string()).with_code("E0501");
+    match &err.error_pos {
+      Some(pos) => diagnostic.with_location(Location::point(pos)),
+      None => diagnostic,
+    }
+  }
+}
+
+// 位置是文法文件中的整行
+impl From<&GrammarIssue> for Diagnostic {
+  fn from(issue: &GrammarIssue) -> Self {
+    let code = match &issue.kind {
+      IssueKind::UndefinedSymbol(_) => "E0601",
+      IssueKind::DuplicateHead { .. } => "E0602",
+      IssueKind::Unreachable(_) => "E0603",
+      IssueKind::Nonproductive(_) => "E0604",
+      IssueKind::UnusedToken(_) => "E0605",
+      IssueKind::MissingStart(_) => "E0606",
+      IssueKind::Malformed(_) => "E0607",
+    };
+    let severity = if issue.is_error() { Severity::Error } else { Severity::Warning };
+    let diagnostic = Diagnostic::new(severity, issue.kind.to_string())
+      .with_code(code)
+      .with_location(Location {
+        row: issue.line,
+        col: 1,
+        len: usize::MAX,
+      });
+    match &issue.kind {
+      IssueKind::DuplicateHead { head, .. } => {
+        diagnostic.with_help(format!("join the alternatives of `{}` on one line with ` | `", head))
+      }
+      _ => diagnostic,
+    }
+  }
+}
+
+// 分析表的冲突在第一个参与归约的产生式所在的行报告，默认已经消解，是警告
+impl From<&Conflict> for Diagnostic {
+  fn from(conflict: &Conflict) -> Self {
+    let diagnostic = Diagnostic::warning(conflict.message())
+      .with_code("E0608")
+      .with_location(Location {
+        row: conflict.line,
+        col: 1,
+        len: usize::MAX,
+      });
+    conflict
+      .item_lines()
+      .into_iter()
+      .fold(diagnostic, Diagnostic::with_note)
+      .with_help(conflict.resolution())
+  }
+}
+
+// 单元测试
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::lexer::Cursor;
+  use crate::source::SourceFile;
+  use crate::test_util::lower_source;
+
+  #[test]
+  fn test_plain() {
+    let source = SourceFile::new("main.sy", "int main() {\n  return a + 1;\n}\n");
+    let diagnostic = Diagnostic::error("use of undeclared identifier 'a'")
+      .with_code("E0401")
+      .with_location(Location { row: 2, col: 10, len: 1 })
+      .with_note("declared nowhere")
+      .with_help("declare `a` first");
+    let text = Renderer::new(Format::Plain, &source).render(&diagnostic);
+    assert_eq!(
+      text,
+      "error[E0401]: use of undeclared identifier 'a'\n \
+       --> main.sy:2:10\n  \
+       |\n\
+       2 |   return a + 1;\n  \
+       |          ^\n  \
+       = note: declared nowhere\n  \
+       = help: declare `a` first\n"
+    );
+
+    // 没有位置的诊断只有一行消息
+    let text = Renderer::new(Format::Plain, &source).render(&Diagnostic::warning("unused"));
+    assert_eq!(text, "warning: unused\n");
+  }
+
+  #[test]
+  fn test_lexer_error() {
+    let text = "int x = 1;\n\tx = 0x;\n/* never closed\n";
+    let (_, errors) = Cursor::new(text).tokenize();
+    let source = SourceFile::new("a.sy", text);
+    let renderer = Renderer::new(Format::Plain, &source);
+    let rendered: Vec<_> = errors.iter().map(|e| renderer.render(&Diagnostic::from(e))).collect();
+    // 制表符原样保留，下划线覆盖整个出错的词素
+    assert!(rendered[0].starts_with("error[E0101]: malformed number\n --> a.sy:2:6\n"));
+    assert!(rendered[0].contains("2 | \tx = 0x;\n  | \t    ^^\n"));
+    // 跨行的区间标到行尾
+    assert!(rendered[1].contains("3 | /* never closed\n  | ^^^^^^^^^^^^^^^\n"));
+    assert!(rendered[1].ends_with("= help: add `*/` to close the comment\n"));
+  }
+
+  #[test]
+  fn test_semantic_error() {
+    let text = "int main() {\n  break;\n  return count + 1;\n}\n";
+    let errors = semantic::analyze(&lower_source(text));
+    let source = SourceFile::new("s.sy", text);
+    let renderer = Renderer::new(Format::Plain, &source);
+    let rendered: Vec<_> = errors.iter().map(|e| renderer.render(&Diagnostic::from(e))).collect();
+    // 下划线覆盖出错的关键字和标识符，而不只是第一个字符
+    assert!(rendered[0].starts_with("error[E0409]: 'break' outside of a loop\n --> s.sy:2:3\n"));
+    assert!(rendered[0].contains("2 |   break;\n  |   ^^^^^\n"));
+    assert!(rendered[1].starts_with("error[E0401]: use of undeclared identifier 'count'\n --> s.sy:3:10\n"));
+    assert!(rendered[1].contains("3 |   return count + 1;\n  |          ^^^^^\n"));
+  }
+
+  #[test]
+  fn test_color_and_json() {
+    let source = SourceFile::new("m.sy", "int a = \"x\";\n");
+    let diagnostic = Diagnostic::error("bad \"string\"")
+      .with_code("E0106")
+      .with_location(Location { row: 1, col: 9, len: 3 })
+      .with_note("line\tbreak");
+    let text = Renderer::new(Format::Color, &source).render(&diagnostic);
+    assert!(text.starts_with("\x1b[1;31merror[E0106]\x1b[0m\x1b[1m: bad \"string\"\x1b[0m\n"));
+    assert!(text.contains("\x1b[1;31m^^^\x1b[0m"));
+
+    let json = Renderer::new(Format::Json, &source).render(&diagnostic);
+    assert_eq!(
+      json,
+      "{\"severity\":\"error\",\"code\":\"E0106\",\"message\":\"bad \\\"string\\\"\",\"file\":\"m.sy\",\
+       \"line\":1,\"column\":9,\"end_column\":12,\"notes\":[\"line\\tbreak\"],\"help\":[]}\n"
+    );
+    let json = Renderer::new(Format::Json, &source).render(&Diagnostic::warning("w"));
+    assert_eq!(json, "{\"severity\":\"warning\",\"code\":null,\"message\":\"w\",\"notes\":[],\"help\":[]}\n");
+    assert_eq!(Format::from_name("json"), Some(Format::Json));
+    assert_eq!(Format::from_name("xml"), None);
+  }
+}
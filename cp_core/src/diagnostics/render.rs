@@ -0,0 +1,178 @@
+use std::fmt::Write;
+
+use crate::diagnostics::{Diagnostic, Location, Severity};
+use crate::preprocess::LineMap;
+use crate::source::SourceFile;
+
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const BLUE: &str = "\x1b[1;34m";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Format {
+  #[default]
+  Plain,
+  // 带ANSI颜色，用于终端
+  Color,
+  // 每条诊断一行JSON，供其他工具读取
+  Json,
+}
+
+impl Format {
+  pub fn from_name(name: &str) -> Option<Self> {
+    match name {
+      "plain" => Some(Format::Plain),
+      "color" => Some(Format::Color),
+      "json" => Some(Format::Json),
+      _ => None,
+    }
+  }
+}
+
+// 把诊断渲染成文本；位置是source中的行列号，有行映射时显示为预处理之前的文件和行号
+pub struct Renderer<'a> {
+  format: Format,
+  source: &'a SourceFile,
+  line_map: Option<&'a LineMap>,
+}
+
+impl<'a> Renderer<'a> {
+  pub fn new(format: Format, source: &'a SourceFile) -> Self {
+    Self {
+      format,
+      source,
+      line_map: None,
+    }
+  }
+
+  pub fn with_line_map(mut self, line_map: &'a LineMap) -> Self {
+    self.line_map = Some(line_map);
+    self
+  }
+
+  pub fn render(&self, diagnostic: &Diagnostic) -> String {
+    match self.format {
+      Format::Json => self.json(diagnostic),
+      _ => self.text(diagnostic),
+    }
+  }
+
+  // 位置对应的文件名和行号
+  fn origin(&self, diagnostic: &Diagnostic, row: usize) -> (String, usize) {
+    if let Some(file) = &diagnostic.file {
+      return (file.clone(), row);
+    }
+    match self.line_map.and_then(|map| map.origin(row)) {
+      Some(origin) => (origin.file.clone(), origin.line),
+      None => (self.source.get_name().to_string(), row),
+    }
+  }
+
+  // 出错的源代码行，位置不在source中时没有
+  fn snippet(&self, diagnostic: &Diagnostic, location: &Location) -> Option<&str> {
+    match diagnostic.file {
+      Some(_) => None,
+      None => self.source.line(location.row),
+    }
+  }
+
+  fn paint(&self, color: &str, text: &str) -> String {
+    match self.format {
+      Format::Color => format!("{}{}{}", color, text, RESET),
+      _ => text.to_string(),
+    }
+  }
+
+  fn text(&self, diagnostic: &Diagnostic) -> String {
+    let color = match diagnostic.severity {
+      Severity::Error => "\x1b[1;31m",
+      Severity::Warning => "\x1b[1;33m",
+      Severity::Note => "\x1b[1;36m",
+    };
+    let mut label = diagnostic.severity.to_string();
+    if let Some(code) = diagnostic.code {
+      label = format!("{}[{}]", label, code);
+    }
+    let mut out = format!(
+      "{}{}\n",
+      self.paint(color, &label),
+      self.paint(BOLD, &format!(": {}", diagnostic.message))
+    );
+
+    let mut width = 1;
+    if let Some(location) = &diagnostic.location {
+      let (file, line) = self.origin(diagnostic, location.row);
+      width = line.to_string().len();
+      let _ = writeln!(out, "{:width$}{} {}:{}:{}", "", self.paint(BLUE, "-->"), file, line, location.col);
+      if let Some(text) = self.snippet(diagnostic, location) {
+        let bar = self.paint(BLUE, "|");
+        let _ = writeln!(out, "{:width$} {}", "", bar);
+        let _ = writeln!(out, "{} {} {}", self.paint(BLUE, &format!("{:width$}", line)), bar, text);
+        let (indent, len) = underline(text, location);
+        let carets = self.paint(color, &"^".repeat(len));
+        let _ = writeln!(out, "{:width$} {} {}{}", "", bar, indent, carets);
+      }
+    }
+    for (kind, messages) in [("note", &diagnostic.notes), ("help", &diagnostic.help)] {
+      for message in messages {
+        let _ = writeln!(out, "{:width$} {} {}: {}", "", self.paint(BLUE, "="), self.paint(BOLD, kind), message);
+      }
+    }
+    out
+  }
+
+  fn json(&self, diagnostic: &Diagnostic) -> String {
+    let mut out = format!("{{\"severity\":{}", quote(&diagnostic.severity.to_string()));
+    let code = diagnostic.code.map_or("null".to_string(), quote);
+    let _ = write!(out, ",\"code\":{},\"message\":{}", code, quote(&diagnostic.message));
+    if let Some(location) = &diagnostic.location {
+      let (file, line) = self.origin(diagnostic, location.row);
+      let len = self.snippet(diagnostic, location).map_or(location.len, |text| underline(text, location).1);
+      let _ = write!(
+        out,
+        ",\"file\":{},\"line\":{},\"column\":{},\"end_column\":{}",
+        quote(&file),
+        line,
+        location.col,
+        location.col + len
+      );
+    }
+    let list = |items: &[String]| items.iter().map(|item| quote(item)).collect::<Vec<_>>().join(",");
+    let _ = writeln!(out, ",\"notes\":[{}],\"help\":[{}]}}", list(&diagnostic.notes), list(&diagnostic.help));
+    out
+  }
+}
+
+// 下划线前的缩进和下划线的长度。缩进中保留制表符，使下划线与源代码对齐；
+// 下划线至少一个字符，不超过行尾
+fn underline(text: &str, location: &Location) -> (String, usize) {
+  let start = location.col.saturating_sub(1);
+  let indent = text
+    .chars()
+    .chain(std::iter::repeat(' '))
+    .take(start)
+    .map(|ch| if ch == '\t' { '\t' } else { ' ' })
+    .collect();
+  let rest = text.chars().count().saturating_sub(start);
+  (indent, location.len.min(rest).max(1))
+}
+
+// JSON字符串
+fn quote(text: &str) -> String {
+  let mut out = String::from("\"");
+  for ch in text.chars() {
+    match ch {
+      '"' => out.push_str("\\\""),
+      '\\' => out.push_str("\\\\"),
+      '\n' => out.push_str("\\n"),
+      '\r' => out.push_str("\\r"),
+      '\t' => out.push_str("\\t"),
+      ch if (ch as u32) < 0x20 => {
+        let _ = write!(out, "\\u{:04x}", ch as u32);
+      }
+      ch => out.push(ch),
+    }
+  }
+  out.push('"');
+  out
+}
@@ -0,0 +1,49 @@
+// 随机生成由词素片段拼接成的输入，片段之间可能没有空白，以便检查相邻词素的切分
+
+// 关键字、标识符、各种进制和形式的数、运算符、界符、字符和字符串、注释，以及一些非法字符
+const FRAGMENTS: &[&str] = &[
+  "int", "float", "char", "void", "const", "if", "else", "while", "for", "break", "continue", "return",
+  "a", "_b1", "main", "intx", "returned", "i0",
+  "0", "7", "10", "010", "0777", "08", "0x1F", "0XaB", "123456", "1.5", "0.25", ".5", "3.", "1e5", "2.5E-3",
+  "+", "-", "*", "/", "%", "=", "==", "!=", "<", ">", "<=", ">=", "!", "&&", "||",
+  "(", ")", "[", "]", "{", "}", ",", ";",
+  "'a'", "'\\n'", "\"str\"", "\"a;b\"",
+  "// line comment\n", "/* block */", "/* multi\nline */",
+  "$", "@", "#", "&", "|",
+];
+
+const SEPARATORS: &[&str] = &["", " ", " ", "\t", "\n", "\r\n"];
+
+// xorshift64*，同一个种子总是产生同样的序列，便于复现失败的用例
+pub struct Generator {
+  state: u64,
+}
+
+impl Generator {
+  pub fn new(seed: u64) -> Self {
+    // 乘奇数是双射，不同的种子得到不同的初始状态；状态不能为0
+    let state = seed.wrapping_add(1).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+    Self { state: state.max(1) }
+  }
+
+  fn next_u64(&mut self) -> u64 {
+    self.state ^= self.state >> 12;
+    self.state ^= self.state << 25;
+    self.state ^= self.state >> 27;
+    self.state.wrapping_mul(0x2545_F491_4F6C_DD1D)
+  }
+
+  fn pick<'a>(&mut self, items: &[&'a str]) -> &'a str {
+    items[(self.next_u64() % items.len() as u64) as usize]
+  }
+
+  // 由count个片段组成的输入
+  pub fn source(&mut self, count: usize) -> String {
+    let mut out = String::new();
+    for _ in 0..count {
+      out.push_str(self.pick(FRAGMENTS));
+      out.push_str(self.pick(SEPARATORS));
+    }
+    out
+  }
+}
@@ -0,0 +1,24 @@
+// 按字符缩小输入：从一半长度开始尝试删去连续的一段，删去后仍然满足fails就保留删除，
+// 一轮都删不掉时段长减半，直到单个字符也删不掉为止。要求fails(source)成立
+pub fn minimize(source: &str, fails: impl Fn(&str) -> bool) -> String {
+  let mut chars: Vec<char> = source.chars().collect();
+  let mut chunk = chars.len() / 2;
+  while chunk > 0 {
+    let mut removed = false;
+    let mut start = 0;
+    while start < chars.len() {
+      let end = (start + chunk).min(chars.len());
+      let candidate: String = chars[..start].iter().chain(&chars[end..]).collect();
+      if fails(&candidate) {
+        chars.drain(start..end);
+        removed = true;
+      } else {
+        start += chunk;
+      }
+    }
+    if !removed {
+      chunk /= 2;
+    }
+  }
+  chars.into_iter().collect()
+}
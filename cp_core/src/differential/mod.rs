@@ -0,0 +1,241 @@
+mod generate;
+mod minimize;
+
+use std::fmt::Display;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::lexer::{LexerKind, Token};
+
+pub use generate::Generator;
+pub use minimize::minimize;
+
+// 词法分析器的差分测试：同一输入分别交给Cursor和ReLexer，逐个比较词素，
+// 以Cursor的结果为基准报告ReLexer的每一处不同
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Divergence {
+  // 区间相同，类型不同
+  Kind { cursor: Token, relexer: Token },
+  // 区间相同，值不同
+  Value { cursor: Token, relexer: Token },
+  // 区间相同，行列号不同
+  Position { cursor: Token, relexer: Token },
+  // 词素边界不同：两边各自的一段词素覆盖同一段源程序
+  Boundary { cursor: Vec<Token>, relexer: Vec<Token> },
+}
+
+impl Divergence {
+  // 出现分歧的字节偏移
+  pub fn offset(&self) -> usize {
+    let token = match self {
+      Divergence::Kind { cursor, .. } | Divergence::Value { cursor, .. } | Divergence::Position { cursor, .. } => {
+        Some(cursor)
+      }
+      Divergence::Boundary { cursor, relexer } => cursor.first().or(relexer.first()),
+    };
+    token.and_then(|t| *t.get_span()).map_or(0, |span| *span.get_start())
+  }
+}
+
+impl Display for Divergence {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Divergence::Kind { cursor, relexer } => write!(
+        f,
+        "kind of {:?} at {}: cursor {:?}, relexer {:?}",
+        cursor.get_value(),
+        position(cursor),
+        cursor.get_type(),
+        relexer.get_type()
+      ),
+      Divergence::Value { cursor, relexer } => write!(
+        f,
+        "value at {}: cursor {:?}, relexer {:?}",
+        position(cursor),
+        cursor.get_value(),
+        relexer.get_value()
+      ),
+      Divergence::Position { cursor, relexer } => write!(
+        f,
+        "position of {:?}: cursor {}, relexer {}",
+        cursor.get_value(),
+        position(cursor),
+        position(relexer)
+      ),
+      Divergence::Boundary { cursor, relexer } => {
+        let values = |tokens: &[Token]| tokens.iter().map(|t| t.get_value().clone()).collect::<Vec<_>>();
+        let at = cursor.first().or(relexer.first()).map_or_else(String::new, position);
+        write!(f, "boundary at {}: cursor {:?}, relexer {:?}", at, values(cursor), values(relexer))
+      }
+    }
+  }
+}
+
+fn position(token: &Token) -> String {
+  token.get_pos().map_or_else(|| "?".to_string(), |p| format!("{}:{}", p.get_row(), p.get_col()))
+}
+
+// 空白的切分方式无关紧要（Cursor逐个字符，ReLexer把\r\n作为一个），不参与比较；
+// 注释和Err词素都参与比较
+fn significant(kind: LexerKind, source: &str) -> Vec<Token> {
+  let (tokens, _) = kind.tokenize(source);
+  tokens.into_iter().filter(|t| !t.is_whitespace()).collect()
+}
+
+fn range(token: &Token) -> (usize, usize) {
+  token.get_span().map_or((0, 0), |span| (*span.get_start(), *span.get_end()))
+}
+
+// 按字节偏移对齐两边的词素：区间相同的逐项比较，不同的一直读到两边在同一位置结束为止
+pub fn compare(source: &str) -> Vec<Divergence> {
+  let cursor = significant(LexerKind::Cursor, source);
+  let relexer = significant(LexerKind::ReLexer, source);
+  let mut divergences = Vec::new();
+  let (mut i, mut j) = (0, 0);
+  while i < cursor.len() || j < relexer.len() {
+    if let (Some(a), Some(b)) = (cursor.get(i), relexer.get(j)) {
+      if range(a) == range(b) {
+        if a.get_type() != b.get_type() {
+          divergences.push(Divergence::Kind { cursor: a.clone(), relexer: b.clone() });
+        } else if a.get_value() != b.get_value() {
+          divergences.push(Divergence::Value { cursor: a.clone(), relexer: b.clone() });
+        } else if a.get_pos() != b.get_pos() {
+          divergences.push(Divergence::Position { cursor: a.clone(), relexer: b.clone() });
+        }
+        i += 1;
+        j += 1;
+        continue;
+      }
+    }
+
+    // 两边各读一个，之后总是推进结束位置较前的一边；一边读完时另一边剩下的词素都属于这处分歧
+    let (start_i, start_j) = (i, j);
+    let mut end_a = cursor.get(i).map(|t| range(t).1);
+    let mut end_b = relexer.get(j).map(|t| range(t).1);
+    i += end_a.is_some() as usize;
+    j += end_b.is_some() as usize;
+    while end_a != end_b {
+      let want_cursor = match (end_a, end_b) {
+        (Some(a), Some(b)) => a < b,
+        (end_a, _) => end_a.is_some(),
+      };
+      if want_cursor && i < cursor.len() {
+        end_a = Some(range(&cursor[i]).1);
+        i += 1;
+      } else if j < relexer.len() {
+        end_b = Some(range(&relexer[j]).1);
+        j += 1;
+      } else if i < cursor.len() {
+        end_a = Some(range(&cursor[i]).1);
+        i += 1;
+      } else {
+        break;
+      }
+    }
+    divergences.push(Divergence::Boundary {
+      cursor: cursor[start_i..i].to_vec(),
+      relexer: relexer[start_j..j].to_vec(),
+    });
+  }
+  divergences
+}
+
+// 缩小一个有分歧的输入，保持至少一处分歧
+pub fn minimize_divergence(source: &str) -> String {
+  minimize(source, |candidate| !compare(candidate).is_empty())
+}
+
+// 递归查找dir下所有.sy文件作为语料，按路径排序
+pub fn corpus(dir: &Path) -> io::Result<Vec<PathBuf>> {
+  let mut files = Vec::new();
+  walk(dir, &mut files)?;
+  files.sort();
+  Ok(files)
+}
+
+fn walk(dir: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+  for entry in fs::read_dir(dir)? {
+    let path = entry?.path();
+    if path.is_dir() {
+      walk(&path, files)?;
+    } else if path.extension().is_some_and(|ext| ext == "sy") {
+      files.push(path);
+    }
+  }
+  Ok(())
+}
+
+// 单元测试
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_compare() {
+    let source = "int main() {\n  // 注释\r\n  while (a >= 0x1F) { a = a % 3 - 1.5; break; }\n  return 0;\n}\n";
+    assert_eq!(compare(source), []);
+
+    // ReLexer不认识转义字符，把字符常量拆成了几段
+    let divergences = compare("c = '\\n';");
+    assert_eq!(divergences.len(), 1);
+    let Divergence::Boundary { cursor, relexer } = &divergences[0] else {
+      panic!("expected a boundary divergence, got {:?}", divergences[0]);
+    };
+    assert_eq!(cursor.iter().map(|t| t.get_value().as_str()).collect::<Vec<_>>(), ["'\\n'"]);
+    assert_eq!(relexer.iter().map(|t| t.get_value().as_str()).collect::<Vec<_>>(), ["'", "\\", "n", "'"]);
+    assert_eq!(divergences[0].offset(), 4);
+    assert_eq!(divergences[0].to_string(), r#"boundary at 1:5: cursor ["'\\n'"], relexer ["'", "\\", "n", "'"]"#);
+
+    // ReLexer不认识单个的&
+    let divergences = compare("a & b");
+    assert!(matches!(&divergences[..], [Divergence::Kind { .. }]), "{:?}", divergences);
+
+    // ReLexer不区分错误种类，但同一个字符都产生Err词素
+    assert_eq!(compare("a $ b"), []);
+  }
+
+  #[test]
+  fn test_minimize() {
+    let source = "int main() {\n  char c = '\\n';\n  return a;\n}\n";
+    let minimized = minimize_divergence(source);
+    assert!(!compare(&minimized).is_empty());
+    assert!(minimized.len() <= 3, "{:?}", minimized);
+
+    // 找不到更小的输入时保持原样
+    assert_eq!(minimize("abc", |s| s == "abc"), "abc");
+    assert_eq!(minimize("xaxbx", |s| s.contains('a') && s.contains('b')), "ab");
+  }
+
+  #[test]
+  fn test_generator() {
+    let mut first = Generator::new(42);
+    let mut second = Generator::new(42);
+    for _ in 0..10 {
+      assert_eq!(first.source(20), second.source(20));
+    }
+    // 随机输入上的分歧都能复现，缩小后依然存在
+    let mut generator = Generator::new(7);
+    for _ in 0..50 {
+      let source = generator.source(30);
+      let divergences = compare(&source);
+      if !divergences.is_empty() {
+        let minimized = minimize_divergence(&source);
+        assert!(minimized.len() <= source.len());
+        assert!(!compare(&minimized).is_empty());
+      }
+    }
+  }
+
+  #[test]
+  fn test_corpus() {
+    let files = corpus(Path::new("../testcases")).unwrap();
+    assert!(!files.is_empty());
+    for file in files {
+      let source = fs::read_to_string(&file).unwrap();
+      let divergences = compare(&source);
+      assert!(divergences.is_empty(), "{}: {}", file.display(), divergences[0]);
+    }
+  }
+}
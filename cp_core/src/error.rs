@@ -1,19 +1,63 @@
+use std::fmt::Display;
+
+use crate::token::Span;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LexerErrorKind {
+  MalformedNumber,
+  // 整数超过32位，或浮点数超过f32的范围
+  IntegerOverflow,
+  FloatOverflow,
+  MalformedIdentifier,
+  MalformedCharacter,
+  UnterminatedString,
+  UnterminatedBlockComment,
+  InvalidEscape,
+  UnknownCharacter,
+}
+
+impl Display for LexerErrorKind {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      LexerErrorKind::MalformedNumber => write!(f, "malformed number"),
+      LexerErrorKind::IntegerOverflow => write!(f, "integer literal is too large"),
+      LexerErrorKind::FloatOverflow => write!(f, "float literal is out of range"),
+      LexerErrorKind::MalformedIdentifier => write!(f, "malformed identifier"),
+      LexerErrorKind::MalformedCharacter => write!(f, "malformed character literal"),
+      LexerErrorKind::UnterminatedString => write!(f, "unterminated string"),
+      LexerErrorKind::UnterminatedBlockComment => write!(f, "unterminated block comment"),
+      LexerErrorKind::InvalidEscape => write!(f, "invalid escape sequence"),
+      LexerErrorKind::UnknownCharacter => write!(f, "unknown character"),
+    }
+  }
+}
+
+// row和col为出错词素的起点，span覆盖已经读入的出错部分
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct LexerError {
-  pub message: String,
+  pub kind: LexerErrorKind,
   pub row: usize,
   pub col: usize,
+  pub span: Span,
 }
 
 impl LexerError {
-  pub fn new(message: String, row: usize, col: usize) -> Self {
+  pub fn new(kind: LexerErrorKind, row: usize, col: usize) -> Self {
     Self {
-      message,
+      kind,
       row,
       col,
+      span: Span::default(),
     }
   }
 }
 
+impl Display for LexerError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{}", self.kind)
+  }
+}
+
 struct ParserError {
   pub message: String,
   pub line: usize,
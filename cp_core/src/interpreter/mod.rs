@@ -0,0 +1,517 @@
+mod runtime;
+
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::io::{BufRead, Write};
+
+use crate::ast::*;
+use crate::token::Point;
+
+// 解释执行的栈空间，递归较深的程序也能运行。
+// 每次调用前检查已用的栈，剩余不足STACK_RESERVE时报告运行时错误而不是栈溢出
+const STACK_SIZE: usize = 1 << 30;
+const STACK_RESERVE: usize = 64 << 20;
+
+// 执行语义检查之后的程序，从input读入、向output输出，返回main的返回值
+pub fn run<R: BufRead + Send, W: Write + Send>(unit: &CompUnit, input: R, output: W) -> Result<i32, RuntimeError> {
+  std::thread::scope(|scope| {
+    std::thread::Builder::new()
+      .stack_size(STACK_SIZE)
+      .spawn_scoped(scope, move || Interpreter::new(unit, input, output).run())
+      .expect("failed to spawn the interpreter thread")
+      .join()
+      .expect("interpreter thread panicked")
+  })
+}
+
+// 当前栈帧中一个局部变量的地址
+fn stack_address() -> usize {
+  let marker = 0u8;
+  std::hint::black_box(&marker) as *const u8 as usize
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Value {
+  Int(i32),
+  Float(f32),
+  // 数组元素在内存中的下标
+  Ptr(usize),
+}
+
+impl Value {
+  fn as_int(self) -> i32 {
+    match self {
+      Value::Int(v) => v,
+      Value::Float(v) => v as i32,
+      Value::Ptr(addr) => addr as i32,
+    }
+  }
+
+  fn as_float(self) -> f32 {
+    match self {
+      Value::Float(v) => v,
+      value => value.as_int() as f32,
+    }
+  }
+
+  fn as_ptr(self) -> usize {
+    match self {
+      Value::Ptr(addr) => addr,
+      value => value.as_int() as usize,
+    }
+  }
+
+  fn is_true(self) -> bool {
+    match self {
+      Value::Float(v) => v != 0.0,
+      value => value.as_int() != 0,
+    }
+  }
+
+  fn zero(ty: BType) -> Self {
+    match ty {
+      BType::Int => Value::Int(0),
+      BType::Float => Value::Float(0.0),
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RuntimeError {
+  pub message: String,
+  pub error_pos: Option<Point>,
+}
+
+impl Display for RuntimeError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{}", self.message)
+  }
+}
+
+fn error<T>(message: impl Into<String>, pos: Point) -> Result<T, RuntimeError> {
+  Err(RuntimeError {
+    message: message.into(),
+    error_pos: Some(pos),
+  })
+}
+
+// 变量在内存中的起始位置和各维长度，数组形参的第一维长度未知，记为0
+#[derive(Debug, Clone)]
+struct Var {
+  addr: usize,
+  dims: Vec<usize>,
+}
+
+enum Flow {
+  Normal,
+  Break,
+  Continue,
+  Return(Option<Value>),
+}
+
+pub struct Interpreter<'a, R, W> {
+  unit: &'a CompUnit,
+  functions: BTreeMap<&'a str, &'a FuncDef>,
+  globals: BTreeMap<&'a str, Var>,
+  // 当前函数的作用域，调用时整体换出
+  scopes: Vec<BTreeMap<&'a str, Var>>,
+  // 所有变量都放在同一块内存中，函数和块退出时回收其局部变量
+  memory: Vec<Value>,
+  // 解释器线程开始执行时的栈地址
+  stack_base: usize,
+  input: R,
+  output: W,
+}
+
+impl<'a, R: BufRead, W: Write> Interpreter<'a, R, W> {
+  pub fn new(unit: &'a CompUnit, input: R, output: W) -> Self {
+    Self {
+      unit,
+      functions: BTreeMap::new(),
+      globals: BTreeMap::new(),
+      scopes: Vec::new(),
+      memory: Vec::new(),
+      stack_base: 0,
+      input,
+      output,
+    }
+  }
+
+  // 只在栈大小为STACK_SIZE的线程中执行，见run函数
+  fn run(mut self) -> Result<i32, RuntimeError> {
+    self.stack_base = stack_address();
+    for item in &self.unit.items {
+      match item {
+        GlobalItem::Decl(decl) => {
+          for def in &decl.defs {
+            let var = self.alloc(decl.ty, def)?;
+            self.globals.insert(&def.ident.name, var);
+          }
+        }
+        GlobalItem::FuncDef(func) => {
+          self.functions.insert(&func.ident.name, func);
+        }
+      }
+    }
+    let Some(main) = self.functions.get("main").copied() else {
+      return Err(RuntimeError {
+        message: "no 'main' function".to_string(),
+        error_pos: None,
+      });
+    };
+    let ret = self.call_func(main, Vec::new())?;
+    self.output.flush().map_err(|e| RuntimeError {
+      message: format!("cannot write output: {}", e),
+      error_pos: None,
+    })?;
+    Ok(ret.map_or(0, Value::as_int))
+  }
+
+  fn lookup(&self, name: &str) -> &Var {
+    self
+      .scopes
+      .iter()
+      .rev()
+      .find_map(|scope| scope.get(name))
+      .or_else(|| self.globals.get(name))
+      .expect("undeclared variable after semantic check")
+  }
+
+  // 为变量分配内存并执行初始化
+  fn alloc(&mut self, ty: BType, def: &'a Def) -> Result<Var, RuntimeError> {
+    let dims = def.shape();
+    let addr = self.memory.len();
+    let size: usize = dims.iter().product();
+    self.memory.resize(addr + size, Value::zero(ty));
+    match &def.init {
+      Some(InitVal::Exp(exp)) => self.memory[addr] = self.eval(exp)?,
+      Some(InitVal::List(items, _)) => {
+        for (offset, item) in items.iter().enumerate() {
+          if let InitVal::Exp(exp) = item {
+            self.memory[addr + offset] = self.eval(exp)?;
+          }
+        }
+      }
+      None => {}
+    }
+    Ok(Var { addr, dims })
+  }
+
+  fn call_func(&mut self, func: &'a FuncDef, args: Vec<Value>) -> Result<Option<Value>, RuntimeError> {
+    if self.stack_base.abs_diff(stack_address()) > STACK_SIZE - STACK_RESERVE {
+      return error("call stack overflow", func.ident.pos());
+    }
+    let mark = self.memory.len();
+    let saved = std::mem::replace(&mut self.scopes, vec![BTreeMap::new()]);
+
+    for (param, arg) in func.params.iter().zip(args) {
+      let var = match param.shape() {
+        Some(shape) => {
+          let mut dims = vec![0];
+          dims.extend(shape);
+          Var { addr: arg.as_ptr(), dims }
+        }
+        None => {
+          self.memory.push(arg);
+          Var {
+            addr: self.memory.len() - 1,
+            dims: Vec::new(),
+          }
+        }
+      };
+      self.scopes[0].insert(&param.ident.name, var);
+    }
+    let flow = self.block_items(&func.body);
+
+    self.scopes = saved;
+    self.memory.truncate(mark);
+    match flow? {
+      Flow::Return(value) => Ok(value),
+      // 非void函数执行到末尾时返回0
+      _ => Ok(match func.func_type {
+        FuncType::Void => None,
+        FuncType::Int => Some(Value::Int(0)),
+        FuncType::Float => Some(Value::Float(0.0)),
+      }),
+    }
+  }
+
+  fn block_items(&mut self, block: &'a Block) -> Result<Flow, RuntimeError> {
+    for item in &block.items {
+      match item {
+        BlockItem::Decl(decl) => {
+          for def in &decl.defs {
+            let var = self.alloc(decl.ty, def)?;
+            self.scopes.last_mut().unwrap().insert(&def.ident.name, var);
+          }
+        }
+        BlockItem::Stmt(stmt) => match self.stmt(stmt)? {
+          Flow::Normal => {}
+          flow => return Ok(flow),
+        },
+      }
+    }
+    Ok(Flow::Normal)
+  }
+
+  fn stmt(&mut self, stmt: &'a Stmt) -> Result<Flow, RuntimeError> {
+    match stmt {
+      Stmt::Assign(lval, exp) => {
+        let addr = self.lval_addr(lval)?;
+        let value = self.eval(exp)?;
+        self.memory[addr] = value;
+      }
+      Stmt::Exp(Some(exp)) => {
+        self.eval(exp)?;
+      }
+      Stmt::Exp(None) => {}
+      Stmt::Block(block) => {
+        let mark = self.memory.len();
+        self.scopes.push(BTreeMap::new());
+        let flow = self.block_items(block);
+        self.scopes.pop();
+        self.memory.truncate(mark);
+        return flow;
+      }
+      Stmt::If(cond, then, otherwise) => {
+        if self.eval(cond)?.is_true() {
+          return self.stmt(then);
+        } else if let Some(otherwise) = otherwise {
+          return self.stmt(otherwise);
+        }
+      }
+      Stmt::While(cond, body) => {
+        while self.eval(cond)?.is_true() {
+          match self.stmt(body)? {
+            Flow::Break => break,
+            Flow::Return(value) => return Ok(Flow::Return(value)),
+            Flow::Normal | Flow::Continue => {}
+          }
+        }
+      }
+      Stmt::Break(_) => return Ok(Flow::Break),
+      Stmt::Continue(_) => return Ok(Flow::Continue),
+      Stmt::Return(exp, _) => {
+        let value = match exp {
+          Some(exp) => Some(self.eval(exp)?),
+          None => None,
+        };
+        return Ok(Flow::Return(value));
+      }
+    }
+    Ok(Flow::Normal)
+  }
+
+  // 计算左值在内存中的位置，下标不足时得到子数组的起始位置
+  fn lval_addr(&mut self, lval: &'a LVal) -> Result<usize, RuntimeError> {
+    let mut indices = Vec::with_capacity(lval.indices.len());
+    for index in &lval.indices {
+      indices.push(self.eval(index)?.as_int());
+    }
+    let var = self.lookup(&lval.ident.name);
+    let mut addr = var.addr;
+    for (i, index) in indices.into_iter().enumerate() {
+      let len = var.dims[i];
+      if index < 0 || (len != 0 && index as usize >= len) {
+        return error(
+          format!("index {} out of bounds for '{}'", index, lval.ident.name),
+          lval.ident.pos(),
+        );
+      }
+      addr += index as usize * var.dims[i + 1..].iter().product::<usize>();
+    }
+    if addr >= self.memory.len() {
+      return error(format!("out-of-bounds access to '{}'", lval.ident.name), lval.ident.pos());
+    }
+    Ok(addr)
+  }
+
+  fn eval(&mut self, exp: &'a Exp) -> Result<Value, RuntimeError> {
+    match &exp.kind {
+      ExpKind::IntConst(v) => Ok(Value::Int(*v)),
+      ExpKind::FloatConst(v) => Ok(Value::Float(*v)),
+      ExpKind::LVal(lval) => {
+        let addr = self.lval_addr(lval)?;
+        match exp.ty {
+          Some(Type::Array(..)) => Ok(Value::Ptr(addr)),
+          _ => Ok(self.memory[addr]),
+        }
+      }
+      ExpKind::Call(func, args) => {
+        let mut values = Vec::with_capacity(args.len());
+        for arg in args {
+          values.push(self.eval(arg)?);
+        }
+        let ret = match self.functions.get(func.name.as_str()).copied() {
+          Some(def) => self.call_func(def, values)?,
+          None => self.call_runtime(func, &values)?,
+        };
+        // void函数的结果不会被使用
+        Ok(ret.unwrap_or(Value::Int(0)))
+      }
+      ExpKind::Unary(op, operand) => {
+        let value = self.eval(operand)?;
+        Ok(match (op, value) {
+          (UnaryOp::Plus, value) => value,
+          (UnaryOp::Minus, Value::Float(v)) => Value::Float(-v),
+          (UnaryOp::Minus, value) => Value::Int(value.as_int().wrapping_neg()),
+          (UnaryOp::Not, value) => Value::Int(!value.is_true() as i32),
+        })
+      }
+      ExpKind::Binary(BinaryOp::And, lhs, rhs) => {
+        let value = self.eval(lhs)?.is_true() && self.eval(rhs)?.is_true();
+        Ok(Value::Int(value as i32))
+      }
+      ExpKind::Binary(BinaryOp::Or, lhs, rhs) => {
+        let value = self.eval(lhs)?.is_true() || self.eval(rhs)?.is_true();
+        Ok(Value::Int(value as i32))
+      }
+      ExpKind::Binary(op, lhs, rhs) => {
+        let lhs = self.eval(lhs)?;
+        let rhs = self.eval(rhs)?;
+        binary(*op, lhs, rhs, exp.pos())
+      }
+      ExpKind::Cast(ty, operand) => {
+        let value = self.eval(operand)?;
+        Ok(match ty {
+          BType::Int => Value::Int(value.as_int()),
+          BType::Float => Value::Float(value.as_float()),
+        })
+      }
+    }
+  }
+}
+
+// 按C的语义计算，int运算在32位上回绕，除法向0截断
+fn binary(op: BinaryOp, lhs: Value, rhs: Value, pos: Point) -> Result<Value, RuntimeError> {
+  let bool_value = |b: bool| Ok(Value::Int(b as i32));
+  match (lhs, rhs) {
+    (Value::Float(_), _) | (_, Value::Float(_)) => {
+      let (a, b) = (lhs.as_float(), rhs.as_float());
+      match op {
+        BinaryOp::Add => Ok(Value::Float(a + b)),
+        BinaryOp::Sub => Ok(Value::Float(a - b)),
+        BinaryOp::Mul => Ok(Value::Float(a * b)),
+        BinaryOp::Div => Ok(Value::Float(a / b)),
+        BinaryOp::Mod => error("invalid operands of type 'float' to '%'", pos),
+        BinaryOp::Lt => bool_value(a < b),
+        BinaryOp::Gt => bool_value(a > b),
+        BinaryOp::Le => bool_value(a <= b),
+        BinaryOp::Ge => bool_value(a >= b),
+        BinaryOp::Eq => bool_value(a == b),
+        BinaryOp::Ne => bool_value(a != b),
+        BinaryOp::And | BinaryOp::Or => unreachable!(),
+      }
+    }
+    _ => {
+      let (a, b) = (lhs.as_int(), rhs.as_int());
+      match op {
+        BinaryOp::Add => Ok(Value::Int(a.wrapping_add(b))),
+        BinaryOp::Sub => Ok(Value::Int(a.wrapping_sub(b))),
+        BinaryOp::Mul => Ok(Value::Int(a.wrapping_mul(b))),
+        BinaryOp::Div | BinaryOp::Mod if b == 0 => error("division by zero", pos),
+        BinaryOp::Div => Ok(Value::Int(a.wrapping_div(b))),
+        BinaryOp::Mod => Ok(Value::Int(a.wrapping_rem(b))),
+        BinaryOp::Lt => bool_value(a < b),
+        BinaryOp::Gt => bool_value(a > b),
+        BinaryOp::Le => bool_value(a <= b),
+        BinaryOp::Ge => bool_value(a >= b),
+        BinaryOp::Eq => bool_value(a == b),
+        BinaryOp::Ne => bool_value(a != b),
+        BinaryOp::And | BinaryOp::Or => unreachable!(),
+      }
+    }
+  }
+}
+
+// 单元测试
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::test_util::check_source;
+
+  fn execute(source: &str, input: &str) -> (String, i32) {
+    let unit = check_source(source);
+    let mut output = Vec::new();
+    let ret = run(&unit, input.as_bytes(), &mut output).unwrap();
+    (String::from_utf8(output).unwrap(), ret)
+  }
+
+  // 递归较深时正常执行，栈将要用尽时报告运行时错误；栈溢出不能让整个进程中止
+  #[test]
+  fn test_call_depth() {
+    let recurse = |n: usize| {
+      let source = format!("int f(int n){{ if(n==0) return 0; return f(n-1)+1; }} int main(){{ return f({}); }}", n);
+      run(&check_source(&source), "".as_bytes(), Vec::new())
+    };
+    assert_eq!(recurse(5000), Ok(5000));
+    assert_eq!(recurse(100_000_000).unwrap_err().message, "call stack overflow");
+  }
+
+  #[test]
+  fn test_recursion_and_arrays() {
+    let source = "
+      int n = 10;
+      int fib(int k) { if (k < 2) return k; return fib(k - 1) + fib(k - 2); }
+      void fill(int a[][3], int rows) {
+        int i = 0;
+        while (i < rows) { a[i][i] = fib(n) + i; i = i + 1; }
+      }
+      int main() {
+        int m[3][3] = {{1}, {2}};
+        fill(m, 3);
+        putarray(3, m[1]);
+        putint(m[0][0] + m[2][2]);
+        return m[1][0];
+      }";
+    assert_eq!(execute(source, ""), ("3: 2 56 0\n112".to_string(), 2));
+  }
+
+  #[test]
+  fn test_c_semantics() {
+    let source = "
+      int main() {
+        int big = 2147483647;
+        putint(big + 1); putch(32);
+        putint(-7 / 2); putch(32);
+        putint(-7 % 2); putch(32);
+        float f = 7 / 2;
+        putfloat(f); putch(32);
+        putfloat(1.0 / 3 + 1); putch(32);
+        int i = 0;
+        if (i != 0 && 1 / i) putint(1); else putint(0);
+        return -1;
+      }";
+    assert_eq!(
+      execute(source, ""),
+      ("-2147483648 -3 -1 0x1.8p+1 0x1.555556p+0 0".to_string(), -1)
+    );
+  }
+
+  #[test]
+  fn test_runtime_input() {
+    let source = "
+      int a[10];
+      int main() {
+        int n = getarray(a);
+        float x = getfloat();
+        int c = getch();
+        c = getch();
+        putint(getint() + n); putch(10);
+        putfloat(x); putch(10);
+        putch(c);
+        return getch();
+      }";
+    assert_eq!(execute(source, "3 1 2 3\n-0x1.8p1 Z -42"), ("-39\n-0x1.8p+1\nZ".to_string(), -1));
+  }
+
+  #[test]
+  fn test_runtime_errors() {
+    let unit = check_source("int main() { int a[2]; int i = 2; a[i] = 1 / (i - 2); return 0; }");
+    let err = run(&unit, "".as_bytes(), Vec::new()).unwrap_err();
+    assert_eq!(err.message, "index 2 out of bounds for 'a'");
+    let unit = check_source("int f(int n) { return f(n + 1); } int main() { return f(0); }");
+    let err = run(&unit, "".as_bytes(), Vec::new()).unwrap_err();
+    assert_eq!(err.message, "call stack overflow");
+  }
+}
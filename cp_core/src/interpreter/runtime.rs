@@ -0,0 +1,204 @@
+use std::io::{BufRead, Write};
+
+use crate::ast::Ident;
+use crate::interpreter::{error, Interpreter, RuntimeError, Value};
+
+// SysY运行库，输入输出格式与libsysy一致：
+// getint/getfloat相当于scanf的%d/%a，putfloat输出printf的%a格式
+impl<'a, R: BufRead, W: Write> Interpreter<'a, R, W> {
+  pub(super) fn call_runtime(&mut self, func: &Ident, args: &[Value]) -> Result<Option<Value>, RuntimeError> {
+    let ret = match func.name.as_str() {
+      "getint" => Some(Value::Int(self.read_int())),
+      "getch" => Some(Value::Int(self.read_byte().map_or(-1, i32::from))),
+      "getfloat" => Some(Value::Float(self.read_float())),
+      "getarray" | "getfarray" => {
+        let len = self.read_int();
+        let base = args[0].as_ptr();
+        for i in 0..len.max(0) as usize {
+          let value = if func.name == "getarray" {
+            Value::Int(self.read_int())
+          } else {
+            Value::Float(self.read_float())
+          };
+          self.store(base + i, value, func)?;
+        }
+        Some(Value::Int(len))
+      }
+      "putint" => {
+        self.print(func, &args[0].as_int().to_string())?;
+        None
+      }
+      "putch" => {
+        let byte = args[0].as_int() as u8;
+        self.write(func, &[byte])?;
+        None
+      }
+      "putfloat" => {
+        self.print(func, &hex_float(args[0].as_float()))?;
+        None
+      }
+      "putarray" | "putfarray" => {
+        let len = args[0].as_int();
+        let base = args[1].as_ptr();
+        let mut line = format!("{}:", len);
+        for i in 0..len.max(0) as usize {
+          let value = self.load(base + i, func)?;
+          if func.name == "putarray" {
+            line.push_str(&format!(" {}", value.as_int()));
+          } else {
+            line.push_str(&format!(" {}", hex_float(value.as_float())));
+          }
+        }
+        line.push('\n');
+        self.print(func, &line)?;
+        None
+      }
+      // 计时只在编译后的程序中有意义
+      "_sysy_starttime" | "_sysy_stoptime" => None,
+      name => return error(format!("unknown function '{}'", name), func.pos()),
+    };
+    Ok(ret)
+  }
+
+  fn load(&self, addr: usize, func: &Ident) -> Result<Value, RuntimeError> {
+    match self.memory.get(addr) {
+      Some(value) => Ok(*value),
+      None => error(format!("out-of-bounds access in '{}'", func.name), func.pos()),
+    }
+  }
+
+  fn store(&mut self, addr: usize, value: Value, func: &Ident) -> Result<(), RuntimeError> {
+    match self.memory.get_mut(addr) {
+      Some(slot) => {
+        *slot = value;
+        Ok(())
+      }
+      None => error(format!("out-of-bounds access in '{}'", func.name), func.pos()),
+    }
+  }
+
+  fn write(&mut self, func: &Ident, bytes: &[u8]) -> Result<(), RuntimeError> {
+    match self.output.write_all(bytes) {
+      Ok(()) => Ok(()),
+      Err(e) => error(format!("cannot write output: {}", e), func.pos()),
+    }
+  }
+
+  fn print(&mut self, func: &Ident, text: &str) -> Result<(), RuntimeError> {
+    self.write(func, text.as_bytes())
+  }
+
+  fn peek_byte(&mut self) -> Option<u8> {
+    self.input.fill_buf().ok().and_then(|buf| buf.first().copied())
+  }
+
+  fn read_byte(&mut self) -> Option<u8> {
+    let byte = self.peek_byte()?;
+    self.input.consume(1);
+    Some(byte)
+  }
+
+  fn skip_whitespace(&mut self) {
+    while self.peek_byte().is_some_and(|b| b.is_ascii_whitespace()) {
+      self.input.consume(1);
+    }
+  }
+
+  // 读取一个十进制整数，溢出时回绕；没有数字时返回0
+  fn read_int(&mut self) -> i32 {
+    self.skip_whitespace();
+    let mut negative = false;
+    if let Some(sign @ (b'+' | b'-')) = self.peek_byte() {
+      negative = sign == b'-';
+      self.input.consume(1);
+    }
+    let mut value: i32 = 0;
+    while let Some(digit @ b'0'..=b'9') = self.peek_byte() {
+      value = value.wrapping_mul(10).wrapping_add((digit - b'0') as i32);
+      self.input.consume(1);
+    }
+    if negative {
+      value.wrapping_neg()
+    } else {
+      value
+    }
+  }
+
+  // 读取十进制或十六进制的浮点数
+  fn read_float(&mut self) -> f32 {
+    self.skip_whitespace();
+    let mut text = String::new();
+    while let Some(b) = self.peek_byte() {
+      let prev = text.bytes().last();
+      let accepted = b.is_ascii_hexdigit()
+        || matches!(b, b'.' | b'x' | b'X' | b'p' | b'P')
+        || (matches!(b, b'+' | b'-') && matches!(prev, None | Some(b'e' | b'E' | b'p' | b'P')));
+      if !accepted {
+        break;
+      }
+      text.push(b as char);
+      self.input.consume(1);
+    }
+    parse_float(&text).unwrap_or(0.0)
+  }
+}
+
+pub(crate) fn parse_float(text: &str) -> Option<f32> {
+  let (negative, rest) = match text.as_bytes().first()? {
+    b'-' => (true, &text[1..]),
+    b'+' => (false, &text[1..]),
+    _ => (false, text),
+  };
+  let value = match rest.strip_prefix("0x").or_else(|| rest.strip_prefix("0X")) {
+    Some(hex) => parse_hex_float(hex)?,
+    None => rest.parse::<f64>().ok()?,
+  };
+  Some(if negative { -value } else { value } as f32)
+}
+
+// 解析0x之后的十六进制浮点数，如"1.8p+1"
+fn parse_hex_float(text: &str) -> Option<f64> {
+  let (mantissa, exponent) = match text.find(['p', 'P']) {
+    Some(i) => (&text[..i], text[i + 1..].parse::<i32>().ok()?),
+    None => (text, 0),
+  };
+  let (int_part, frac_part) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+  if int_part.is_empty() && frac_part.is_empty() {
+    return None;
+  }
+  let mut value = 0.0f64;
+  for c in int_part.chars() {
+    value = value * 16.0 + c.to_digit(16)? as f64;
+  }
+  let mut scale = 1.0 / 16.0;
+  for c in frac_part.chars() {
+    value += c.to_digit(16)? as f64 * scale;
+    scale /= 16.0;
+  }
+  Some(value * 2f64.powi(exponent))
+}
+
+// 与printf("%a", (double)value)的输出一致
+pub(crate) fn hex_float(value: f32) -> String {
+  let value = value as f64;
+  let sign = if value.is_sign_negative() { "-" } else { "" };
+  if value.is_nan() {
+    return format!("{}nan", sign);
+  }
+  if value.is_infinite() {
+    return format!("{}inf", sign);
+  }
+  if value == 0.0 {
+    return format!("{}0x0p+0", sign);
+  }
+  let bits = value.to_bits();
+  let exponent = ((bits >> 52) & 0x7ff) as i32 - 1023;
+  let mantissa = bits & ((1 << 52) - 1);
+  let digits = format!("{:013x}", mantissa);
+  let digits = digits.trim_end_matches('0');
+  if digits.is_empty() {
+    format!("{}0x1p{:+}", sign, exponent)
+  } else {
+    format!("{}0x1.{}p{:+}", sign, digits, exponent)
+  }
+}
@@ -0,0 +1,531 @@
+use std::collections::BTreeMap;
+
+use crate::ast::*;
+use crate::ir::*;
+use crate::semantic::ConstValue;
+
+// 由语义检查之后的AST生成中间代码
+// 要求数组维度已经折叠为字面量，初始化列表已经展开，类型转换已经显式插入
+pub fn generate(unit: &CompUnit) -> Program {
+  let mut generator = Generator::new();
+  generator.generate(unit);
+  generator.into_program()
+}
+
+#[derive(Debug, Clone)]
+enum Storage {
+  Var(Var),
+  // 数组形参保存在临时变量中的地址
+  Pointer(Temp),
+}
+
+// 变量的存储位置和各维长度，数组形参的第一维记为0
+#[derive(Debug, Clone)]
+struct VarInfo {
+  storage: Storage,
+  dims: Vec<usize>,
+}
+
+pub struct Generator {
+  scopes: Vec<BTreeMap<String, VarInfo>>,
+  globals: Vec<Global>,
+  functions: Vec<Function>,
+  // 当前函数的状态
+  locals: Vec<Local>,
+  temps: Vec<IrType>,
+  code: Vec<Inst>,
+  label_count: usize,
+  // 循环的continue和break目标
+  loops: Vec<(Label, Label)>,
+}
+
+impl Default for Generator {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl Generator {
+  pub fn new() -> Self {
+    Self {
+      scopes: vec![BTreeMap::new()],
+      globals: Vec::new(),
+      functions: Vec::new(),
+      locals: Vec::new(),
+      temps: Vec::new(),
+      code: Vec::new(),
+      label_count: 0,
+      loops: Vec::new(),
+    }
+  }
+
+  pub fn into_program(self) -> Program {
+    Program {
+      globals: self.globals,
+      functions: self.functions,
+    }
+  }
+
+  pub fn generate(&mut self, unit: &CompUnit) {
+    for item in &unit.items {
+      match item {
+        GlobalItem::Decl(decl) => self.global_decl(decl),
+        GlobalItem::FuncDef(func) => self.func_def(func),
+      }
+    }
+  }
+
+  fn declare(&mut self, name: &str, info: VarInfo) {
+    self.scopes.last_mut().unwrap().insert(name.to_string(), info);
+  }
+
+  fn lookup(&self, name: &str) -> &VarInfo {
+    self
+      .scopes
+      .iter()
+      .rev()
+      .find_map(|scope| scope.get(name))
+      .expect("undeclared variable after semantic check")
+  }
+
+  fn temp(&mut self, ty: IrType) -> Temp {
+    self.temps.push(ty);
+    Temp(self.temps.len() - 1)
+  }
+
+  fn label(&mut self) -> Label {
+    self.label_count += 1;
+    Label(self.label_count)
+  }
+
+  fn emit(&mut self, inst: Inst) {
+    self.code.push(inst);
+  }
+
+  fn local(&mut self, ty: BType, size: usize) -> Var {
+    self.locals.push(Local { ty, size });
+    Var::Local(self.locals.len() - 1)
+  }
+
+  fn addr(&mut self, var: Var) -> Operand {
+    let dst = self.temp(IrType::Ptr);
+    self.emit(Inst::Addr { dst, var });
+    Operand::Temp(dst)
+  }
+
+  fn global_decl(&mut self, decl: &Decl) {
+    for def in &decl.defs {
+      let init = def.init.as_ref().map(|init| match init {
+        InitVal::Exp(exp) => vec![literal(exp)],
+        InitVal::List(items, _) => items.iter().map(|item| literal(init_exp(item))).collect(),
+      });
+      self.globals.push(Global {
+        name: def.ident.name.clone(),
+        ty: decl.ty,
+        dims: def.shape(),
+        init,
+      });
+      self.declare(
+        &def.ident.name,
+        VarInfo {
+          storage: Storage::Var(Var::Global(def.ident.name.clone())),
+          dims: def.shape(),
+        },
+      );
+    }
+  }
+
+  fn local_decl(&mut self, decl: &Decl) {
+    for def in &decl.defs {
+      let dims = def.shape();
+      let size = dims.iter().product();
+      let var = self.local(decl.ty, size);
+      match &def.init {
+        Some(InitVal::Exp(exp)) => {
+          let value = self.exp(exp);
+          let addr = self.addr(var.clone());
+          self.emit(Inst::Store { src: value, addr });
+        }
+        Some(InitVal::List(items, _)) => self.array_init(var.clone(), decl.ty, items),
+        None => {}
+      }
+      // 初始化表达式中的同名标识符指向外层变量，所以定义完成后再声明
+      self.declare(
+        &def.ident.name,
+        VarInfo {
+          storage: Storage::Var(var),
+          dims,
+        },
+      );
+    }
+  }
+
+  // 局部数组的初始化：有0元素时先用循环清零，再逐个写入其余元素
+  fn array_init(&mut self, var: Var, ty: BType, items: &[InitVal]) {
+    let is_zero = |item: &InitVal| match init_exp(item).kind {
+      ExpKind::IntConst(v) => v == 0,
+      ExpKind::FloatConst(v) => v == 0.0,
+      _ => false,
+    };
+    let base = self.addr(var);
+    if items.iter().any(is_zero) {
+      self.zero_fill(base, ty, items.len());
+    }
+    for (index, item) in items.iter().enumerate() {
+      if is_zero(item) {
+        continue;
+      }
+      let value = self.exp(init_exp(item));
+      let addr = self.offset(base, Operand::Int(index as i32), 1);
+      self.emit(Inst::Store { src: value, addr });
+    }
+  }
+
+  fn zero_fill(&mut self, base: Operand, ty: BType, len: usize) {
+    // 循环变量放在隐藏的局部变量中，保持临时变量只赋值一次
+    let counter = self.local(BType::Int, 1);
+    let counter = self.addr(counter);
+    self.emit(Inst::Store {
+      src: Operand::Int(0),
+      addr: counter,
+    });
+    let (cond, body, end) = (self.label(), self.label(), self.label());
+    self.emit(Inst::Label(cond));
+    let i = self.load(IrType::Int, counter);
+    let test = self.binary(BinOp::Lt, BType::Int, i, Operand::Int(len as i32));
+    self.emit(Inst::Branch {
+      cond: test,
+      then: body,
+      otherwise: end,
+    });
+    self.emit(Inst::Label(body));
+    let addr = self.offset(base, i, 1);
+    let zero = match ty {
+      BType::Int => Operand::Int(0),
+      BType::Float => Operand::Float(0.0),
+    };
+    self.emit(Inst::Store { src: zero, addr });
+    let next = self.binary(BinOp::Add, BType::Int, i, Operand::Int(1));
+    self.emit(Inst::Store {
+      src: next,
+      addr: counter,
+    });
+    self.emit(Inst::Jump(cond));
+    self.emit(Inst::Label(end));
+  }
+
+  fn func_def(&mut self, func: &FuncDef) {
+    self.locals.clear();
+    self.temps.clear();
+    self.code.clear();
+    self.scopes.push(BTreeMap::new());
+
+    let params: Vec<_> = func
+      .params
+      .iter()
+      .map(|param| match param.dims {
+        Some(_) => IrType::Ptr,
+        None => param.ty.into(),
+      })
+      .collect();
+    for ty in &params {
+      self.temp(*ty);
+    }
+    for (index, param) in func.params.iter().enumerate() {
+      let temp = Temp(index);
+      let info = match param.shape() {
+        Some(shape) => {
+          let mut dims = vec![0];
+          dims.extend(shape);
+          VarInfo {
+            storage: Storage::Pointer(temp),
+            dims,
+          }
+        }
+        // 标量形参复制到栈上，以便被赋值
+        None => {
+          let var = self.local(param.ty, 1);
+          let addr = self.addr(var.clone());
+          self.emit(Inst::Store {
+            src: Operand::Temp(temp),
+            addr,
+          });
+          VarInfo {
+            storage: Storage::Var(var),
+            dims: Vec::new(),
+          }
+        }
+      };
+      self.declare(&param.ident.name, info);
+    }
+
+    self.block_items(&func.body);
+    // 补上函数末尾的返回，非void函数返回0
+    let ret = match func.func_type {
+      FuncType::Void => None,
+      FuncType::Int => Some(Operand::Int(0)),
+      FuncType::Float => Some(Operand::Float(0.0)),
+    };
+    self.emit(Inst::Return(ret));
+    self.scopes.pop();
+
+    self.functions.push(Function {
+      name: func.ident.name.clone(),
+      ret: func.func_type,
+      params,
+      locals: std::mem::take(&mut self.locals),
+      temps: std::mem::take(&mut self.temps),
+      code: std::mem::take(&mut self.code),
+    });
+  }
+
+  fn block_items(&mut self, block: &Block) {
+    for item in &block.items {
+      match item {
+        BlockItem::Decl(decl) => self.local_decl(decl),
+        BlockItem::Stmt(stmt) => self.stmt(stmt),
+      }
+    }
+  }
+
+  fn stmt(&mut self, stmt: &Stmt) {
+    match stmt {
+      Stmt::Assign(lval, exp) => {
+        let addr = self.lval_addr(lval);
+        let value = self.exp(exp);
+        self.emit(Inst::Store { src: value, addr });
+      }
+      Stmt::Exp(Some(exp)) => {
+        self.exp(exp);
+      }
+      Stmt::Exp(None) => {}
+      Stmt::Block(block) => {
+        self.scopes.push(BTreeMap::new());
+        self.block_items(block);
+        self.scopes.pop();
+      }
+      Stmt::If(cond, then, otherwise) => {
+        let (then_label, else_label, end) = (self.label(), self.label(), self.label());
+        let false_label = if otherwise.is_some() { else_label } else { end };
+        self.cond(cond, then_label, false_label);
+        self.emit(Inst::Label(then_label));
+        self.stmt(then);
+        if let Some(otherwise) = otherwise {
+          self.emit(Inst::Jump(end));
+          self.emit(Inst::Label(else_label));
+          self.stmt(otherwise);
+        }
+        self.emit(Inst::Label(end));
+      }
+      Stmt::While(cond, body) => {
+        let (cond_label, body_label, end) = (self.label(), self.label(), self.label());
+        self.emit(Inst::Label(cond_label));
+        self.cond(cond, body_label, end);
+        self.emit(Inst::Label(body_label));
+        self.loops.push((cond_label, end));
+        self.stmt(body);
+        self.loops.pop();
+        self.emit(Inst::Jump(cond_label));
+        self.emit(Inst::Label(end));
+      }
+      Stmt::Break(_) => {
+        let (_, end) = *self.loops.last().expect("break outside of a loop");
+        self.emit(Inst::Jump(end));
+        // 跳转之后的代码不可达，放在新的标号之后
+        let label = self.label();
+        self.emit(Inst::Label(label));
+      }
+      Stmt::Continue(_) => {
+        let (cond, _) = *self.loops.last().expect("continue outside of a loop");
+        self.emit(Inst::Jump(cond));
+        let label = self.label();
+        self.emit(Inst::Label(label));
+      }
+      Stmt::Return(exp, _) => {
+        let value = exp.as_ref().map(|exp| self.exp(exp));
+        self.emit(Inst::Return(value));
+        let label = self.label();
+        self.emit(Inst::Label(label));
+      }
+    }
+  }
+
+  // 条件表达式，&&和||短路求值
+  fn cond(&mut self, exp: &Exp, then: Label, otherwise: Label) {
+    match &exp.kind {
+      ExpKind::Binary(BinaryOp::And, lhs, rhs) => {
+        let mid = self.label();
+        self.cond(lhs, mid, otherwise);
+        self.emit(Inst::Label(mid));
+        self.cond(rhs, then, otherwise);
+      }
+      ExpKind::Binary(BinaryOp::Or, lhs, rhs) => {
+        let mid = self.label();
+        self.cond(lhs, then, mid);
+        self.emit(Inst::Label(mid));
+        self.cond(rhs, then, otherwise);
+      }
+      ExpKind::Unary(UnaryOp::Not, operand) => self.cond(operand, otherwise, then),
+      _ => {
+        let mut value = self.exp(exp);
+        if exp.ty == Some(Type::Float) {
+          value = self.binary(BinOp::Ne, BType::Float, value, Operand::Float(0.0));
+        }
+        self.emit(Inst::Branch {
+          cond: value,
+          then,
+          otherwise,
+        });
+      }
+    }
+  }
+
+  fn binary(&mut self, op: BinOp, ty: BType, lhs: Operand, rhs: Operand) -> Operand {
+    let result_ty = match op {
+      BinOp::Add | BinOp::Sub | BinOp::Mul | BinOp::Div | BinOp::Mod => ty.into(),
+      _ => IrType::Int,
+    };
+    let dst = self.temp(result_ty);
+    self.emit(Inst::Binary { op, ty, dst, lhs, rhs });
+    Operand::Temp(dst)
+  }
+
+  fn load(&mut self, ty: IrType, addr: Operand) -> Operand {
+    let dst = self.temp(ty);
+    self.emit(Inst::Load { dst, addr });
+    Operand::Temp(dst)
+  }
+
+  fn offset(&mut self, base: Operand, index: Operand, scale: usize) -> Operand {
+    let dst = self.temp(IrType::Ptr);
+    self.emit(Inst::Offset { dst, base, index, scale });
+    Operand::Temp(dst)
+  }
+
+  // 计算左值的地址，下标不足时得到子数组的地址
+  fn lval_addr(&mut self, lval: &LVal) -> Operand {
+    let info = self.lookup(&lval.ident.name).clone();
+    let mut addr = match info.storage {
+      Storage::Var(var) => self.addr(var),
+      Storage::Pointer(temp) => Operand::Temp(temp),
+    };
+    for (i, index) in lval.indices.iter().enumerate() {
+      let index = self.exp(index);
+      let scale = info.dims[i + 1..].iter().product();
+      addr = self.offset(addr, index, scale);
+    }
+    addr
+  }
+
+  fn exp(&mut self, exp: &Exp) -> Operand {
+    match &exp.kind {
+      ExpKind::IntConst(v) => Operand::Int(*v),
+      ExpKind::FloatConst(v) => Operand::Float(*v),
+      ExpKind::LVal(lval) => {
+        let addr = self.lval_addr(lval);
+        match exp.ty {
+          Some(Type::Array(..)) => addr,
+          Some(Type::Float) => self.load(IrType::Float, addr),
+          _ => self.load(IrType::Int, addr),
+        }
+      }
+      ExpKind::Call(func, args) => {
+        let args: Vec<_> = args.iter().map(|arg| self.exp(arg)).collect();
+        let name = func.name.clone();
+        let dst = match exp.ty {
+          Some(Type::Int) => Some(self.temp(IrType::Int)),
+          Some(Type::Float) => Some(self.temp(IrType::Float)),
+          _ => None,
+        };
+        self.emit(Inst::Call { dst, func: name, args });
+        // void函数的结果不会被使用
+        dst.map_or(Operand::Int(0), Operand::Temp)
+      }
+      ExpKind::Unary(UnaryOp::Plus, operand) => self.exp(operand),
+      ExpKind::Unary(op, operand) => {
+        let ty = operand_type(operand);
+        let src = self.exp(operand);
+        let (op, result_ty) = match op {
+          UnaryOp::Minus => (UnOp::Neg, ty.into()),
+          _ => (UnOp::Not, IrType::Int),
+        };
+        let dst = self.temp(result_ty);
+        self.emit(Inst::Unary { op, ty, dst, src });
+        Operand::Temp(dst)
+      }
+      ExpKind::Binary(op, ..) if op.is_logical() => self.logical_value(exp),
+      ExpKind::Binary(op, lhs, rhs) => {
+        let ty = operand_type(lhs);
+        let lhs = self.exp(lhs);
+        let rhs = self.exp(rhs);
+        self.binary(bin_op(*op), ty, lhs, rhs)
+      }
+      ExpKind::Cast(to, operand) => {
+        let src = self.exp(operand);
+        let dst = self.temp((*to).into());
+        self.emit(Inst::Convert { to: *to, dst, src });
+        Operand::Temp(dst)
+      }
+    }
+  }
+
+  // 在需要值的地方出现的&&和||，结果经由隐藏的局部变量汇合
+  fn logical_value(&mut self, exp: &Exp) -> Operand {
+    let var = self.local(BType::Int, 1);
+    let result = self.addr(var);
+    let (then, otherwise, end) = (self.label(), self.label(), self.label());
+    self.cond(exp, then, otherwise);
+    self.emit(Inst::Label(then));
+    self.emit(Inst::Store {
+      src: Operand::Int(1),
+      addr: result,
+    });
+    self.emit(Inst::Jump(end));
+    self.emit(Inst::Label(otherwise));
+    self.emit(Inst::Store {
+      src: Operand::Int(0),
+      addr: result,
+    });
+    self.emit(Inst::Label(end));
+    self.load(IrType::Int, result)
+  }
+}
+
+fn operand_type(exp: &Exp) -> BType {
+  match exp.ty {
+    Some(Type::Float) => BType::Float,
+    _ => BType::Int,
+  }
+}
+
+fn bin_op(op: BinaryOp) -> BinOp {
+  match op {
+    BinaryOp::Add => BinOp::Add,
+    BinaryOp::Sub => BinOp::Sub,
+    BinaryOp::Mul => BinOp::Mul,
+    BinaryOp::Div => BinOp::Div,
+    BinaryOp::Mod => BinOp::Mod,
+    BinaryOp::Lt => BinOp::Lt,
+    BinaryOp::Gt => BinOp::Gt,
+    BinaryOp::Le => BinOp::Le,
+    BinaryOp::Ge => BinOp::Ge,
+    BinaryOp::Eq => BinOp::Eq,
+    BinaryOp::Ne => BinOp::Ne,
+    BinaryOp::And | BinaryOp::Or => unreachable!("logical operators are lowered to branches"),
+  }
+}
+
+fn init_exp(init: &InitVal) -> &Exp {
+  match init {
+    InitVal::Exp(exp) => exp,
+    InitVal::List(..) => panic!("initializer list has not been flattened"),
+  }
+}
+
+fn literal(exp: &Exp) -> ConstValue {
+  match exp.kind {
+    ExpKind::IntConst(v) => ConstValue::Int(v),
+    ExpKind::FloatConst(v) => ConstValue::Float(v),
+    _ => panic!("global initializer has not been evaluated"),
+  }
+}
@@ -0,0 +1,377 @@
+mod generator;
+
+use std::fmt::{Display, Formatter, Result};
+
+use crate::ast::{BType, FuncType};
+use crate::semantic::ConstValue;
+
+pub use generator::{generate, Generator};
+
+// 三地址码（四元式）形式的中间表示
+// 具名变量都放在内存中，通过Addr取得地址后Load/Store；临时变量只在定义处赋值一次
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Program {
+  pub globals: Vec<Global>,
+  pub functions: Vec<Function>,
+}
+
+// 全局变量，init为按行优先展开的初始值，None表示全部为0
+#[derive(Debug, Clone, PartialEq)]
+pub struct Global {
+  pub name: String,
+  pub ty: BType,
+  pub dims: Vec<usize>,
+  pub init: Option<Vec<ConstValue>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Function {
+  pub name: String,
+  pub ret: FuncType,
+  // 形参依次是临时变量t0, t1, ...，数组形参是指针
+  pub params: Vec<IrType>,
+  pub locals: Vec<Local>,
+  // 每个临时变量的类型，下标即编号
+  pub temps: Vec<IrType>,
+  pub code: Vec<Inst>,
+}
+
+// 函数栈帧中的变量，size为元素个数，标量为1
+#[derive(Debug, Clone, PartialEq)]
+pub struct Local {
+  pub ty: BType,
+  pub size: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IrType {
+  Int,
+  Float,
+  Ptr,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Temp(pub usize);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Label(pub usize);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Operand {
+  Temp(Temp),
+  Int(i32),
+  Float(f32),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Var {
+  Global(String),
+  Local(usize),
+}
+
+// 运算的操作数类型由ty给出，比较运算的结果总是int
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinOp {
+  Add,
+  Sub,
+  Mul,
+  Div,
+  Mod,
+  Lt,
+  Gt,
+  Le,
+  Ge,
+  Eq,
+  Ne,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnOp {
+  Neg,
+  // 逻辑非，结果是int
+  Not,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Inst {
+  Binary { op: BinOp, ty: BType, dst: Temp, lhs: Operand, rhs: Operand },
+  Unary { op: UnOp, ty: BType, dst: Temp, src: Operand },
+  // int和float之间的转换，to为目标类型
+  Convert { to: BType, dst: Temp, src: Operand },
+  Addr { dst: Temp, var: Var },
+  // dst = base + index * scale，单位是数组元素
+  Offset { dst: Temp, base: Operand, index: Operand, scale: usize },
+  Load { dst: Temp, addr: Operand },
+  Store { src: Operand, addr: Operand },
+  Call { dst: Option<Temp>, func: String, args: Vec<Operand> },
+  Label(Label),
+  Jump(Label),
+  // cond为int，非0时跳转到then
+  Branch { cond: Operand, then: Label, otherwise: Label },
+  Return(Option<Operand>),
+}
+
+impl Operand {
+  pub fn as_temp(&self) -> Option<Temp> {
+    match self {
+      Operand::Temp(temp) => Some(*temp),
+      _ => None,
+    }
+  }
+}
+
+impl Function {
+  pub fn temp_type(&self, operand: &Operand) -> IrType {
+    match operand {
+      Operand::Temp(temp) => self.temps[temp.0],
+      Operand::Int(_) => IrType::Int,
+      Operand::Float(_) => IrType::Float,
+    }
+  }
+}
+
+impl Global {
+  pub fn size(&self) -> usize {
+    self.dims.iter().product()
+  }
+}
+
+impl From<BType> for IrType {
+  fn from(ty: BType) -> Self {
+    match ty {
+      BType::Int => IrType::Int,
+      BType::Float => IrType::Float,
+    }
+  }
+}
+
+impl Display for IrType {
+  fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+    match self {
+      IrType::Int => write!(f, "int"),
+      IrType::Float => write!(f, "float"),
+      IrType::Ptr => write!(f, "ptr"),
+    }
+  }
+}
+
+impl Display for Temp {
+  fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+    write!(f, "t{}", self.0)
+  }
+}
+
+impl Display for Label {
+  fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+    write!(f, "L{}", self.0)
+  }
+}
+
+impl Display for Operand {
+  fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+    match self {
+      Operand::Temp(temp) => write!(f, "{}", temp),
+      Operand::Int(v) => write!(f, "{}", v),
+      Operand::Float(v) => write!(f, "{:?}", v),
+    }
+  }
+}
+
+impl Display for Var {
+  fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+    match self {
+      Var::Global(name) => write!(f, "@{}", name),
+      Var::Local(id) => write!(f, "%{}", id),
+    }
+  }
+}
+
+impl Display for BinOp {
+  fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+    let op = match self {
+      BinOp::Add => "+",
+      BinOp::Sub => "-",
+      BinOp::Mul => "*",
+      BinOp::Div => "/",
+      BinOp::Mod => "%",
+      BinOp::Lt => "<",
+      BinOp::Gt => ">",
+      BinOp::Le => "<=",
+      BinOp::Ge => ">=",
+      BinOp::Eq => "==",
+      BinOp::Ne => "!=",
+    };
+    write!(f, "{}", op)
+  }
+}
+
+fn type_name(ty: BType) -> &'static str {
+  match ty {
+    BType::Int => "int",
+    BType::Float => "float",
+  }
+}
+
+impl Display for Inst {
+  fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+    match self {
+      Inst::Binary { op, ty, dst, lhs, rhs } => {
+        write!(f, "  {} = {} {} {} {}", dst, type_name(*ty), lhs, op, rhs)
+      }
+      Inst::Unary { op: UnOp::Neg, ty, dst, src } => write!(f, "  {} = {} -{}", dst, type_name(*ty), src),
+      Inst::Unary { op: UnOp::Not, ty, dst, src } => write!(f, "  {} = {} !{}", dst, type_name(*ty), src),
+      Inst::Convert { to, dst, src } => write!(f, "  {} = ({}) {}", dst, type_name(*to), src),
+      Inst::Addr { dst, var } => write!(f, "  {} = &{}", dst, var),
+      Inst::Offset { dst, base, index, scale } => write!(f, "  {} = {} + {} * {}", dst, base, index, scale),
+      Inst::Load { dst, addr } => write!(f, "  {} = *{}", dst, addr),
+      Inst::Store { src, addr } => write!(f, "  *{} = {}", addr, src),
+      Inst::Call { dst, func, args } => {
+        write!(f, "  ")?;
+        if let Some(dst) = dst {
+          write!(f, "{} = ", dst)?;
+        }
+        let args: Vec<_> = args.iter().map(ToString::to_string).collect();
+        write!(f, "call {}({})", func, args.join(", "))
+      }
+      Inst::Label(label) => write!(f, "{}:", label),
+      Inst::Jump(label) => write!(f, "  goto {}", label),
+      Inst::Branch { cond, then, otherwise } => write!(f, "  if {} goto {} else goto {}", cond, then, otherwise),
+      Inst::Return(Some(value)) => write!(f, "  return {}", value),
+      Inst::Return(None) => write!(f, "  return"),
+    }
+  }
+}
+
+impl Display for Function {
+  fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+    let params: Vec<_> = self
+      .params
+      .iter()
+      .enumerate()
+      .map(|(i, ty)| format!("t{}: {}", i, ty))
+      .collect();
+    let ret = match self.ret {
+      FuncType::Void => "void",
+      FuncType::Int => "int",
+      FuncType::Float => "float",
+    };
+    writeln!(f, "func {}({}) -> {} {{", self.name, params.join(", "), ret)?;
+    for (id, local) in self.locals.iter().enumerate() {
+      writeln!(f, "  local %{}: {}[{}]", id, type_name(local.ty), local.size)?;
+    }
+    for inst in &self.code {
+      writeln!(f, "{}", inst)?;
+    }
+    writeln!(f, "}}")
+  }
+}
+
+impl Display for Program {
+  fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+    for global in &self.globals {
+      write!(f, "global @{}: {}", global.name, type_name(global.ty))?;
+      for dim in &global.dims {
+        write!(f, "[{}]", dim)?;
+      }
+      if let Some(init) = &global.init {
+        let values: Vec<_> = init
+          .iter()
+          .map(|value| match value {
+            ConstValue::Int(v) => v.to_string(),
+            ConstValue::Float(v) => format!("{:?}", v),
+          })
+          .collect();
+        write!(f, " = {{{}}}", values.join(", "))?;
+      }
+      writeln!(f)?;
+    }
+    for function in &self.functions {
+      writeln!(f)?;
+      write!(f, "{}", function)?;
+    }
+    Ok(())
+  }
+}
+
+// 单元测试
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::test_util::check_source;
+
+  fn function<'a>(program: &'a Program, name: &str) -> &'a Function {
+    program.functions.iter().find(|f| f.name == name).unwrap()
+  }
+
+  #[test]
+  fn test_globals() {
+    let program = generate(&check_source("int a[2][2] = {1, 0, {2}}; float b; int main() { return a[1][0]; }"));
+    assert_eq!(program.globals.len(), 2);
+    assert_eq!(program.globals[0].dims, vec![2, 2]);
+    assert_eq!(
+      program.globals[0].init,
+      Some([1, 0, 2, 0].map(ConstValue::Int).to_vec())
+    );
+    assert_eq!(program.globals[1].init, None);
+    assert_eq!(program.globals[1].size(), 1);
+
+    // a[1][0]的地址按行优先计算
+    let main = function(&program, "main");
+    assert!(main.code.contains(&Inst::Offset {
+      dst: Temp(1),
+      base: Operand::Temp(Temp(0)),
+      index: Operand::Int(1),
+      scale: 2,
+    }));
+  }
+
+  #[test]
+  fn test_short_circuit() {
+    let program = generate(&check_source(
+      "int f() { return 1; } int main() { int a = 0; if (a && f() || !a) a = 2; return a; }",
+    ));
+    let main = function(&program, "main");
+    // 条件中不出现逻辑运算，只有分支；f的调用在a为真的分支之后
+    assert!(!main.code.iter().any(|inst| matches!(inst, Inst::Unary { op: UnOp::Not, .. })));
+    let branches: Vec<_> = main
+      .code
+      .iter()
+      .filter_map(|inst| match inst {
+        Inst::Branch { then, otherwise, .. } => Some((*then, *otherwise)),
+        _ => None,
+      })
+      .collect();
+    assert_eq!(branches.len(), 3);
+    let call = main.code.iter().position(|inst| matches!(inst, Inst::Call { .. })).unwrap();
+    let first_true = main.code.iter().position(|inst| *inst == Inst::Label(branches[0].0)).unwrap();
+    assert!(first_true < call);
+    // a或f()为假时跳到||的右侧；!a通过交换出口实现，a为假时进入then
+    assert_eq!(branches[0].1, branches[1].1);
+    assert_eq!(branches[2].1, branches[1].0);
+  }
+
+  #[test]
+  fn test_loop_targets() {
+    let program = generate(&check_source(
+      "int main() { int i = 0; while (i < 10) { i = i + 1; if (i == 3) continue; if (i == 5) break; } return i; }",
+    ));
+    let main = function(&program, "main");
+    let Some(Inst::Label(head)) = main.code.iter().find(|inst| matches!(inst, Inst::Label(_))) else {
+      panic!()
+    };
+    let Some(Inst::Branch { otherwise: exit, .. }) = main.code.iter().find(|inst| matches!(inst, Inst::Branch { .. })) else {
+      panic!()
+    };
+    let jumps: Vec<_> = main
+      .code
+      .iter()
+      .filter_map(|inst| match inst {
+        Inst::Jump(label) => Some(*label),
+        _ => None,
+      })
+      .collect();
+    // continue、break，以及循环末尾回到条件判断
+    assert_eq!(jumps, vec![*head, *exit, *head]);
+  }
+}
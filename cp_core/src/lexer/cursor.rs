@@ -1,14 +1,16 @@
 use std::f32::consts::E;
 // use crate::token::Numbers::*;
 // use crate::token::{KeyWords, Numbers, Operators, Token, TokenKind};
-use crate::lexer::{Token, TokenKind, KeyWords, Point, LexerError, Operators};
+use crate::lexer::{Token, TokenKind, KeyWords, Point, Span, LexerError, LexerErrorKind, Operators};
 use crate::lexer::LexerResult;
+use crate::lexer::literal::parse_number;
 use std::str::Chars;
-use crate::token::{Numbers, TokenType};
+use crate::token::TokenType;
 
 const EOF_CHAR: char = '\0';
 
 pub struct Cursor<'a> {
+  input: &'a str,
   chars: Chars<'a>,
   current: char,
   row: usize,
@@ -25,6 +27,7 @@ pub struct Cursor<'a> {
 impl<'a> Cursor<'a> {
   pub fn new(input: &'a str) -> Cursor<'a> {
     Cursor {
+      input,
       chars: input.chars(),
       current: EOF_CHAR,
       row: 1,
@@ -46,17 +49,24 @@ impl<'a> Cursor<'a> {
     chars.next().unwrap_or(EOF_CHAR)
   }
 
-  fn next(&mut self) -> Option<char> {
-    if self.current == '\n' {
+  // 下一个字符的字节偏移
+  pub fn offset(&self) -> usize {
+    self.input.len() - self.chars.as_str().len()
+  }
+
+  // 下一个字符的行列号
+  pub fn point(&self) -> Point {
+    Point::new(self.row, self.column)
+  }
+
+  fn bump(&mut self) -> Option<char> {
+    let output = self.chars.next();
+    if output == Some('\n') {
       self.row += 1;
       self.column = 1;
     } else {
       self.column += 1;
     }
-    let output = match self.chars.next() {
-      Some(ch) => Some(ch),
-      _ => None,
-    };
     self.current = self.first();
     output
   }
@@ -133,7 +143,49 @@ fn is_keyword(buf: &str) -> Option<TokenKind> {
 }
 
 impl Cursor<'_> {
+  // 读取下一个词素，记录它的区间
   pub fn advance_token(&mut self) -> LexerResult {
+    let (start, start_point) = (self.offset(), self.point());
+    let result = self.scan_token();
+    let span = Span::new(start, self.offset(), start_point, self.point());
+    match result {
+      Ok(token) => Ok(token.with_span(span)),
+      Err(mut err) => {
+        err.span = span;
+        Err(err)
+      }
+    }
+  }
+
+  // 出错时不中断：诊断信息加入errors，返回覆盖出错部分的Err词素，保证至少前进一个字符
+  pub fn recover_token(&mut self, errors: &mut Vec<LexerError>) -> Token {
+    let start = self.offset();
+    match self.advance_token() {
+      Ok(token) => token,
+      Err(mut err) => {
+        if self.offset() == start {
+          self.bump();
+          err.span = Span::new(start, self.offset(), *err.span.get_start_point(), self.point());
+        }
+        let text = self.input[start..self.offset()].to_string();
+        let token = Token::new(TokenType::Terminal(TokenKind::Err), text, None).with_span(err.span);
+        errors.push(err);
+        token
+      }
+    }
+  }
+
+  // 读取全部词素，包括空白、注释和Err词素
+  pub fn tokenize(&mut self) -> (Vec<Token>, Vec<LexerError>) {
+    let mut tokens = Vec::new();
+    let mut errors = Vec::new();
+    while !self.is_eof() {
+      tokens.push(self.recover_token(&mut errors));
+    }
+    (tokens, errors)
+  }
+
+  fn scan_token(&mut self) -> LexerResult {
     match self.first() {
       '/' => match self.second() {
         '/' => self.line_comment(),
@@ -147,6 +199,8 @@ impl Cursor<'_> {
 
       ch if is_number(ch) => self.number(),
 
+      '.' if is_number(self.second()) => self.number(),
+
       '\'' => self.character(),
 
       '"' => self.str(),
@@ -156,19 +210,19 @@ impl Cursor<'_> {
       // ch if is_delimiter(ch) => self.delimiter(),
 
       ch if ch == ';' => {
-        self.next();
+        self.bump();
         Ok(Token::new(TokenType::Terminal(TokenKind::Semicolon), ";".to_string(), Some(Point::new(self.row, self.column))))
       }
       ch if ch == ',' => {
-        self.next();
+        self.bump();
         Ok(Token::new(TokenType::Terminal(TokenKind::Comma), ",".to_string(), Some(Point::new(self.row, self.column))))
       }
       ch if ch == '{' => {
-        self.next();
+        self.bump();
         Ok(Token::new(TokenType::Terminal(TokenKind::OpenBrace), "{".to_string(), Some(Point::new(self.row, self.column))))
       }
       ch if ch == '}' => {
-        self.next();
+        self.bump();
         Ok(Token::new(TokenType::Terminal(TokenKind::CloseBrace), "}".to_string(), Some(Point::new(self.row, self.column))))
       }
 
@@ -187,12 +241,12 @@ impl Cursor<'_> {
       match status {
         // NOTE:通过外层判断，状态0和状态1应该一定有
         0 => {
-          let ch = self.next().unwrap();
+          let ch = self.bump().unwrap();
           buf.push(ch);
           status = 1;
         }
         1 => {
-          let ch = self.next().unwrap();
+          let ch = self.bump().unwrap();
           buf.push(ch);
           status = 2;
         }
@@ -200,7 +254,7 @@ impl Cursor<'_> {
           // NOTE:去往终结状态，但不取字符
           '\n' => status = 3,
           _ => {
-            let ch = self.next().unwrap();
+            let ch = self.bump().unwrap();
             buf.push(ch);
             status = 2;
           }
@@ -224,23 +278,23 @@ impl Cursor<'_> {
       match status {
         // NOTE:通过外层判断，状态0和状态1应该一定有
         0 => {
-          let ch = self.next().unwrap();
+          let ch = self.bump().unwrap();
           buf.push(ch);
           status = 1;
         }
         1 => {
-          let ch = self.next().unwrap();
+          let ch = self.bump().unwrap();
           buf.push(ch);
           status = 2;
         }
         2 => match self.first() {
           '*' => {
-            let ch = self.next().unwrap();
+            let ch = self.bump().unwrap();
             buf.push(ch);
             status = 3;
           }
           _ => {
-            let ch = self.next().unwrap();
+            let ch = self.bump().unwrap();
             buf.push(ch);
             status = 2;
           }
@@ -248,12 +302,12 @@ impl Cursor<'_> {
         3 => match self.first() {
           // NOTE: 这里与单行注释不同，去往终结状态但取字符
           '/' => {
-            let ch = self.next().unwrap();
+            let ch = self.bump().unwrap();
             buf.push(ch);
             status = 4;
           }
           _ => {
-            let ch = self.next().unwrap();
+            let ch = self.bump().unwrap();
             buf.push(ch);
             status = 2;
           }
@@ -263,10 +317,14 @@ impl Cursor<'_> {
         _ => {}
       }
     }
+    // NOTE: 注释恰好在文件尾闭合
+    if status == 4 {
+      return Ok(Token::new(TokenType::Terminal(TokenKind::Comment), buf, Some(Point::new(row, column))));
+    }
     // TODO: 设置错误类型
     // NOTE: 当读取到文件尾却没到终结状态时，会产生未闭合错误
     // Err(Token::new(row, column, TokenKind::Comment, buf))
-    Err(LexerError::new("未闭合的注释".to_string(), row, column))
+    Err(LexerError::new(LexerErrorKind::UnterminatedBlockComment, row, column))
   }
 
   fn whitespace(&mut self) -> LexerResult {
@@ -278,7 +336,7 @@ impl Cursor<'_> {
     while !self.is_eof() {
       match status {
         0 => {
-          let ch = self.next().unwrap();
+          let ch = self.bump().unwrap();
           buf.push(ch);
           status = 1;
         }
@@ -302,13 +360,13 @@ impl Cursor<'_> {
       match status {
         // NOTE: 根据外部判断，0状态直接取
         0 => {
-          let ch = self.next().unwrap();
+          let ch = self.bump().unwrap();
           buf.push(ch);
           status = 1;
         }
         1 => match self.first() {
           ch if is_id_continue(ch) => {
-            let ch = self.next().unwrap();
+            let ch = self.bump().unwrap();
             buf.push(ch);
             status = 1;
           }
@@ -322,7 +380,7 @@ impl Cursor<'_> {
               match self.first() {
                 ch if is_whitespace_or_operator_or_delimiter(ch) => break, // 没有读到文件尾
                 _ => {
-                  let ch = self.next().unwrap();
+                  let ch = self.bump().unwrap();
                   buf.push(ch);
                 }
               }
@@ -331,7 +389,7 @@ impl Cursor<'_> {
             // 两种方式相同，可以合并
             // TODO: 设置错误类型
             // return Err(Token::new(row, column, TokenKind::Identifier, buf));
-            return Err(LexerError::new("标识符错误".to_string(), row, column));
+            return Err(LexerError::new(LexerErrorKind::MalformedIdentifier, row, column));
           }
         },
         2 => break,
@@ -349,473 +407,178 @@ impl Cursor<'_> {
     }
   }
 
+  // 先按C的预处理数读入整个词素：字母、数字、下划线、小数点，以及紧跟在指数标记后的正负号，
+  // 再按SysY的规则解析，这样 1a、0x1.8 之类的错误作为一个整体报告
   fn number(&mut self) -> LexerResult {
-    let row = self.row.clone();
-    let column = self.column.clone();
+    let row = self.row;
+    let column = self.column;
     let mut buf = String::from("");
+    let hex = self.first() == '0' && matches!(self.second(), 'x' | 'X');
 
-    let mut status = 0;
     while !self.is_eof() {
-      match status {
-        0 => match self.first() {
-          // NOTE: 由外层判断决定，此处不会有其他情况
-          '1'..='9' => {
-            let ch = self.next().unwrap();
-            buf.push(ch);
-            status = 1;
-          }
-          '0' => {
-            let ch = self.next().unwrap();
-            buf.push(ch);
-            status = 2;
-          }
-          _ => {}
-        },
-        1 => match self.first() {
-          '0'..='9' => {
-            let ch = self.next().unwrap();
-            buf.push(ch);
-            status = 7;
-          }
-          '.' => {
-            let ch = self.next().unwrap();
-            buf.push(ch);
-            status = 8;
-          }
-          ch if is_whitespace_or_operator_or_delimiter(ch) => status = 6,
-          _ => {
-            while !self.is_eof() {
-              match self.first() {
-                ch if is_whitespace_or_operator_or_delimiter(ch) => break, // 没有读到文件尾
-                _ => {
-                  let ch = self.next().unwrap();
-                  buf.push(ch);
-                }
-              }
-            }
-            // TODO: 设置错误类型
-            // return Err(Token::new(row, column, TokenKind::Identifier, buf));
-            return Err(LexerError::new("数字错误".to_string(), row, column));
-          }
-        },
-        2 => match self.first() {
-          // '0' => {
-          //     let ch = self.next().unwrap();
-          //     buf.push(ch);
-          //     status = 2;
-          // }
-          '1'..='7' => {
-            let ch = self.next().unwrap();
-            buf.push(ch);
-            status = 3;
-          }
-          'x' | 'X' => {
-            let ch = self.next().unwrap();
-            buf.push(ch);
-            status = 4;
-          }
-          'b' | 'B' => {
-            let ch = self.next().unwrap();
-            buf.push(ch);
-            status = 5;
-          }
-          '.' => {
-            let ch = self.next().unwrap();
-            buf.push(ch);
-            status = 8;
-          }
-          ch if is_whitespace_or_operator_or_delimiter(ch) => status = 6,
-          _ => {
-            while !self.is_eof() {
-              match self.first() {
-                ch if is_whitespace_or_operator_or_delimiter(ch) => break, // 没有读到文件尾
-                _ => {
-                  let ch = self.next().unwrap();
-                  buf.push(ch);
-                }
-              }
-            }
-            // TODO: 设置错误类型
-            // return Err(Token::new(row, column, TokenKind::Identifier, buf));
-            return Err(LexerError::new("数字错误".to_string(), row, column));
-          }
-        },
-        3 => match self.first() {
-          '0'..='7' => {
-            let ch = self.next().unwrap();
-            buf.push(ch);
-            status = 3;
-          }
-          ch if is_whitespace_or_operator_or_delimiter(ch) => status = 6,
-          _ => {
-            while !self.is_eof() {
-              match self.first() {
-                ch if is_whitespace_or_operator_or_delimiter(ch) => break, // 没有读到文件尾
-                _ => {
-                  let ch = self.next().unwrap();
-                  buf.push(ch);
-                }
-              }
-            }
-            // TODO: 设置错误类型
-            // return Err(Token::new(row, column, TokenKind::Identifier, buf));
-            return Err(LexerError::new("数字错误".to_string(), row, column));
-          }
-        },
-        4 => match self.first() {
-          '0'..='9' | 'a'..='f' | 'A'..='F' => {
-            let ch = self.next().unwrap();
-            buf.push(ch);
-            status = 4;
-          }
-          ch if is_whitespace_or_operator_or_delimiter(ch) => status = 6,
-          _ => {
-            while !self.is_eof() {
-              match self.first() {
-                ch if is_whitespace_or_operator_or_delimiter(ch) => break, // 没有读到文件尾
-                _ => {
-                  let ch = self.next().unwrap();
-                  buf.push(ch);
-                }
-              }
-            }
-            // TODO: 设置错误类型
-            // return Err(Token::new(row, column, TokenKind::Identifier, buf));
-            return Err(LexerError::new("数字错误".to_string(), row, column));
-          }
-        },
-        5 => match self.first() {
-          '0' | '1' => {
-            let ch = self.next().unwrap();
-            buf.push(ch);
-            status = 5;
-          }
-          ch if is_whitespace_or_operator_or_delimiter(ch) => status = 6,
-          _ => {
-            while !self.is_eof() {
-              match self.first() {
-                ch if is_whitespace_or_operator_or_delimiter(ch) => break, // 没有读到文件尾
-                _ => {
-                  let ch = self.next().unwrap();
-                  buf.push(ch);
-                }
-              }
-            }
-            // TODO: 设置错误类型
-            // return Err(Token::new(row, column, TokenKind::Identifier, buf));
-            return Err(LexerError::new("数字错误".to_string(), row, column));
-          }
-        },
-        6 => {
-          // return Ok(Token::new(
-          //   row,
-          //   column,
-          //   TokenKind::Number(Numbers::Integer),
-          //   buf,
-          // ));
-          return Ok(Token::new(TokenType::Terminal(TokenKind::Number(Numbers::Integer)), buf, Some(Point::new(row, column))));
-        }
-        7 => match self.first() {
-          '0'..='9' => {
-            let ch = self.next().unwrap();
-            buf.push(ch);
-            status = 7;
-          }
-          '.' => {
-            let ch = self.next().unwrap();
-            buf.push(ch);
-            status = 8;
-          }
-          'e' | 'E' => {
-            let ch = self.next().unwrap();
-            buf.push(ch);
-            status = 11;
-          }
-          ch if is_whitespace_or_operator_or_delimiter(ch) => status = 6,
-          _ => {
-            while !self.is_eof() {
-              match self.first() {
-                ch if is_whitespace_or_operator_or_delimiter(ch) => break, // 没有读到文件尾
-                _ => {
-                  let ch = self.next().unwrap();
-                  buf.push(ch);
-                }
-              }
-            }
-            // TODO: 设置错误类型
-            // return Err(Token::new(row, column, TokenKind::Identifier, buf));
-            return Err(LexerError::new("数字错误".to_string(), row, column));
-          }
-        },
-        8 => match self.first() {
-          '0'..='9' => {
-            let ch = self.next().unwrap();
-            buf.push(ch);
-            status = 9;
-          }
-          _ => {
-            while !self.is_eof() {
-              match self.first() {
-                ch if is_whitespace_or_operator_or_delimiter(ch) => break, // 没有读到文件尾
-                _ => {
-                  let ch = self.next().unwrap();
-                  buf.push(ch);
-                }
-              }
-            }
-            // TODO: 设置错误类型
-            // return Err(Token::new(row, column, TokenKind::Identifier, buf));
-            return Err(LexerError::new("数字错误".to_string(), row, column));
-          }
-        },
-        9 => match self.first() {
-          '0'..='9' => {
-            let ch = self.next().unwrap();
-            buf.push(ch);
-            status = 9;
-          }
-
-          'e' | 'E' => {
-            let ch = self.next().unwrap();
-            buf.push(ch);
-            status = 11;
-          }
-          ch if is_whitespace_or_operator_or_delimiter(ch) => status = 10,
-          _ => {
-            while !self.is_eof() {
-              match self.first() {
-                ch if is_whitespace_or_operator_or_delimiter(ch) => break, // 没有读到文件尾
-                _ => {
-                  let ch = self.next().unwrap();
-                  buf.push(ch);
-                }
-              }
-            }
-            // TODO: 设置错误类型
-            // return Err(Token::new(row, column, TokenKind::Identifier, buf));
-            return Err(LexerError::new("数字错误".to_string(), row, column));
-          }
-        },
-        10 => {
-          // return Ok(Token::new(
-          //   row,
-          //   column,
-          //   TokenKind::Number(Numbers::Float),
-          //   buf,
-          // ));
-          return Ok(Token::new(TokenType::Terminal(TokenKind::Number(Numbers::Float)), buf, Some(Point::new(row, column))));
-        }
-        11 => match self.first() {
-          '0'..='9' => {
-            let ch = self.next().unwrap();
-            buf.push(ch);
-            status = 11;
-          }
-          '+' | '-' => {
-            let ch = self.next().unwrap();
-            buf.push(ch);
-            status = 12;
-          }
-          ch if is_whitespace_or_operator_or_delimiter(ch) => status = 14,
-          _ => {
-            while !self.is_eof() {
-              match self.first() {
-                ch if is_whitespace_or_operator_or_delimiter(ch) => break, // 没有读到文件尾
-                _ => {
-                  let ch = self.next().unwrap();
-                  buf.push(ch);
-                }
-              }
-            }
-            // TODO: 设置错误类型
-            // return Err(Token::new(row, column, TokenKind::Identifier, buf));
-            return Err(LexerError::new("数字错误".to_string(), row, column));
-          }
-        },
-        12 => match self.first() {
-          '0'..='9' => {
-            let ch = self.next().unwrap();
-            buf.push(ch);
-            status = 13;
-          }
-          _ => {
-            while !self.is_eof() {
-              match self.first() {
-                ch if is_whitespace_or_operator_or_delimiter(ch) => break, // 没有读到文件尾
-                _ => {
-                  let ch = self.next().unwrap();
-                  buf.push(ch);
-                }
-              }
-            }
-            // TODO: 设置错误类型
-            // return Err(Token::new(row, column, TokenKind::Identifier, buf));
-            return Err(LexerError::new("数字错误".to_string(), row, column));
-          }
-        },
-        13 => match self.first() {
-          '0'..='9' => {
-            let ch = self.next().unwrap();
-            buf.push(ch);
-            status = 13;
-          }
-          ch if is_whitespace_or_operator_or_delimiter(ch) => status = 14,
-          _ => {
-            while !self.is_eof() {
-              match self.first() {
-                ch if is_whitespace_or_operator_or_delimiter(ch) => break, // 没有读到文件尾
-                _ => {
-                  let ch = self.next().unwrap();
-                  buf.push(ch);
-                }
-              }
-            }
-            // TODO: 设置错误类型
-            // return Err(Token::new(row, column, TokenKind::Identifier, buf));
-            return Err(LexerError::new("数字错误".to_string(), row, column));
-          }
-        },
-        14 => {
-          // return Ok(Token::new(
-          //   row,
-          //   column,
-          //   TokenKind::Number(Numbers::Exponent),
-          //   buf,
-          // ));
-          return Ok(Token::new(TokenType::Terminal(TokenKind::Number(Numbers::Exponent)), buf, Some(Point::new(row, column))));
-        }
-        _ => {}
+      let ch = self.first();
+      let after_exponent = match buf.chars().last() {
+        Some('p' | 'P') => hex,
+        Some('e' | 'E') => !hex,
+        _ => false,
+      };
+      if ch.is_ascii_alphanumeric() || ch == '_' || ch == '.' || (after_exponent && matches!(ch, '+' | '-')) {
+        let ch = self.bump().unwrap();
+        buf.push(ch);
+      } else {
+        break;
       }
     }
-    // FIX: 如果数字的结尾后没有其他东西，会走到这里，但是无法知道类型
-    // TODO: 考虑读到文件尾的情况
-    // Ok(Token::new(row, column, TokenKind::Number(Integer), buf))
-    Ok(Token::new(TokenType::Terminal(TokenKind::Number(Numbers::Integer)), buf, Some(Point::new(row, column))))
+    // 后面紧跟其他字符时，一直读到空白、运算符或界符为止，整体作为错误的数
+    if !self.is_eof() && !is_whitespace_or_operator_or_delimiter(self.first()) {
+      while !self.is_eof() && !is_whitespace_or_operator_or_delimiter(self.first()) {
+        let ch = self.bump().unwrap();
+        buf.push(ch);
+      }
+      return Err(LexerError::new(LexerErrorKind::MalformedNumber, row, column));
+    }
+
+    match parse_number(&buf) {
+      Ok(literal) => Ok(
+        Token::new(TokenType::Terminal(TokenKind::Number(literal.kind())), buf, Some(Point::new(row, column)))
+          .with_literal(literal),
+      ),
+      Err(kind) => Err(LexerError::new(kind, row, column)),
+    }
   }
 
-  // FIX：单个引号会被识别为正确
   fn character(&mut self) -> LexerResult {
     let row = self.row.clone();
     let column = self.column.clone();
     let mut buf = String::from("");
+    // NOTE: 非法的转义不立即返回，读完整个字符常量再报错
+    let mut invalid_escape = false;
 
     let mut status = 0;
     while !self.is_eof() {
       match status {
         0 => {
-          let ch = self.next().unwrap();
+          let ch = self.bump().unwrap();
           buf.push(ch);
           status = 1;
         }
         1 => match self.first() {
           '\\' => {
-            let ch = self.next().unwrap();
+            let ch = self.bump().unwrap();
             buf.push(ch);
             status = 4;
           }
-          // TODO: 设置错误类型
-          // '\'' => return Err(Token::new(row, column, TokenKind::Character, buf)),
-          '\'' => return Err(LexerError::new("字符错误".to_string(), row, column)),
+          // NOTE: 空的字符常量，取走第二个引号
+          '\'' => {
+            self.bump();
+            return Err(LexerError::new(LexerErrorKind::MalformedCharacter, row, column));
+          }
+          '\n' => break,
           _ => {
-            let ch = self.next().unwrap();
+            let ch = self.bump().unwrap();
             buf.push(ch);
             status = 2;
           }
         },
         2 => match self.first() {
           '\'' => {
-            let ch = self.next().unwrap();
+            let ch = self.bump().unwrap();
             buf.push(ch);
             status = 3;
           }
-          // TODO: 这里应该需要特殊考虑
-          // _ => return Err(Token::new(row, column, TokenKind::Character, buf)),
-          _ => return Err(LexerError::new("字符错误".to_string(), row, column)),
+          _ => return Err(LexerError::new(LexerErrorKind::MalformedCharacter, row, column)),
         },
         3 => break,
         4 => match self.first() {
-          'n' | 't' | '\\' | 'r' | '\'' | '"' => {
-            let ch = self.next().unwrap();
+          'n' | 't' | '\\' | 'r' | '\'' | '"' | '0' => {
+            let ch = self.bump().unwrap();
+            buf.push(ch);
+            status = 2;
+          }
+          '\n' => break,
+          _ => {
+            let ch = self.bump().unwrap();
             buf.push(ch);
+            invalid_escape = true;
             status = 2;
           }
-          // TODO: 这里应该需要特殊考虑
-          // _ => return Err(Token::new(row, column, TokenKind::Character, buf)),
-          _ => return Err(LexerError::new("字符错误".to_string(), row, column)),
         },
         _ => {}
       }
     }
-    // TODO: 考虑读到文件尾的问题
-    // Ok(Token::new(row, column, TokenKind::Character, buf))
+    // NOTE: 读到文件尾或换行时没有闭合
+    if status != 3 {
+      return Err(LexerError::new(LexerErrorKind::MalformedCharacter, row, column));
+    }
+    if invalid_escape {
+      return Err(LexerError::new(LexerErrorKind::InvalidEscape, row, column));
+    }
     Ok(Token::new(TokenType::Terminal(TokenKind::Character), buf, Some(Point::new(row, column))))
   }
-  // FIX: 单个引号会被识别为正确
+
   fn str(&mut self) -> LexerResult {
     let row = self.row.clone();
     let column = self.column.clone();
     let mut buf = String::from("");
+    // NOTE: 非法的转义不立即返回，读完整个字符串再报错
+    let mut invalid_escape = false;
 
     let mut status = 0;
     while !self.is_eof() {
       match status {
         0 => {
-          let ch = self.next().unwrap();
+          let ch = self.bump().unwrap();
           buf.push(ch);
           status = 1;
         }
         1 => match self.first() {
           '"' => {
-            let ch = self.next().unwrap();
+            let ch = self.bump().unwrap();
             buf.push(ch);
             status = 2;
           }
           '\\' => {
-            let ch = self.next().unwrap();
+            let ch = self.bump().unwrap();
             buf.push(ch);
             status = 3;
           }
-          '\n' => {
-            while !self.is_eof() {
-              match self.first() {
-                ch if is_whitespace_or_operator_or_delimiter(ch) => break, // 没有读到文件尾
-                _ => {
-                  let ch = self.next().unwrap();
-                  buf.push(ch);
-                }
-              }
-            }
-            // TODO: 设置错误类型
-            // return Err(Token::new(row, column, TokenKind::Identifier, buf));
-            return Err(LexerError::new("字符串错误".to_string(), row, column));
-          }
+          // NOTE: 字符串不能跨行，停在换行符之前
+          '\n' => return Err(LexerError::new(LexerErrorKind::UnterminatedString, row, column)),
           _ => {
-            let ch = self.next().unwrap();
+            let ch = self.bump().unwrap();
             buf.push(ch);
             status = 1;
           }
         },
-        // 2 => return Ok(Token::new(row, column, TokenKind::String, buf)),
-        2 => return Ok(Token::new(TokenType::Terminal(TokenKind::String), buf, Some(Point::new(row, column)))),
+        2 => break,
         3 => match self.first() {
-          'n' | 't' | '\\' | 'r' | '\'' | '"' => {
-            let ch = self.next().unwrap();
+          'n' | 't' | '\\' | 'r' | '\'' | '"' | '0' => {
+            let ch = self.bump().unwrap();
             buf.push(ch);
             status = 1;
           }
           '\n' => {
             // NOTE: 注意这里没有取换行符
-            self.next();
+            self.bump();
+            status = 1;
+          }
+          _ => {
+            let ch = self.bump().unwrap();
+            buf.push(ch);
+            invalid_escape = true;
             status = 1;
           }
-          // _ => return Err(Token::new(row, column, TokenKind::String, buf)),
-          _ => return Err(LexerError::new("字符串错误".to_string(), row, column)),
         },
         _ => {}
       }
     }
-    // Err(Token::new(row, column, TokenKind::String, buf))
-    Err(LexerError::new("字符串错误".to_string(), row, column))
+    if status != 2 {
+      return Err(LexerError::new(LexerErrorKind::UnterminatedString, row, column));
+    }
+    if invalid_escape {
+      return Err(LexerError::new(LexerErrorKind::InvalidEscape, row, column));
+    }
+    Ok(Token::new(TokenType::Terminal(TokenKind::String), buf, Some(Point::new(row, column))))
   }
 
   fn operator(&mut self) -> LexerResult {
@@ -828,87 +591,87 @@ impl Cursor<'_> {
       match status {
         0 => match self.first() {
           '+' => {
-            let ch = self.next().unwrap();
+            let ch = self.bump().unwrap();
             buf.push(ch);
             status = 1;
           }
           '-' => {
-            let ch = self.next().unwrap();
+            let ch = self.bump().unwrap();
             buf.push(ch);
             status = 2;
           }
           '*' => {
-            let ch = self.next().unwrap();
+            let ch = self.bump().unwrap();
             buf.push(ch);
             status = 3;
           }
           '/' => {
-            let ch = self.next().unwrap();
+            let ch = self.bump().unwrap();
             buf.push(ch);
             status = 4;
           }
           '%' => {
-            let ch = self.next().unwrap();
+            let ch = self.bump().unwrap();
             buf.push(ch);
             status = 5;
           }
           '>' => {
-            let ch = self.next().unwrap();
+            let ch = self.bump().unwrap();
             buf.push(ch);
             status = 6;
           }
           '<' => {
-            let ch = self.next().unwrap();
+            let ch = self.bump().unwrap();
             buf.push(ch);
             status = 7;
           }
           '&' => {
-            let ch = self.next().unwrap();
+            let ch = self.bump().unwrap();
             buf.push(ch);
             status = 8;
           }
           '|' => {
-            let ch = self.next().unwrap();
+            let ch = self.bump().unwrap();
             buf.push(ch);
             status = 9;
           }
           '!' => {
-            let ch = self.next().unwrap();
+            let ch = self.bump().unwrap();
             buf.push(ch);
             status = 10;
           }
           '^' => {
-            let ch = self.next().unwrap();
+            let ch = self.bump().unwrap();
             buf.push(ch);
             status = 11;
           }
           '?' => {
-            let ch = self.next().unwrap();
+            let ch = self.bump().unwrap();
             buf.push(ch);
             status = 12;
           }
           '=' => {
-            let ch = self.next().unwrap();
+            let ch = self.bump().unwrap();
             buf.push(ch);
             status = 13;
           }
           '(' => {
-            let ch = self.next().unwrap();
+            let ch = self.bump().unwrap();
             buf.push(ch);
             status = 14;
           }
           ')' => {
-            let ch = self.next().unwrap();
+            let ch = self.bump().unwrap();
             buf.push(ch);
             status = 15;
           }
           '[' => {
-            let ch = self.next().unwrap();
+            let ch = self.bump().unwrap();
             buf.push(ch);
             status = 16;
           }
           ']' => {
-            let ch = self.next().unwrap();
+            let ch = self.bump().unwrap();
             buf.push(ch);
             status = 17;
           }
@@ -916,162 +679,162 @@ impl Cursor<'_> {
         },
         1 => match self.first() {
           '+' => {
-            let ch = self.next().unwrap();
+            let ch = self.bump().unwrap();
             buf.push(ch);
             status = 18;
           }
           '=' => {
-            let ch = self.next().unwrap();
+            let ch = self.bump().unwrap();
             buf.push(ch);
             status = 19;
           }
           _ => {
-            // let ch = self.next().unwrap();
+            // let ch = self.bump().unwrap();
             // buf.push(ch);
             status = 20;
           }
         },
         2 => match self.first() {
           '-' => {
-            let ch = self.next().unwrap();
+            let ch = self.bump().unwrap();
             buf.push(ch);
             status = 21;
           }
           '=' => {
-            let ch = self.next().unwrap();
+            let ch = self.bump().unwrap();
             buf.push(ch);
             status = 22;
           }
           _ => {
-            // let ch = self.next().unwrap();
+            // let ch = self.bump().unwrap();
             // buf.push(ch);
             status = 23;
           }
         },
         3 => match self.first() {
           '=' => {
-            let ch = self.next().unwrap();
+            let ch = self.bump().unwrap();
             buf.push(ch);
             status = 24;
           }
           _ => {
-            // let ch = self.next().unwrap();
+            // let ch = self.bump().unwrap();
             // buf.push(ch);
             status = 25;
           }
         },
         4 => match self.first() {
           '=' => {
-            let ch = self.next().unwrap();
+            let ch = self.bump().unwrap();
             buf.push(ch);
             status = 26;
           }
           _ => {
-            // let ch = self.next().unwrap();
+            // let ch = self.bump().unwrap();
             // buf.push(ch);
             status = 27
           }
         },
         5 => match self.first() {
           '=' => {
-            let ch = self.next().unwrap();
+            let ch = self.bump().unwrap();
             buf.push(ch);
             status = 28;
           }
           _ => {
-            // let ch = self.next().unwrap();
+            // let ch = self.bump().unwrap();
             // buf.push(ch);
             status = 29;
           }
         },
         6 => match self.first() {
           '>' => {
-            let ch = self.next().unwrap();
+            let ch = self.bump().unwrap();
             buf.push(ch);
             status = 30;
           }
           '=' => {
-            let ch = self.next().unwrap();
+            let ch = self.bump().unwrap();
             buf.push(ch);
             status = 31;
           }
           _ => {
-            // let ch = self.next().unwrap();
+            // let ch = self.bump().unwrap();
             // buf.push(ch);
             status = 32;
           }
         },
         7 => match self.first() {
           '<' => {
-            let ch = self.next().unwrap();
+            let ch = self.bump().unwrap();
             buf.push(ch);
             status = 33;
           }
           '=' => {
-            let ch = self.next().unwrap();
+            let ch = self.bump().unwrap();
             buf.push(ch);
             status = 34;
           }
           _ => {
-            // let ch = self.next().unwrap();
+            // let ch = self.bump().unwrap();
             // buf.push(ch);
             status = 35;
           }
         },
         8 => match self.first() {
           '&' => {
-            let ch = self.next().unwrap();
+            let ch = self.bump().unwrap();
             buf.push(ch);
             status = 36;
           }
           '=' => {
-            let ch = self.next().unwrap();
+            let ch = self.bump().unwrap();
             buf.push(ch);
             status = 37;
           }
           _ => {
-            // let ch = self.next().unwrap();
+            // let ch = self.bump().unwrap();
             // buf.push(ch);
             status = 38;
           }
         },
         9 => match self.first() {
           '|' => {
-            let ch = self.next().unwrap();
+            let ch = self.bump().unwrap();
             buf.push(ch);
             status = 39;
           }
           '=' => {
-            let ch = self.next().unwrap();
+            let ch = self.bump().unwrap();
             buf.push(ch);
             status = 40;
           }
           _ => {
-            // let ch = self.next().unwrap();
+            // let ch = self.bump().unwrap();
             // buf.push(ch);
             status = 41;
           }
         },
         10 => match self.first() {
           '=' => {
-            let ch = self.next().unwrap();
+            let ch = self.bump().unwrap();
             buf.push(ch);
             status = 42;
           }
           _ => {
-            // let ch = self.next().unwrap();
+            // let ch = self.bump().unwrap();
             // buf.push(ch);
             status = 43;
           }
         },
         11 => match self.first() {
           '=' => {
-            let ch = self.next().unwrap();
+            let ch = self.bump().unwrap();
             buf.push(ch);
             status = 44;
           }
           _ => {
-            // let ch = self.next().unwrap();
+            // let ch = self.bump().unwrap();
             // buf.push(ch);
             status = 45;
           }
@@ -1087,12 +850,12 @@ impl Cursor<'_> {
         }
         13 => match self.first() {
           '=' => {
-            let ch = self.next().unwrap();
+            let ch = self.bump().unwrap();
             buf.push(ch);
             status = 46;
           }
           _ => {
-            // let ch = self.next().unwrap();
+            // let ch = self.bump().unwrap();
             // buf.push(ch);
             status = 47;
           }
@@ -1243,12 +1006,12 @@ impl Cursor<'_> {
         }
         30 => match self.first() {
           '=' => {
-            let ch = self.next().unwrap();
+            let ch = self.bump().unwrap();
             buf.push(ch);
             status = 48;
           }
           _ => {
-            // let ch = self.next().unwrap();
+            // let ch = self.bump().unwrap();
             // buf.push(ch);
             status = 49;
           }
@@ -1273,12 +1036,12 @@ impl Cursor<'_> {
         }
         33 => match self.first() {
           '=' => {
-            let ch = self.next().unwrap();
+            let ch = self.bump().unwrap();
             buf.push(ch);
             status = 50;
           }
           _ => {
-            // let ch = self.next().unwrap();
+            // let ch = self.bump().unwrap();
             // buf.push(ch);
             status = 51;
           }
@@ -1462,7 +1225,7 @@ impl Cursor<'_> {
   //   while !self.is_eof() {
   //     match status {
   //       0 => {
-  //         let ch = self.next().unwrap();
+  //         let ch = self.bump().unwrap();
   //         buf.push(ch);
   //         status = 1;
   //       }
@@ -1483,14 +1246,14 @@ impl Cursor<'_> {
     while !self.is_eof() {
       match status {
         0 => {
-          let ch = self.next().unwrap();
+          let ch = self.bump().unwrap();
           buf.push(ch);
           status = 1;
         }
         1 => match self.first() {
           ch if is_whitespace_or_operator_or_delimiter(ch) => status = 2,
           _ => {
-            let ch = self.next().unwrap();
+            let ch = self.bump().unwrap();
             buf.push(ch);
             status = 1;
           }
@@ -1500,6 +1263,6 @@ impl Cursor<'_> {
       }
     }
     // Err(Token::new(row, column, TokenKind::Unknown, buf))
-    Err(LexerError::new("未知的字符".to_string(), row, column))
+    Err(LexerError::new(LexerErrorKind::UnknownCharacter, row, column))
   }
 }
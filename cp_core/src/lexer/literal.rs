@@ -0,0 +1,92 @@
+use crate::lexer::{LexerErrorKind, Literal};
+
+// 数值常量的解析，规则与C相同（不带后缀）：
+// 整数有十进制、以0开头的八进制和以0x开头的十六进制；
+// 浮点数有十进制（1.5、.5、5.、1e10、1.5e-3）和十六进制（0x1.8p3，必须有p指数）
+
+pub fn parse_number(text: &str) -> Result<Literal, LexerErrorKind> {
+  if let Some(hex) = text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+    if hex.contains(['.', 'p', 'P']) {
+      hex_float(hex)
+    } else {
+      int(hex, 16)
+    }
+  } else if text.contains(['.', 'e', 'E']) {
+    decimal_float(text)
+  } else if text.len() > 1 && text.starts_with('0') {
+    int(&text[1..], 8)
+  } else {
+    int(text, 10)
+  }
+}
+
+// 超过i32但不超过u32的值按32位截断，例如 -2147483648 中的 2147483648
+fn int(digits: &str, radix: u32) -> Result<Literal, LexerErrorKind> {
+  if digits.is_empty() || !digits.chars().all(|ch| ch.is_digit(radix)) {
+    return Err(LexerErrorKind::MalformedNumber);
+  }
+  match u32::from_str_radix(digits, radix) {
+    Ok(value) => Ok(Literal::Int(value as i32)),
+    Err(_) => Err(LexerErrorKind::IntegerOverflow),
+  }
+}
+
+fn decimal_float(text: &str) -> Result<Literal, LexerErrorKind> {
+  let (mantissa, exponent) = split_exponent(text, ['e', 'E']);
+  if !is_mantissa(mantissa, 10) || !exponent.is_none_or(is_exponent) {
+    return Err(LexerErrorKind::MalformedNumber);
+  }
+  let value: f32 = text.parse().map_err(|_| LexerErrorKind::MalformedNumber)?;
+  finite(value)
+}
+
+// 尾数逐位累加到f64中，再乘以2的幂
+fn hex_float(text: &str) -> Result<Literal, LexerErrorKind> {
+  let (mantissa, Some(exponent)) = split_exponent(text, ['p', 'P']) else {
+    return Err(LexerErrorKind::MalformedNumber);
+  };
+  if !is_mantissa(mantissa, 16) || !is_exponent(exponent) {
+    return Err(LexerErrorKind::MalformedNumber);
+  }
+  let (int_part, frac_part) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+  let value = int_part
+    .chars()
+    .chain(frac_part.chars())
+    .fold(0.0f64, |value, ch| value * 16.0 + ch.to_digit(16).unwrap() as f64);
+  if value == 0.0 {
+    return Ok(Literal::float(0.0));
+  }
+  // 指数超出i32时结果必然溢出或为0
+  let exponent = exponent
+    .parse::<i32>()
+    .unwrap_or(if exponent.starts_with('-') { i32::MIN } else { i32::MAX });
+  let scale = exponent.saturating_sub(4 * frac_part.len() as i32);
+  finite((value * 2.0f64.powi(scale)) as f32)
+}
+
+fn finite(value: f32) -> Result<Literal, LexerErrorKind> {
+  if value.is_infinite() {
+    Err(LexerErrorKind::FloatOverflow)
+  } else {
+    Ok(Literal::float(value))
+  }
+}
+
+fn split_exponent(text: &str, markers: [char; 2]) -> (&str, Option<&str>) {
+  match text.find(markers) {
+    Some(i) => (&text[..i], Some(&text[i + 1..])),
+    None => (text, None),
+  }
+}
+
+// 小数点两侧至少有一侧有数字
+fn is_mantissa(text: &str, radix: u32) -> bool {
+  let (int_part, frac_part) = text.split_once('.').unwrap_or((text, ""));
+  let digits = |part: &str| part.chars().all(|ch| ch.is_digit(radix));
+  !(int_part.is_empty() && frac_part.is_empty()) && digits(int_part) && digits(frac_part)
+}
+
+fn is_exponent(text: &str) -> bool {
+  let digits = text.strip_prefix(['+', '-']).unwrap_or(text);
+  !digits.is_empty() && digits.chars().all(|ch| ch.is_ascii_digit())
+}
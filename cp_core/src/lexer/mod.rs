@@ -1,7 +1,289 @@
 mod cursor;
+mod literal;
+mod stream;
+mod trivia;
 
 pub use crate::token::*;
-use crate::error::LexerError;
+pub use crate::error::{LexerError, LexerErrorKind};
+use crate::lexgen::{LexerSpec, LexerTable};
+use crate::relexer::ReLexer;
 pub use cursor::Cursor;
+pub use literal::parse_number;
+pub use stream::{Lexeme, Recovering, SkipTrivia, TokenStream};
+pub use trivia::{lossless_tokens, to_source, TriviaToken};
 
-type LexerResult = Result<Token, LexerError>;
\ No newline at end of file
+pub type LexerResult = Result<Token, LexerError>;
+
+// 语法分析前端可选的词法分析器：手写的Cursor、基于logos的ReLexer，
+// 或由文法文件中的%lex和%skip规则生成的表驱动词法分析器
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum LexerKind {
+  #[default]
+  Cursor,
+  ReLexer,
+  Generated(LexerTable),
+}
+
+impl LexerKind {
+  // generated需要读取文法文件grammar中的词法规则
+  pub fn from_name(name: &str, grammar: &str) -> Result<Self, String> {
+    match name {
+      "cursor" => Ok(LexerKind::Cursor),
+      "relexer" | "logos" => Ok(LexerKind::ReLexer),
+      "generated" => LexerSpec::load(grammar)
+        .and_then(|spec| spec.build())
+        .map(LexerKind::Generated)
+        .map_err(|e| match e.line {
+          0 => e.message,
+          _ => format!("in lexer rules of `{}`, {}", grammar, e),
+        }),
+      _ => Err(format!("unknown lexer `{}`", name)),
+    }
+  }
+
+  // 按需产生词素，包括空白、注释和Err词素，诊断信息收集到errors中
+  pub fn stream<'a>(&'a self, source: &'a str, errors: &'a mut Vec<LexerError>) -> Box<dyn Iterator<Item = Token> + 'a> {
+    match self {
+      LexerKind::Cursor => Box::new(Cursor::new(source).recovering(errors)),
+      LexerKind::ReLexer => Box::new(ReLexer::stream(source, errors)),
+      LexerKind::Generated(table) => Box::new(table.stream(source, errors)),
+    }
+  }
+
+  pub fn tokenize(&self, source: &str) -> (Vec<Token>, Vec<LexerError>) {
+    let mut errors = Vec::new();
+    let tokens = self.stream(source, &mut errors).collect();
+    (tokens, errors)
+  }
+}
+
+// 单元测试
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::source::SourceFile;
+
+  fn tokens(source: &str) -> Vec<Token> {
+    let mut cursor = Cursor::new(source);
+    let mut tokens = Vec::new();
+    while !cursor.is_eof() {
+      if let Ok(token) = cursor.advance_token() {
+        tokens.push(token);
+      }
+    }
+    tokens
+  }
+
+  #[test]
+  fn test_token_spans() {
+    let source = "int a;\n/* 注释 */ b = 10;\n";
+    let file = SourceFile::new("test.sy", source);
+    let tokens = tokens(source);
+    // 区间首尾相接，覆盖整个源程序
+    assert_eq!(*tokens[0].get_span().unwrap().get_start(), 0);
+    for pair in tokens.windows(2) {
+      assert_eq!(pair[0].get_span().unwrap().get_end(), pair[1].get_span().unwrap().get_start());
+    }
+    assert_eq!(*tokens.last().unwrap().get_span().unwrap().get_end(), source.len());
+
+    for token in &tokens {
+      let span = token.get_span().unwrap();
+      assert_eq!(file.slice(&span), token.get_value());
+      assert_eq!(file.point(*span.get_start()), *span.get_start_point());
+      assert_eq!(file.point(*span.get_end()), *span.get_end_point());
+      assert_eq!(*token.get_pos(), Some(*span.get_start_point()));
+    }
+
+    let semicolon = &tokens[3];
+    assert_eq!(semicolon.get_value(), ";");
+    assert_eq!(semicolon.get_pos().unwrap(), Point::new(1, 6));
+    let ten = tokens.iter().find(|t| t.get_value() == "10").unwrap();
+    assert_eq!(ten.get_span().unwrap(), Span::new(24, 26, Point::new(2, 14), Point::new(2, 16)));
+  }
+
+  #[test]
+  fn test_error_span() {
+    let mut cursor = Cursor::new("a\n  /* open");
+    let err = loop {
+      if let Err(err) = cursor.advance_token() {
+        break err;
+      }
+    };
+    assert_eq!((err.row, err.col), (2, 3));
+    assert_eq!(err.span, Span::new(4, 11, Point::new(2, 3), Point::new(2, 10)));
+  }
+
+  #[test]
+  fn test_lossless_tokens() {
+    let source = "// 头部注释\nint main() { // 入口\r\n  return 0; /* 结束 */\n}\n\n/* 文件末尾 */";
+    let (tokens, errors) = lossless_tokens(source);
+    assert!(errors.is_empty());
+    assert_eq!(to_source(&tokens), source);
+
+    let values: Vec<_> = tokens.iter().map(|t| t.token.get_value().as_str()).collect();
+    assert_eq!(values, ["int", "main", "(", ")", "{", "return", "0", ";", "}", ""]);
+    let trivia = |list: &[Token]| list.iter().map(|t| t.get_value().clone()).collect::<Vec<_>>();
+    assert_eq!(trivia(&tokens[0].leading), ["// 头部注释", "\n"]);
+    assert_eq!(trivia(&tokens[0].trailing), [" "]);
+    // 同一行的注释属于前一个词素，换行及其后的缩进属于下一个词素
+    // 行注释包含CRLF中的\r
+    assert_eq!(trivia(&tokens[4].trailing), [" ", "// 入口\r"]);
+    assert_eq!(trivia(&tokens[5].leading), ["\n  "]);
+    assert_eq!(trivia(&tokens[7].trailing), [" ", "/* 结束 */"]);
+    assert!(tokens[9].is_eof());
+    assert_eq!(trivia(&tokens[9].leading), ["\n\n", "/* 文件末尾 */"]);
+    assert_eq!(*tokens[9].token.get_span().unwrap().get_start(), source.len());
+
+    // 出错的部分作为Err词素，同样可以还原
+    let source = "int a = 1@;\n";
+    let (tokens, errors) = lossless_tokens(source);
+    assert_eq!(errors.len(), 1);
+    assert_eq!(to_source(&tokens), source);
+  }
+
+  #[test]
+  fn test_error_recovery() {
+    let source = "int 1a = '';\nx = \"a\\qb\" + $;\ns = \"open\n/* never closed";
+    let (tokens, errors) = Cursor::new(source).tokenize();
+    let kinds: Vec<_> = errors.iter().map(|e| (e.kind, e.row, e.col)).collect();
+    assert_eq!(
+      kinds,
+      [
+        (LexerErrorKind::MalformedNumber, 1, 5),
+        (LexerErrorKind::MalformedCharacter, 1, 10),
+        (LexerErrorKind::InvalidEscape, 2, 5),
+        (LexerErrorKind::UnknownCharacter, 2, 14),
+        (LexerErrorKind::UnterminatedString, 3, 5),
+        (LexerErrorKind::UnterminatedBlockComment, 4, 1),
+      ]
+    );
+    // 每个错误对应一个Err词素，之后的词素照常识别
+    let error_tokens: Vec<_> = tokens.iter().filter(|t| t.is_error()).map(|t| t.get_value().as_str()).collect();
+    assert_eq!(error_tokens, ["1a", "''", "\"a\\qb\"", "$", "\"open", "/* never closed"]);
+    let text: String = tokens.iter().map(|t| t.get_value().as_str()).collect();
+    assert_eq!(text, source);
+    assert!(tokens.iter().any(|t| t.get_value() == "+"));
+    assert_eq!(errors[2].to_string(), "invalid escape sequence");
+  }
+
+  #[test]
+  fn test_token_stream() {
+    let source = "a /* b */ + 1@ ;";
+    let all: Vec<_> = Cursor::new(source).collect();
+    assert_eq!(all.len(), 9);
+    assert_eq!(all.iter().filter(|result| result.is_err()).count(), 1);
+
+    let values: Vec<_> = Cursor::new(source)
+      .skip_trivia()
+      .map(|result| result.map(|token| token.get_value().clone()).map_err(|err| err.kind))
+      .collect();
+    assert_eq!(
+      values,
+      [
+        Ok("a".to_string()),
+        Ok("+".to_string()),
+        Err(LexerErrorKind::MalformedNumber),
+        Ok(";".to_string()),
+      ]
+    );
+
+    let mut errors = Vec::new();
+    let tokens: Vec<_> = Cursor::new(source).recovering(&mut errors).skip_trivia().collect();
+    let values: Vec<_> = tokens.iter().map(|token| token.get_value().as_str()).collect();
+    assert_eq!(values, ["a", "+", "1@", ";"]);
+    assert!(tokens[2].is_error());
+    assert_eq!(errors.len(), 1);
+  }
+
+  #[test]
+  fn test_relexer() {
+    let source = "int main() {\n  // 注释\n  while (a >= 0x1F) { a = a % 3 - 1.5; break; }\n  return a == 0;\n}\n";
+    let (expected, _) = LexerKind::Cursor.tokenize(source);
+    let (tokens, errors) = LexerKind::ReLexer.tokenize(source);
+    assert!(errors.is_empty());
+    // 两个前端产生的有效词素完全相同，包括类型和区间
+    let significant = |tokens: Vec<Token>| tokens.into_iter().filter(|t| !t.is_trivia()).collect::<Vec<_>>();
+    assert_eq!(significant(tokens.clone()), significant(expected));
+    let text: String = tokens.iter().map(|t| t.get_value().as_str()).collect();
+    assert_eq!(text, source);
+    let brk = tokens.iter().find(|t| t.get_value() == "break").unwrap();
+    assert_eq!(*brk.get_type(), TokenType::Terminal(TokenKind::KeyWord(KeyWords::Break)));
+
+    let (tokens, errors) = LexerKind::ReLexer.tokenize("a = $;");
+    assert_eq!(errors.len(), 1);
+    assert_eq!((errors[0].kind, errors[0].row, errors[0].col), (LexerErrorKind::UnknownCharacter, 1, 5));
+    assert!(tokens[4].is_error());
+    assert_eq!(LexerKind::from_name("relexer", "../g3.txt"), Ok(LexerKind::ReLexer));
+    assert!(matches!(LexerKind::from_name("generated", "../g3.txt"), Ok(LexerKind::Generated(_))));
+    assert_eq!(LexerKind::from_name("lex", "../g3.txt"), Err("unknown lexer `lex`".to_string()));
+  }
+
+  #[test]
+  fn test_number_literals() {
+    let int = |text: &str| parse_number(text).map(|l| l.as_i32());
+    assert_eq!(int("0"), Ok(Some(0)));
+    assert_eq!(int("123"), Ok(Some(123)));
+    assert_eq!(int("017"), Ok(Some(15)));
+    assert_eq!(int("0x1F"), Ok(Some(31)));
+    assert_eq!(int("0XaB"), Ok(Some(171)));
+    // 超过i32但不超过u32的按32位截断
+    assert_eq!(int("2147483648"), Ok(Some(i32::MIN)));
+    assert_eq!(int("0xFFFFFFFF"), Ok(Some(-1)));
+    assert_eq!(int("4294967296"), Err(LexerErrorKind::IntegerOverflow));
+    assert_eq!(int("08"), Err(LexerErrorKind::MalformedNumber));
+    assert_eq!(int("0x"), Err(LexerErrorKind::MalformedNumber));
+
+    let float = |text: &str| parse_number(text).map(|l| l.as_f32());
+    assert_eq!(float("1.5"), Ok(Some(1.5)));
+    assert_eq!(float(".5"), Ok(Some(0.5)));
+    assert_eq!(float("5."), Ok(Some(5.0)));
+    assert_eq!(float("1e10"), Ok(Some(1e10)));
+    assert_eq!(float("2.5E-3"), Ok(Some(2.5e-3)));
+    assert_eq!(float("0x1.8p3"), Ok(Some(12.0)));
+    assert_eq!(float("0x.8P-1"), Ok(Some(0.25)));
+    assert_eq!(float("0xAp0"), Ok(Some(10.0)));
+    assert_eq!(float("1e39"), Err(LexerErrorKind::FloatOverflow));
+    assert_eq!(float("0x1p128"), Err(LexerErrorKind::FloatOverflow));
+    assert_eq!(float("1e"), Err(LexerErrorKind::MalformedNumber));
+    assert_eq!(float("1.2.3"), Err(LexerErrorKind::MalformedNumber));
+    assert_eq!(float("0x1.8"), Err(LexerErrorKind::MalformedNumber));
+
+    // 两个词法分析器都附带解析好的值
+    let source = "a=0x1.8p3+.5e1-010;c=99999999999;";
+    for lexer in [LexerKind::Cursor, LexerKind::ReLexer] {
+      let (tokens, errors) = lexer.tokenize(source);
+      let numbers: Vec<_> = tokens.iter().filter_map(|t| t.get_literal().map(|l| (t.get_value().as_str(), l))).collect();
+      assert_eq!(
+        numbers,
+        [
+          ("0x1.8p3", Literal::float(12.0)),
+          (".5e1", Literal::float(5.0)),
+          ("010", Literal::Int(8)),
+        ],
+        "{:?}",
+        lexer
+      );
+      let number = tokens.iter().find(|t| t.get_value() == "0x1.8p3").unwrap();
+      assert_eq!(*number.get_type(), TokenType::Terminal(TokenKind::Number(Numbers::Float)));
+      let kinds: Vec<_> = errors.iter().map(|e| e.kind).collect();
+      assert_eq!(kinds.last(), Some(&LexerErrorKind::IntegerOverflow), "{:?}", lexer);
+    }
+    let (_, errors) = Cursor::new("1e+2f").tokenize();
+    assert_eq!(errors[0].kind, LexerErrorKind::MalformedNumber);
+    assert_eq!(errors[0].span.len(), 5);
+  }
+
+  #[test]
+  fn test_source_file() {
+    let file = SourceFile::new("test.sy", "ab\r\n\nc");
+    assert_eq!(file.line_count(), 3);
+    assert_eq!(file.line(1), Some("ab"));
+    assert_eq!(file.line(2), Some(""));
+    assert_eq!(file.line(3), Some("c"));
+    assert_eq!(file.line(4), None);
+    assert_eq!(file.point(0), Point::new(1, 1));
+    assert_eq!(file.point(4), Point::new(2, 1));
+    assert_eq!(file.point(5), Point::new(3, 1));
+    assert_eq!(file.point(100), Point::new(3, 2));
+  }
+}
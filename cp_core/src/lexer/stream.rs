@@ -0,0 +1,84 @@
+use crate::lexer::{Cursor, LexerError, LexerResult, Token};
+
+// 流式的词法分析接口：Cursor按需逐个产生词素，适配器在此基础上过滤和恢复错误
+
+// 包括空白和注释，出错时产生Err，之后从出错部分的下一个字符继续
+impl Iterator for Cursor<'_> {
+  type Item = LexerResult;
+
+  fn next(&mut self) -> Option<LexerResult> {
+    if self.is_eof() {
+      None
+    } else {
+      Some(self.advance_token())
+    }
+  }
+}
+
+// 空白和注释在分析中没有意义，称为附属成分
+pub trait Lexeme {
+  fn is_trivia(&self) -> bool;
+}
+
+impl Lexeme for Token {
+  fn is_trivia(&self) -> bool {
+    self.is_whitespace() || self.is_comment()
+  }
+}
+
+impl Lexeme for LexerResult {
+  fn is_trivia(&self) -> bool {
+    matches!(self, Ok(token) if token.is_trivia())
+  }
+}
+
+pub trait TokenStream: Iterator + Sized {
+  fn skip_trivia(self) -> SkipTrivia<Self>
+  where
+    Self::Item: Lexeme,
+  {
+    SkipTrivia { inner: self }
+  }
+}
+
+impl<I: Iterator> TokenStream for I {}
+
+pub struct SkipTrivia<I> {
+  inner: I,
+}
+
+impl<I> Iterator for SkipTrivia<I>
+where
+  I: Iterator,
+  I::Item: Lexeme,
+{
+  type Item = I::Item;
+
+  fn next(&mut self) -> Option<I::Item> {
+    self.inner.find(|lexeme| !lexeme.is_trivia())
+  }
+}
+
+// 出错时产生Err词素，诊断信息收集到errors中
+pub struct Recovering<'a, 'e> {
+  cursor: Cursor<'a>,
+  errors: &'e mut Vec<LexerError>,
+}
+
+impl<'a> Cursor<'a> {
+  pub fn recovering(self, errors: &mut Vec<LexerError>) -> Recovering<'a, '_> {
+    Recovering { cursor: self, errors }
+  }
+}
+
+impl Iterator for Recovering<'_, '_> {
+  type Item = Token;
+
+  fn next(&mut self) -> Option<Token> {
+    if self.cursor.is_eof() {
+      None
+    } else {
+      Some(self.cursor.recover_token(self.errors))
+    }
+  }
+}
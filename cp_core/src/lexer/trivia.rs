@@ -0,0 +1,99 @@
+use std::mem;
+
+use crate::lexer::{Cursor, LexerError, Span, Token, TokenKind, TokenType};
+
+// 无损的词法分析结果：空白和注释作为附属成分挂在有效词素上，
+// 按顺序拼接所有词素和附属成分即得到原始源程序
+
+// 后随成分是词素之后、换行之前的空白和注释，其余的都属于下一个词素的前导成分
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TriviaToken {
+  pub leading: Vec<Token>,
+  pub token: Token,
+  pub trailing: Vec<Token>,
+}
+
+impl TriviaToken {
+  // 最后一个元素是值为空的结束符，文件末尾的空白和注释是它的前导成分
+  pub fn is_eof(&self) -> bool {
+    matches!(self.token.get_type(), TokenType::Terminal(TokenKind::End))
+  }
+
+  pub fn write_to(&self, out: &mut String) {
+    for trivia in &self.leading {
+      out.push_str(trivia.get_value());
+    }
+    out.push_str(self.token.get_value());
+    for trivia in &self.trailing {
+      out.push_str(trivia.get_value());
+    }
+  }
+}
+
+// 还原源程序
+pub fn to_source(tokens: &[TriviaToken]) -> String {
+  let mut out = String::new();
+  for token in tokens {
+    token.write_to(&mut out);
+  }
+  out
+}
+
+// 词法错误不中断分析，出错的部分作为Err词素
+pub fn lossless_tokens(source: &str) -> (Vec<TriviaToken>, Vec<LexerError>) {
+  let mut cursor = Cursor::new(source);
+  let mut tokens: Vec<TriviaToken> = Vec::new();
+  let mut leading = Vec::new();
+  let mut errors = Vec::new();
+  // 是否还在上一个有效词素所在的行
+  let mut same_line = false;
+  while !cursor.is_eof() {
+    let token = cursor.recover_token(&mut errors);
+    if token.is_whitespace() || token.is_comment() {
+      if token.get_value().contains('\n') {
+        same_line = false;
+      }
+      match tokens.last_mut() {
+        Some(last) if same_line => push_trivia(&mut last.trailing, token),
+        _ => push_trivia(&mut leading, token),
+      }
+    } else {
+      tokens.push(TriviaToken {
+        leading: mem::take(&mut leading),
+        token,
+        trailing: Vec::new(),
+      });
+      same_line = true;
+    }
+  }
+
+  let (offset, point) = (cursor.offset(), cursor.point());
+  let eof = Token::new(TokenType::Terminal(TokenKind::End), String::new(), None)
+    .with_span(Span::new(offset, offset, point, point));
+  tokens.push(TriviaToken {
+    leading,
+    token: eof,
+    trailing: Vec::new(),
+  });
+  (tokens, errors)
+}
+
+// 相邻的空白合并为一个
+fn push_trivia(list: &mut Vec<Token>, trivia: Token) {
+  match list.last_mut() {
+    Some(last) if last.is_whitespace() && trivia.is_whitespace() => {
+      let (Some(first), Some(second)) = (last.get_span(), trivia.get_span()) else {
+        unreachable!("tokens from Cursor always have a span");
+      };
+      let span = Span::new(
+        *first.get_start(),
+        *second.get_end(),
+        *first.get_start_point(),
+        *second.get_end_point(),
+      );
+      let value = format!("{}{}", last.get_value(), trivia.get_value());
+      *last = Token::new(trivia.get_type().clone(), value, None).with_span(span);
+    }
+    _ => list.push(trivia),
+  }
+}
@@ -0,0 +1,172 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::ops::Range;
+
+use crate::lexgen::nfa::Nfa;
+
+// 输入字符按规则中出现的区间边界划分成等价类，同一类的字符在任何状态下转移都相同，
+// 转移表按等价类而不是按字符存储
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Alphabet {
+  boundaries: Vec<u32>,
+}
+
+impl Alphabet {
+  fn new(nfa: &Nfa) -> Self {
+    let mut boundaries: Vec<u32> = nfa
+      .states
+      .iter()
+      .flat_map(|state| &state.edges)
+      .flat_map(|&((lo, hi), _)| [lo as u32, hi as u32 + 1])
+      .collect();
+    boundaries.sort_unstable();
+    boundaries.dedup();
+    Self { boundaries }
+  }
+
+  pub fn len(&self) -> usize {
+    self.boundaries.len().saturating_sub(1)
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.len() == 0
+  }
+
+  // 不属于任何规则的字符没有等价类
+  pub fn class(&self, ch: char) -> Option<usize> {
+    let i = self.boundaries.partition_point(|&b| b <= ch as u32);
+    (i > 0 && i < self.boundaries.len()).then(|| i - 1)
+  }
+
+  // 区间覆盖的等价类；区间端点都是边界
+  fn classes(&self, (lo, hi): (char, char)) -> Range<usize> {
+    let index = |b: u32| self.boundaries.binary_search(&b).unwrap();
+    index(lo as u32)..index(hi as u32 + 1)
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dfa {
+  pub alphabet: Alphabet,
+  // transitions[状态][等价类]
+  pub transitions: Vec<Vec<Option<usize>>>,
+  // 接受的规则下标
+  pub accept: Vec<Option<usize>>,
+  pub start: usize,
+}
+
+impl Dfa {
+  // 子集构造；一个状态同时接受多条规则时取优先级最高的，优先级相同时取先声明的
+  pub fn new(nfa: &Nfa, priorities: &[u32]) -> Self {
+    let alphabet = Alphabet::new(nfa);
+    let mut ids: BTreeMap<BTreeSet<usize>, usize> = BTreeMap::new();
+    let mut sets = Vec::new();
+    let mut transitions = Vec::new();
+
+    let start = closure(nfa, [nfa.start].into());
+    ids.insert(start.clone(), 0);
+    sets.push(start);
+    let mut current = 0;
+    while current < sets.len() {
+      let mut moves = vec![BTreeSet::new(); alphabet.len()];
+      for &state in &sets[current] {
+        for &(range, to) in &nfa.states[state].edges {
+          for class in alphabet.classes(range) {
+            moves[class].insert(to);
+          }
+        }
+      }
+      let mut row = Vec::with_capacity(alphabet.len());
+      for targets in moves {
+        if targets.is_empty() {
+          row.push(None);
+          continue;
+        }
+        let set = closure(nfa, targets);
+        let id = match ids.get(&set) {
+          Some(&id) => id,
+          None => {
+            ids.insert(set.clone(), sets.len());
+            sets.push(set);
+            sets.len() - 1
+          }
+        };
+        row.push(Some(id));
+      }
+      transitions.push(row);
+      current += 1;
+    }
+
+    let accept = sets
+      .iter()
+      .map(|set| {
+        set
+          .iter()
+          .filter_map(|&state| nfa.states[state].accept)
+          .min_by_key(|&rule| (std::cmp::Reverse(priorities[rule]), rule))
+      })
+      .collect();
+    Self {
+      alphabet,
+      transitions,
+      accept,
+      start: 0,
+    }
+  }
+
+  pub fn state_count(&self) -> usize {
+    self.transitions.len()
+  }
+
+  // Moore算法：先按接受的规则划分，再按各等价类转移到的块反复细分，直到块数不再增加
+  pub fn minimize(&self) -> Dfa {
+    let mut blocks = Vec::new();
+    let mut initial = BTreeMap::new();
+    for accept in &self.accept {
+      let next = initial.len();
+      blocks.push(*initial.entry(*accept).or_insert(next));
+    }
+    let mut count = initial.len();
+    loop {
+      let mut signatures = BTreeMap::new();
+      let refined: Vec<usize> = (0..self.state_count())
+        .map(|state| {
+          let targets: Vec<_> = self.transitions[state].iter().map(|to| to.map(|to| blocks[to])).collect();
+          let next = signatures.len();
+          *signatures.entry((blocks[state], targets)).or_insert(next)
+        })
+        .collect();
+      blocks = refined;
+      if signatures.len() == count {
+        break;
+      }
+      count = signatures.len();
+    }
+
+    let mut transitions = vec![Vec::new(); count];
+    let mut accept = vec![None; count];
+    for state in 0..self.state_count() {
+      let block = blocks[state];
+      transitions[block] = self.transitions[state].iter().map(|to| to.map(|to| blocks[to])).collect();
+      accept[block] = self.accept[state];
+    }
+    Dfa {
+      alphabet: self.alphabet.clone(),
+      transitions,
+      accept,
+      start: blocks[self.start],
+    }
+  }
+}
+
+// ε闭包
+fn closure(nfa: &Nfa, mut set: BTreeSet<usize>) -> BTreeSet<usize> {
+  let mut stack: Vec<usize> = set.iter().copied().collect();
+  while let Some(state) = stack.pop() {
+    for &next in &nfa.states[state].eps {
+      if set.insert(next) {
+        stack.push(next);
+      }
+    }
+  }
+  set
+}
@@ -0,0 +1,217 @@
+mod dfa;
+mod nfa;
+mod regex;
+mod scanner;
+
+use std::fmt::Display;
+use std::fs;
+
+use crate::lexer::{TokenKind, TokenType};
+
+pub use dfa::Dfa;
+pub use nfa::Nfa;
+pub use regex::{parse as parse_regex, Regex};
+pub use scanner::{LexerTable, TableStream};
+
+// 词法分析器生成器：词法规则和文法写在同一个文件中，每行一条
+//   %lex <终结符> <优先级> <正则表达式>
+//   %skip <名字> <优先级> <正则表达式>
+// 正则表达式是行内剩余的部分，可以包含空格。%skip的规则产生空白或注释（名字为Comment时）。
+// 匹配按最长优先，长度相同时取优先级高的，再相同时取先声明的
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TokenSpec {
+  pub name: String,
+  pub priority: u32,
+  pub pattern: String,
+  pub skip: bool,
+  // 所在的行号，用于报错
+  pub line: usize,
+}
+
+impl TokenSpec {
+  // 词法分析器不认识的名字作为新的终结符，只需在文法中用%token声明
+  fn token_type(&self) -> TokenType {
+    if self.skip {
+      let kind = if self.name == "Comment" { TokenKind::Comment } else { TokenKind::WhiteSpace };
+      return TokenType::Terminal(kind);
+    }
+    match TokenType::from(&self.name) {
+      TokenType::NotTerminal => TokenType::Terminal(TokenKind::Other(self.name.clone())),
+      token_type => token_type,
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpecError {
+  pub line: usize,
+  pub message: String,
+}
+
+impl Display for SpecError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "line {}: {}", self.line, self.message)
+  }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LexerSpec {
+  pub tokens: Vec<TokenSpec>,
+}
+
+impl LexerSpec {
+  // 只读取%lex和%skip行，其余的行属于文法
+  pub fn parse(text: &str) -> Result<Self, SpecError> {
+    let mut tokens = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+      let line_no = index + 1;
+      let (skip, rest) = if let Some(rest) = line.strip_prefix("%lex ") {
+        (false, rest)
+      } else if let Some(rest) = line.strip_prefix("%skip ") {
+        (true, rest)
+      } else {
+        continue;
+      };
+      let error = |message: String| SpecError { line: line_no, message };
+      let mut fields = rest.trim_start().splitn(3, ' ');
+      let (Some(name), Some(priority), Some(pattern)) = (fields.next(), fields.next(), fields.next()) else {
+        return Err(error("expected `<name> <priority> <pattern>`".to_string()));
+      };
+      let priority = priority
+        .parse()
+        .map_err(|_| error(format!("invalid priority `{}`", priority)))?;
+      tokens.push(TokenSpec {
+        name: name.to_string(),
+        priority,
+        pattern: pattern.trim().to_string(),
+        skip,
+        line: line_no,
+      });
+    }
+    Ok(Self { tokens })
+  }
+
+  pub fn load(path: &str) -> Result<Self, SpecError> {
+    let text = fs::read_to_string(path).map_err(|e| SpecError {
+      line: 0,
+      message: format!("cannot read `{}`: {}", path, e),
+    })?;
+    Self::parse(&text)
+  }
+
+  // 正则表达式 -> NFA -> DFA -> 最小化的DFA
+  pub fn build(&self) -> Result<LexerTable, SpecError> {
+    let mut rules = Vec::new();
+    let mut token_types = Vec::new();
+    for token in &self.tokens {
+      let error = |message: String| SpecError { line: token.line, message };
+      let regex = parse_regex(&token.pattern).map_err(|e| error(format!("in pattern of `{}`: {}", token.name, e)))?;
+      rules.push(regex);
+      token_types.push(token.token_type());
+    }
+    let priorities: Vec<_> = self.tokens.iter().map(|token| token.priority).collect();
+    let nfa = Nfa::new(&rules);
+    let dfa = Dfa::new(&nfa, &priorities).minimize();
+    Ok(LexerTable::new(dfa, token_types))
+  }
+}
+
+// 单元测试
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::lexer::{Cursor, LexerErrorKind, LexerKind, Literal, Token, TokenStream};
+  use crate::parser::{Grammar, LR1Parser};
+
+  fn values(tokens: &[Token]) -> Vec<&str> {
+    tokens
+      .iter()
+      .filter(|t| !t.is_whitespace() && !t.is_comment())
+      .map(|t| t.get_value().as_str())
+      .collect()
+  }
+
+  #[test]
+  fn test_regex() {
+    assert_eq!(parse_regex("ab").unwrap(), Regex::Concat(vec![Regex::Class(vec![('a', 'a')]), Regex::Class(vec![('b', 'b')])]));
+    assert_eq!(parse_regex("[a-cx-z_]").unwrap(), Regex::Class(vec![('_', '_'), ('a', 'c'), ('x', 'z')]));
+    assert_eq!(parse_regex("a|").unwrap(), Regex::Alt(vec![Regex::Class(vec![('a', 'a')]), Regex::Empty]));
+    assert_eq!(parse_regex("[^\\n]").unwrap(), parse_regex(".").unwrap());
+    assert_eq!(parse_regex("\\+?").unwrap(), Regex::Opt(Box::new(Regex::Class(vec![('+', '+')]))));
+    assert!(parse_regex("(ab").is_err());
+    assert!(parse_regex("*a").is_err());
+    assert!(parse_regex("[z-a]").is_err());
+    assert!(parse_regex("a)").is_err());
+  }
+
+  #[test]
+  fn test_minimize() {
+    // 龙书中的例子：(a|b)*abb的最小DFA有4个状态
+    let nfa = Nfa::new(&[parse_regex("(a|b)*abb").unwrap()]);
+    let dfa = Dfa::new(&nfa, &[0]);
+    assert_eq!(dfa.state_count(), 5);
+    assert_eq!(dfa.minimize().state_count(), 4);
+    // 等价的写法得到同样大小的DFA
+    let nfa = Nfa::new(&[parse_regex("[ab]*a(b)(b)").unwrap()]);
+    assert_eq!(Dfa::new(&nfa, &[0]).minimize().state_count(), 4);
+  }
+
+  #[test]
+  fn test_priority() {
+    let spec = LexerSpec::parse(
+      "%lex Ident 1 [_a-z][_a-z0-9]*\n%lex int 2 int\n%lex IntConst 1 [0-9]+\n%lex + 1 \\+\n%skip WhiteSpace 0 [ \\t\\n]+\n",
+    )
+    .unwrap();
+    let table = spec.build().unwrap();
+    let (tokens, errors) = table.tokenize("int intx 12+x $ 99999999999");
+    assert_eq!(values(&tokens), ["int", "intx", "12", "+", "x", "$", "99999999999"]);
+    let types: Vec<_> = tokens.iter().filter(|t| !t.is_whitespace()).map(|t| t.get_type().to_string()).collect();
+    assert_eq!(types, ["int", "Ident", "IntConst", "+", "Ident", "Err", "Err"]);
+    assert_eq!(tokens[4].get_literal(), Some(Literal::Int(12)));
+    assert_eq!(*tokens[4].get_pos(), Some(crate::lexer::Point::new(1, 10)));
+    let kinds: Vec<_> = errors.iter().map(|e| (e.kind, e.col)).collect();
+    assert_eq!(kinds, [(LexerErrorKind::UnknownCharacter, 15), (LexerErrorKind::IntegerOverflow, 17)]);
+
+    assert_eq!(LexerSpec::parse("%lex int x int").unwrap_err().line, 1);
+  }
+
+  // 词法分析器不认识的名字是新的终结符，不需要修改手写的词法分析器
+  #[test]
+  fn test_new_terminal() {
+    let text = "%token Foo Ident\n%lex Foo 1 foo\n%lex Ident 0 [a-z]+\n%skip WhiteSpace 0 [ ]+\nCompUnit':S\nS:Foo Ident | Ident\n";
+    let lexer = LexerKind::Generated(LexerSpec::parse(text).unwrap().build().unwrap());
+    let (tokens, errors) = lexer.tokenize("foo bar");
+    assert!(errors.is_empty());
+    assert_eq!(tokens[0].get_type(), &TokenType::Terminal(TokenKind::Other("Foo".to_string())));
+    assert_eq!(tokens[0].get_type().to_string(), "Foo");
+
+    let mut grammar = Grammar::new();
+    grammar.grammar_load_text(text);
+    let mut lr1 = LR1Parser::new();
+    lr1.compute_lr1_item_sets(&grammar);
+    lr1.construct_parsing_table(&grammar).unwrap();
+    let lr1 = lr1.parse_stream(tokens.into_iter().skip_trivia());
+    assert!(lr1.get_errors().is_empty());
+    assert!(lr1.get_tree().is_some());
+  }
+
+  // g3.txt中的词法规则与Cursor的结果一致
+  #[test]
+  fn test_grammar_spec() {
+    let table = LexerSpec::load("../g3.txt").unwrap().build().unwrap();
+    let source = "int main() {\n  /* 注释 */ float f = 0x1.8p3 + .5e1; // 行注释\n  if (a <= 017 && b != 0) return a[1];\n}\n";
+    let (tokens, errors) = table.tokenize(source);
+    assert!(errors.is_empty());
+    let (expected, _) = Cursor::new(source).tokenize();
+    let significant = |tokens: Vec<Token>| tokens.into_iter().filter(|t| !t.is_whitespace()).collect::<Vec<_>>();
+    assert_eq!(significant(tokens), significant(expected));
+
+    for path in crate::differential::corpus(std::path::Path::new("../testcases")).unwrap() {
+      let source = fs::read_to_string(path).unwrap();
+      let (tokens, _) = table.tokenize(&source);
+      let (expected, _) = Cursor::new(&source).tokenize();
+      assert_eq!(significant(tokens), significant(expected));
+    }
+  }
+}
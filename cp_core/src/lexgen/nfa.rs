@@ -0,0 +1,95 @@
+use crate::lexgen::regex::Regex;
+
+// Thompson构造：每个正则表达式对应一个只有一个起点和一个终点的片段，
+// 所有规则的片段从同一个起点经ε边进入，终点标记为接受对应的规则
+
+#[derive(Debug, Default)]
+pub struct NfaState {
+  pub eps: Vec<usize>,
+  pub edges: Vec<((char, char), usize)>,
+  // 接受的规则下标
+  pub accept: Option<usize>,
+}
+
+#[derive(Debug)]
+pub struct Nfa {
+  pub states: Vec<NfaState>,
+  pub start: usize,
+}
+
+impl Nfa {
+  pub fn new(rules: &[Regex]) -> Self {
+    let mut nfa = Nfa {
+      states: Vec::new(),
+      start: 0,
+    };
+    nfa.start = nfa.add_state();
+    for (index, regex) in rules.iter().enumerate() {
+      let (start, end) = nfa.fragment(regex);
+      nfa.states[nfa.start].eps.push(start);
+      nfa.states[end].accept = Some(index);
+    }
+    nfa
+  }
+
+  fn add_state(&mut self) -> usize {
+    self.states.push(NfaState::default());
+    self.states.len() - 1
+  }
+
+  fn eps(&mut self, from: usize, to: usize) {
+    self.states[from].eps.push(to);
+  }
+
+  // 返回片段的起点和终点
+  fn fragment(&mut self, regex: &Regex) -> (usize, usize) {
+    let start = self.add_state();
+    let end = match regex {
+      Regex::Empty => {
+        let end = self.add_state();
+        self.eps(start, end);
+        end
+      }
+      Regex::Class(ranges) => {
+        let end = self.add_state();
+        for &range in ranges {
+          self.states[start].edges.push((range, end));
+        }
+        end
+      }
+      Regex::Concat(items) => {
+        let mut end = start;
+        for item in items {
+          let (inner_start, inner_end) = self.fragment(item);
+          self.eps(end, inner_start);
+          end = inner_end;
+        }
+        end
+      }
+      Regex::Alt(items) => {
+        let end = self.add_state();
+        for item in items {
+          let (inner_start, inner_end) = self.fragment(item);
+          self.eps(start, inner_start);
+          self.eps(inner_end, end);
+        }
+        end
+      }
+      Regex::Star(inner) | Regex::Plus(inner) | Regex::Opt(inner) => {
+        let end = self.add_state();
+        let (inner_start, inner_end) = self.fragment(inner);
+        self.eps(start, inner_start);
+        self.eps(inner_end, end);
+        // *和?可以跳过，*和+可以重复
+        if !matches!(regex, Regex::Plus(_)) {
+          self.eps(start, end);
+        }
+        if !matches!(regex, Regex::Opt(_)) {
+          self.eps(inner_end, inner_start);
+        }
+        end
+      }
+    };
+    (start, end)
+  }
+}
@@ -0,0 +1,201 @@
+// 词法规则中的正则表达式
+// 支持字符、转义（\n \t \r \0 \d \w \s 以及转义的元字符）、任意字符 .（不含换行）、
+// 字符类 [a-z] 和 [^...]、分组 ()、选择 |、闭包 * + ?
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Regex {
+  // 空串
+  Empty,
+  // 字符属于其中某个闭区间
+  Class(Vec<(char, char)>),
+  Concat(Vec<Regex>),
+  Alt(Vec<Regex>),
+  Star(Box<Regex>),
+  Plus(Box<Regex>),
+  Opt(Box<Regex>),
+}
+
+pub fn parse(pattern: &str) -> Result<Regex, String> {
+  let mut parser = Parser {
+    chars: pattern.chars().collect(),
+    pos: 0,
+  };
+  let regex = parser.alt()?;
+  match parser.peek() {
+    None => Ok(regex),
+    Some(ch) => Err(format!("unexpected '{}' at {}", ch, parser.pos)),
+  }
+}
+
+struct Parser {
+  chars: Vec<char>,
+  pos: usize,
+}
+
+impl Parser {
+  fn peek(&self) -> Option<char> {
+    self.chars.get(self.pos).copied()
+  }
+
+  fn bump(&mut self) -> Option<char> {
+    let ch = self.peek();
+    self.pos += 1;
+    ch
+  }
+
+  // alt := concat ('|' concat)*
+  fn alt(&mut self) -> Result<Regex, String> {
+    let mut items = vec![self.concat()?];
+    while self.peek() == Some('|') {
+      self.bump();
+      items.push(self.concat()?);
+    }
+    Ok(if items.len() == 1 { items.pop().unwrap() } else { Regex::Alt(items) })
+  }
+
+  // concat := repeat*
+  fn concat(&mut self) -> Result<Regex, String> {
+    let mut items = Vec::new();
+    while let Some(ch) = self.peek() {
+      if ch == '|' || ch == ')' {
+        break;
+      }
+      items.push(self.repeat()?);
+    }
+    Ok(match items.len() {
+      0 => Regex::Empty,
+      1 => items.pop().unwrap(),
+      _ => Regex::Concat(items),
+    })
+  }
+
+  // repeat := atom ('*' | '+' | '?')*
+  fn repeat(&mut self) -> Result<Regex, String> {
+    let mut regex = self.atom()?;
+    loop {
+      regex = match self.peek() {
+        Some('*') => Regex::Star(Box::new(regex)),
+        Some('+') => Regex::Plus(Box::new(regex)),
+        Some('?') => Regex::Opt(Box::new(regex)),
+        _ => return Ok(regex),
+      };
+      self.bump();
+    }
+  }
+
+  fn atom(&mut self) -> Result<Regex, String> {
+    let pos = self.pos;
+    match self.bump() {
+      Some('(') => {
+        let regex = self.alt()?;
+        match self.bump() {
+          Some(')') => Ok(regex),
+          _ => Err(format!("unclosed group at {}", pos)),
+        }
+      }
+      Some('[') => self.class(pos),
+      Some('.') => Ok(Regex::Class(complement(&[('\n', '\n')]))),
+      Some('\\') => self.escape(pos).map(Regex::Class),
+      Some(ch @ ('*' | '+' | '?')) => Err(format!("nothing to repeat before '{}' at {}", ch, pos)),
+      Some(ch) => Ok(Regex::Class(vec![(ch, ch)])),
+      None => Err(format!("unexpected end of pattern at {}", pos)),
+    }
+  }
+
+  // 转义得到一组字符区间
+  fn escape(&mut self, pos: usize) -> Result<Vec<(char, char)>, String> {
+    let ranges = match self.bump() {
+      Some('n') => vec![('\n', '\n')],
+      Some('t') => vec![('\t', '\t')],
+      Some('r') => vec![('\r', '\r')],
+      Some('0') => vec![('\0', '\0')],
+      Some('d') => vec![('0', '9')],
+      Some('w') => vec![('0', '9'), ('A', 'Z'), ('_', '_'), ('a', 'z')],
+      Some('s') => vec![('\t', '\n'), ('\r', '\r'), (' ', ' ')],
+      Some(ch) if !ch.is_alphanumeric() => vec![(ch, ch)],
+      Some(ch) => return Err(format!("unknown escape '\\{}' at {}", ch, pos)),
+      None => return Err(format!("unexpected end of pattern at {}", pos)),
+    };
+    Ok(ranges)
+  }
+
+  // 字符类：[abc] [a-z] [^\n]，类中的 - 出现在首尾时是普通字符
+  fn class(&mut self, pos: usize) -> Result<Regex, String> {
+    let negated = self.peek() == Some('^');
+    if negated {
+      self.bump();
+    }
+    let mut ranges = Vec::new();
+    loop {
+      let item_pos = self.pos;
+      let lo = match self.bump() {
+        Some(']') if item_pos > pos + 1 + negated as usize => break,
+        Some('\\') => {
+          let escaped = self.escape(item_pos)?;
+          if escaped.len() > 1 || escaped[0].0 != escaped[0].1 {
+            ranges.extend(escaped);
+            continue;
+          }
+          escaped[0].0
+        }
+        Some(ch) => ch,
+        None => return Err(format!("unclosed character class at {}", pos)),
+      };
+      if self.peek() == Some('-') && self.chars.get(self.pos + 1).is_some_and(|&ch| ch != ']') {
+        self.bump();
+        let hi = match self.bump() {
+          Some('\\') => {
+            let escaped = self.escape(self.pos - 1)?;
+            escaped[0].0
+          }
+          Some(ch) => ch,
+          None => return Err(format!("unclosed character class at {}", pos)),
+        };
+        if hi < lo {
+          return Err(format!("invalid range '{}-{}' at {}", lo, hi, item_pos));
+        }
+        ranges.push((lo, hi));
+      } else {
+        ranges.push((lo, lo));
+      }
+    }
+    let ranges = normalize(ranges);
+    Ok(Regex::Class(if negated { complement(&ranges) } else { ranges }))
+  }
+}
+
+// 排序并合并重叠或相邻的区间
+fn normalize(mut ranges: Vec<(char, char)>) -> Vec<(char, char)> {
+  ranges.sort();
+  let mut merged: Vec<(char, char)> = Vec::new();
+  for (lo, hi) in ranges {
+    match merged.last_mut() {
+      Some(last) if lo as u32 <= last.1 as u32 + 1 => last.1 = last.1.max(hi),
+      _ => merged.push((lo, hi)),
+    }
+  }
+  merged
+}
+
+// 全体字符中不属于ranges的部分；码点区间跨过代理区时跳过它
+fn complement(ranges: &[(char, char)]) -> Vec<(char, char)> {
+  let ranges = normalize(ranges.to_vec());
+  let mut result = Vec::new();
+  let mut next = 0u32;
+  for &(lo, hi) in &ranges {
+    push_codepoints(&mut result, next, lo as u32);
+    next = hi as u32 + 1;
+  }
+  push_codepoints(&mut result, next, char::MAX as u32 + 1);
+  result
+}
+
+// 加入码点区间[lo, hi)
+fn push_codepoints(result: &mut Vec<(char, char)>, lo: u32, hi: u32) {
+  const SURROGATES: (u32, u32) = (0xD800, 0xE000);
+  for (lo, hi) in [(lo, hi.min(SURROGATES.0)), (lo.max(SURROGATES.1), hi)] {
+    if lo < hi {
+      result.push((char::from_u32(lo).unwrap(), char::from_u32(hi - 1).unwrap()));
+    }
+  }
+}
@@ -0,0 +1,107 @@
+use crate::lexer::{parse_number, LexerError, LexerErrorKind, Token, TokenKind, TokenType};
+use crate::lexgen::dfa::Dfa;
+use crate::source::SourceFile;
+
+// 表驱动的词法分析器：按最长匹配运行最小化的DFA，
+// 没有任何规则能匹配时当前字符作为Err词素，从下一个字符继续
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LexerTable {
+  dfa: Dfa,
+  // 每条规则产生的词素类型
+  rules: Vec<TokenType>,
+}
+
+impl LexerTable {
+  pub(crate) fn new(dfa: Dfa, rules: Vec<TokenType>) -> Self {
+    Self { dfa, rules }
+  }
+
+  pub fn state_count(&self) -> usize {
+    self.dfa.state_count()
+  }
+
+  pub fn class_count(&self) -> usize {
+    self.dfa.alphabet.len()
+  }
+
+  // 从offset开始的最长匹配，返回规则下标和结束偏移
+  fn longest_match(&self, source: &str, offset: usize) -> Option<(usize, usize)> {
+    let mut state = self.dfa.start;
+    let mut matched = None;
+    for (i, ch) in source[offset..].char_indices() {
+      let Some(next) = self.dfa.alphabet.class(ch).and_then(|class| self.dfa.transitions[state][class]) else {
+        break;
+      };
+      state = next;
+      if let Some(rule) = self.dfa.accept[state] {
+        matched = Some((rule, offset + i + ch.len_utf8()));
+      }
+    }
+    matched
+  }
+
+  // 按需产生词素，包括空白、注释和Err词素，诊断信息收集到errors中
+  pub fn stream<'a, 'e>(&'a self, source: &'a str, errors: &'e mut Vec<LexerError>) -> TableStream<'a, 'e> {
+    TableStream {
+      table: self,
+      source,
+      file: SourceFile::new("", source),
+      offset: 0,
+      errors,
+    }
+  }
+
+  // 读取全部词素，包括空白、注释和Err词素；数值常量附带解析好的值
+  pub fn tokenize(&self, source: &str) -> (Vec<Token>, Vec<LexerError>) {
+    let mut errors = Vec::new();
+    let tokens = self.stream(source, &mut errors).collect();
+    (tokens, errors)
+  }
+}
+
+pub struct TableStream<'a, 'e> {
+  table: &'a LexerTable,
+  source: &'a str,
+  // 用于把字节偏移换算成行列号
+  file: SourceFile,
+  offset: usize,
+  errors: &'e mut Vec<LexerError>,
+}
+
+impl Iterator for TableStream<'_, '_> {
+  type Item = Token;
+
+  fn next(&mut self) -> Option<Token> {
+    let (source, offset) = (self.source, self.offset);
+    if offset >= source.len() {
+      return None;
+    }
+    let (token_type, end, mut error) = match self.table.longest_match(source, offset) {
+      Some((rule, end)) => (self.table.rules[rule].clone(), end, None),
+      None => {
+        let len = source[offset..].chars().next().unwrap().len_utf8();
+        (TokenType::Terminal(TokenKind::Err), offset + len, Some(LexerErrorKind::UnknownCharacter))
+      }
+    };
+    let span = self.file.span(offset, end);
+    let value = source[offset..end].to_string();
+    let mut token = Token::new(token_type, value, None).with_span(span);
+    if let TokenType::Terminal(TokenKind::Number(_)) = token.get_type() {
+      match parse_number(token.get_value()) {
+        Ok(literal) => token = token.with_literal(literal),
+        Err(kind) => {
+          token = Token::new(TokenType::Terminal(TokenKind::Err), token.get_value().clone(), None).with_span(span);
+          error = Some(kind);
+        }
+      }
+    }
+    if let Some(kind) = error {
+      let start = span.get_start_point();
+      let mut err = LexerError::new(kind, *start.get_row(), *start.get_col());
+      err.span = span;
+      self.errors.push(err);
+    }
+    self.offset = end;
+    Some(token)
+  }
+}
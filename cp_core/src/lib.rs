@@ -1,7 +1,21 @@
 pub mod lexer;
 pub mod relexer;
 pub mod token;
+pub mod source;
 pub mod parser;
+pub mod ast;
+pub mod semantic;
+pub mod ir;
+pub mod interpreter;
+pub mod backend;
+pub mod conformance;
+pub mod diagnostics;
+pub mod differential;
+pub mod lexgen;
+pub mod preprocess;
 mod error;
 
 pub use lexer::Cursor;
+
+#[cfg(test)]
+mod test_util;
@@ -0,0 +1,164 @@
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
+use crate::parser::grammar::Grammar;
+use crate::parser::lr1_parser::{Action, LR1Item};
+use crate::parser::precedence::Resolution;
+
+// 分析表的冲突：同一状态、同一向前看符号上有多个不同的动作。
+// 一个移进和一个归约竞争时先看优先级声明，能按优先级决定的不算冲突；
+// 其余冲突按yacc的惯例消解——移进优先于归约，归约/归约冲突取文法文件中靠前的产生式，
+// 并记录下来；ConflictPolicy决定记录的冲突是警告还是错误
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ConflictPolicy {
+  // 按惯例消解，冲突只是警告
+  #[default]
+  PreferShift,
+  // 有冲突时不能使用分析表
+  Error,
+}
+
+impl ConflictPolicy {
+  pub fn from_name(name: &str) -> Option<Self> {
+    match name {
+      "prefer-shift" => Some(ConflictPolicy::PreferShift),
+      "error" => Some(ConflictPolicy::Error),
+      _ => None,
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ConflictKind {
+  ShiftReduce,
+  ReduceReduce,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Conflict {
+  pub kind: ConflictKind,
+  pub state: usize,
+  pub lookahead: String,
+  // 要求移进的项目和要求归约的项目，核心相同的项目只保留一个
+  pub shift_items: Vec<LR1Item>,
+  pub reduce_items: Vec<LR1Item>,
+  // 消解后分析表中的动作
+  pub chosen: Action,
+  // 第一个参与归约的产生式在文法文件中的行号
+  pub line: usize,
+}
+
+impl Display for ConflictKind {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      ConflictKind::ShiftReduce => write!(f, "shift/reduce"),
+      ConflictKind::ReduceReduce => write!(f, "reduce/reduce"),
+    }
+  }
+}
+
+impl Conflict {
+  pub fn message(&self) -> String {
+    format!("{} conflict in state {} on `{}`", self.kind, self.state, self.lookahead)
+  }
+
+  // 每个参与冲突的项目一行
+  pub fn item_lines(&self) -> Vec<String> {
+    let shifts = self.shift_items.iter().map(|item| format!("shift  {}", item.core()));
+    let reduces = self.reduce_items.iter().map(|item| format!("reduce {}", item.core()));
+    shifts.chain(reduces).collect()
+  }
+
+  pub fn resolution(&self) -> String {
+    match &self.chosen {
+      Action::Shift(_) => "resolved as shift".to_string(),
+      Action::Reduce(head, body) => format!("resolved as reduce by {} → {}", head, body.join(" ")),
+      Action::Accept => "resolved as accept".to_string(),
+    }
+  }
+}
+
+impl Display for Conflict {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    writeln!(f, "{}", self.message())?;
+    for line in self.item_lines() {
+      writeln!(f, "  {}", line)?;
+    }
+    write!(f, "  {}", self.resolution())
+  }
+}
+
+// 产生式在文法文件中的次序，用于归约/归约冲突
+fn production_order(grammar: &Grammar, head: &str, body: &[String]) -> usize {
+  grammar
+    .rules
+    .iter()
+    .flat_map(|rule| rule.body.iter().map(move |item| (&rule.head, item)))
+    .position(|(rule_head, item)| rule_head == head && item == body)
+    .unwrap_or(usize::MAX)
+}
+
+// 从同一状态、同一向前看符号上的全部候选动作中选出一个，None表示不填动作（%nonassoc）；
+// 有未能按优先级决定的多个动作时同时返回冲突
+pub(super) fn resolve(
+  grammar: &Grammar,
+  state: usize,
+  lookahead: &str,
+  candidates: &[(Action, LR1Item)],
+) -> (Option<Action>, Option<Conflict>) {
+  if candidates.iter().all(|(action, _)| *action == candidates[0].0) {
+    return (Some(candidates[0].0.clone()), None);
+  }
+
+  let mut shift_items = Vec::new();
+  let mut reduce_items = Vec::new();
+  let mut shift = None;
+  for (action, item) in candidates {
+    let seen = |items: &Vec<LR1Item>| items.iter().any(|other| other.core() == item.core());
+    if seen(&shift_items) || seen(&reduce_items) {
+      continue;
+    }
+    match action {
+      Action::Reduce(..) => reduce_items.push(item.clone()),
+      _ => {
+        shift.get_or_insert_with(|| action.clone());
+        shift_items.push(item.clone());
+      }
+    }
+  }
+  if let (Some(shift), [reduce]) = (&shift, reduce_items.as_slice()) {
+    match grammar.resolve_by_precedence(lookahead, &reduce.head, &reduce.body) {
+      Some(Resolution::Shift) => return (Some(shift.clone()), None),
+      Some(Resolution::Reduce) => return (Some(Action::Reduce(reduce.head.clone(), reduce.body.clone())), None),
+      Some(Resolution::Error) => return (None, None),
+      None => {}
+    }
+  }
+  let chosen = match shift {
+    Some(action) => action,
+    None => {
+      let first = reduce_items
+        .iter()
+        .min_by_key(|item| production_order(grammar, &item.head, &item.body))
+        .unwrap();
+      Action::Reduce(first.head.clone(), first.body.clone())
+    }
+  };
+  let kind = if shift_items.is_empty() { ConflictKind::ReduceReduce } else { ConflictKind::ShiftReduce };
+  let line = reduce_items
+    .first()
+    .and_then(|item| grammar.rules.iter().find(|rule| rule.head == item.head))
+    .map_or(1, |rule| rule.line);
+  let conflict = Conflict {
+    kind,
+    state,
+    lookahead: lookahead.to_string(),
+    shift_items,
+    reduce_items,
+    chosen: chosen.clone(),
+    line,
+  };
+  (Some(chosen), Some(conflict))
+}
@@ -0,0 +1,308 @@
+use crate::parser::grammar::{Item, PBody, PHead};
+use crate::parser::EMPTY_SYMBOL;
+
+// 产生式右部的EBNF：
+//   Alt := Seq ('|' Seq)*      Seq := Postfix*      Postfix := Atom ('?' | '*' | '+')*
+//   Atom := 名字 | '引号中的终结符' | '(' Alt ')'
+// 用 %token 声明过的符号总是终结符，因此 ( ) * + 等写成终结符时需要声明或加引号；
+// 没有声明的 ( ) | ? * + 是EBNF运算符。
+// 顶层的候选式可以以 `%prec 终结符` 结尾，指定这个候选式的优先级
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Expr {
+  Symbol(String),
+  Seq(Vec<Expr>),
+  Alt(Vec<Expr>),
+  Opt(Box<Expr>),
+  Star(Box<Expr>),
+  Plus(Box<Expr>),
+  Prec(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Lexeme {
+  Name(String),
+  // 带引号的终结符
+  Quoted(String),
+  Op(char),
+  // %prec 和其后的终结符
+  Prec(String),
+}
+
+const OPERATORS: &str = "()|?*+";
+
+// 名字由字母、数字、下划线和非ASCII字符（如ε）组成，第一个字符之后还可以有 '
+fn is_name_char(ch: char) -> bool {
+  ch == '_' || ch.is_alphanumeric() || !ch.is_ascii()
+}
+
+fn tokenize(text: &str, is_token: &dyn Fn(&str) -> bool) -> Result<Vec<Lexeme>, String> {
+  let chars: Vec<char> = text.chars().collect();
+  let mut lexemes = Vec::new();
+  let mut i = 0;
+  while i < chars.len() {
+    let ch = chars[i];
+    if ch.is_whitespace() {
+      i += 1;
+    } else if chars[i..].starts_with(&['%', 'p', 'r', 'e', 'c']) && chars.get(i + 5).is_none_or(|ch| ch.is_whitespace()) {
+      i += 5;
+      while i < chars.len() && chars[i].is_whitespace() {
+        i += 1;
+      }
+      let start = i;
+      while i < chars.len() && !chars[i].is_whitespace() {
+        i += 1;
+      }
+      let token: String = chars[start..i].iter().collect();
+      let token = token.strip_prefix('\'').and_then(|t| t.strip_suffix('\'')).unwrap_or(&token);
+      if token.is_empty() {
+        return Err("`%prec` expects a token".to_string());
+      }
+      lexemes.push(Lexeme::Prec(token.to_string()));
+    } else if ch == '\'' {
+      let mut value = String::new();
+      i += 1;
+      loop {
+        match chars.get(i) {
+          None => return Err("unterminated quoted terminal".to_string()),
+          Some('\'') => break,
+          Some('\\') if i + 1 < chars.len() => {
+            value.push(chars[i + 1]);
+            i += 2;
+          }
+          Some(&ch) => {
+            value.push(ch);
+            i += 1;
+          }
+        }
+      }
+      i += 1;
+      if value.is_empty() {
+        return Err("empty quoted terminal".to_string());
+      }
+      lexemes.push(Lexeme::Quoted(value));
+    } else if is_name_char(ch) {
+      let start = i;
+      while i < chars.len() && (is_name_char(chars[i]) || chars[i] == '\'') {
+        i += 1;
+      }
+      lexemes.push(Lexeme::Name(chars[start..i].iter().collect()));
+    } else {
+      let start = i;
+      while i < chars.len() && !chars[i].is_whitespace() && !is_name_char(chars[i]) && chars[i] != '\'' {
+        i += 1;
+      }
+      let run: String = chars[start..i].iter().collect();
+      if is_token(&run) {
+        lexemes.push(Lexeme::Name(run));
+        continue;
+      }
+      for ch in run.chars() {
+        if !OPERATORS.contains(ch) {
+          return Err(format!("`{}` is not a declared token; quote it or add it to %token", run));
+        }
+        lexemes.push(Lexeme::Op(ch));
+      }
+    }
+  }
+  Ok(lexemes)
+}
+
+struct Parser {
+  lexemes: Vec<Lexeme>,
+  pos: usize,
+  // 括号的嵌套层数
+  depth: usize,
+}
+
+impl Parser {
+  fn peek(&self) -> Option<&Lexeme> {
+    self.lexemes.get(self.pos)
+  }
+
+  fn alt(&mut self) -> Result<Expr, String> {
+    let mut alternatives = vec![self.seq()?];
+    while self.peek() == Some(&Lexeme::Op('|')) {
+      self.pos += 1;
+      alternatives.push(self.seq()?);
+    }
+    Ok(Expr::Alt(alternatives))
+  }
+
+  fn seq(&mut self) -> Result<Expr, String> {
+    let mut items = Vec::new();
+    while let Some(lexeme) = self.peek() {
+      if matches!(lexeme, Lexeme::Op('|') | Lexeme::Op(')')) {
+        break;
+      }
+      if let Lexeme::Prec(token) = lexeme {
+        let token = token.clone();
+        self.pos += 1;
+        if self.depth > 0 || !matches!(self.peek(), None | Some(Lexeme::Op('|'))) {
+          return Err("`%prec` must end a top-level alternative".to_string());
+        }
+        items.push(Expr::Prec(token));
+        break;
+      }
+      items.push(self.postfix()?);
+    }
+    Ok(Expr::Seq(items))
+  }
+
+  fn postfix(&mut self) -> Result<Expr, String> {
+    let mut expr = self.atom()?;
+    while let Some(Lexeme::Op(op @ ('?' | '*' | '+'))) = self.peek() {
+      expr = match op {
+        '?' => Expr::Opt(Box::new(expr)),
+        '*' => Expr::Star(Box::new(expr)),
+        _ => Expr::Plus(Box::new(expr)),
+      };
+      self.pos += 1;
+    }
+    Ok(expr)
+  }
+
+  fn atom(&mut self) -> Result<Expr, String> {
+    let lexeme = self.peek().cloned();
+    self.pos += 1;
+    match lexeme {
+      Some(Lexeme::Name(name)) | Some(Lexeme::Quoted(name)) => Ok(Expr::Symbol(name)),
+      Some(Lexeme::Op('(')) => {
+        self.depth += 1;
+        let expr = self.alt()?;
+        self.depth -= 1;
+        match self.peek() {
+          Some(Lexeme::Op(')')) => {
+            self.pos += 1;
+            Ok(expr)
+          }
+          _ => Err("missing `)`".to_string()),
+        }
+      }
+      Some(Lexeme::Op(op)) => Err(format!("unexpected `{}`", op)),
+      Some(Lexeme::Prec(_)) => Err("`%prec` must end a top-level alternative".to_string()),
+      None => Err("unexpected end of production".to_string()),
+    }
+  }
+}
+
+// 解析右部，同时返回其中带引号的终结符
+pub(crate) fn parse(text: &str, is_token: &dyn Fn(&str) -> bool) -> Result<(Expr, Vec<String>), String> {
+  let lexemes = tokenize(text, is_token)?;
+  let quoted = lexemes
+    .iter()
+    .filter_map(|lexeme| match lexeme {
+      Lexeme::Quoted(value) => Some(value.clone()),
+      _ => None,
+    })
+    .collect();
+  let mut parser = Parser { lexemes, pos: 0, depth: 0 };
+  let expr = parser.alt()?;
+  match parser.peek() {
+    None => Ok((expr, quoted)),
+    Some(_) => Err("unmatched `)`".to_string()),
+  }
+}
+
+// 把EBNF改写为BNF，新的非终结符以左部加 .opt/.rep/.grp 和序号命名，不会与文法中的名字冲突。
+// 重复改写为右递归：X* => R: X R | ε
+pub(crate) struct Desugar {
+  head: PHead,
+  // 整个文法共用的序号，同一左部出现多次时也不会重名
+  pub(crate) count: usize,
+  pub(crate) rules: Vec<(PHead, PBody)>,
+  // 用 %prec 指定了优先级的顶层候选式
+  pub(crate) precs: Vec<(Item, String)>,
+}
+
+impl Desugar {
+  pub(crate) fn new(head: &str, count: usize) -> Self {
+    Self {
+      head: head.to_string(),
+      count,
+      rules: Vec::new(),
+      precs: Vec::new(),
+    }
+  }
+
+  // 文法中的名字不含 .，只有改写产生的非终结符含有
+  pub(crate) fn is_generated(head: &str) -> bool {
+    head.contains('.')
+  }
+
+  fn fresh(&mut self, kind: &str) -> PHead {
+    self.count += 1;
+    format!("{}.{}{}", self.head, kind, self.count)
+  }
+
+  // 顶层的候选式，去掉其中的 %prec 并记录下来
+  pub(crate) fn productions(&mut self, expr: &Expr) -> PBody {
+    let alternatives = match expr {
+      Expr::Alt(alternatives) => alternatives.as_slice(),
+      expr => std::slice::from_ref(expr),
+    };
+    let mut body = Vec::new();
+    for alt in alternatives {
+      match alt {
+        Expr::Seq(items) if matches!(items.last(), Some(Expr::Prec(_))) => {
+          let Some(Expr::Prec(token)) = items.last() else { unreachable!() };
+          let item = self.symbols(&Expr::Seq(items[..items.len() - 1].to_vec()));
+          self.precs.push((item.clone(), token.clone()));
+          body.push(item);
+        }
+        alt => body.push(self.symbols(alt)),
+      }
+    }
+    body
+  }
+
+  // 顶层或括号中的候选式
+  fn alternatives(&mut self, expr: &Expr) -> PBody {
+    match expr {
+      Expr::Alt(alternatives) => alternatives.iter().map(|alt| self.symbols(alt)).collect(),
+      expr => vec![self.symbols(expr)],
+    }
+  }
+
+  fn symbols(&mut self, expr: &Expr) -> Item {
+    let empty = EMPTY_SYMBOL.get_value().to_string();
+    match expr {
+      Expr::Symbol(name) => vec![name.clone()],
+      // 解析时已经保证 %prec 只出现在顶层候选式的末尾
+      Expr::Prec(_) => Vec::new(),
+      // 只有一个候选式的括号直接展开在序列中
+      Expr::Seq(items) => items.iter().flat_map(|item| self.symbols(item)).collect(),
+      Expr::Alt(alternatives) if alternatives.len() == 1 => self.symbols(&alternatives[0]),
+      Expr::Alt(_) => {
+        let head = self.fresh("grp");
+        let body = self.alternatives(expr);
+        self.rules.push((head.clone(), body));
+        vec![head]
+      }
+      Expr::Opt(inner) => {
+        let head = self.fresh("opt");
+        let mut body = self.alternatives(inner);
+        body.push(vec![empty]);
+        self.rules.push((head.clone(), body));
+        vec![head]
+      }
+      Expr::Star(inner) => vec![self.repeat(inner)],
+      Expr::Plus(inner) => {
+        let mut symbols = self.symbols(inner);
+        symbols.push(self.repeat(inner));
+        symbols
+      }
+    }
+  }
+
+  fn repeat(&mut self, inner: &Expr) -> PHead {
+    let head = self.fresh("rep");
+    let mut body = self.alternatives(inner);
+    for item in &mut body {
+      item.push(head.clone());
+    }
+    body.push(vec![EMPTY_SYMBOL.get_value().to_string()]);
+    self.rules.push((head.clone(), body));
+    head
+  }
+}
@@ -1,19 +1,41 @@
 use std::collections::{BTreeMap, BTreeSet};
 use std::fs::File;
 use std::io::Read;
-use crate::parser::{EMPTY_SYMBOL, START_SYMBOL};
+use crate::parser::ebnf::{self, Desugar};
+use crate::parser::precedence::{Assoc, Precedence};
+use crate::parser::{CACHE_VERSION, EMPTY_SYMBOL, START_SYMBOL};
 use crate::token::{TokenKind, TokenType, Token};
 
 pub type Item = Vec<String>;
 pub type PHead = String;
 pub type PBody = Vec<Item>;
 
+// 文法文件中的一行产生式，保留行号用于检查
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Rule {
+  pub(crate) head: PHead,
+  pub(crate) body: PBody,
+  pub(crate) line: usize,
+}
+
 #[derive(Debug)]
 pub struct Grammar {
   file_buff: String,
   pub(crate) token_list: Vec<String>,
+  // 终结符声明所在的行号
+  pub(crate) token_lines: BTreeMap<String, usize>,
   pub(crate) pro_list: BTreeMap<PHead, PBody>,
+  // 按文件顺序的全部产生式，同一左部出现多次时pro_list只保留最后一次
+  pub(crate) rules: Vec<Rule>,
+  // 无法解析的行及原因
+  pub(crate) syntax_errors: Vec<(usize, String)>,
+  // 可空的非终结符，以及各非终结符的FIRST和FOLLOW集合，见sets.rs
+  pub(crate) nullable: BTreeSet<String>,
   pub(crate) first_sets: BTreeMap<String, BTreeSet<String>>,
+  pub(crate) follow_sets: BTreeMap<String, BTreeSet<String>>,
+  // 终结符的优先级和用 %prec 指定了优先级的产生式，见precedence.rs
+  pub(crate) precedence: BTreeMap<String, Precedence>,
+  pub(crate) prec_tokens: BTreeMap<(PHead, Item), String>,
   pub(crate) start_symbol: String,
 }
 
@@ -21,8 +43,15 @@ impl Grammar {
   pub fn new() -> Self {
     Self {
       token_list: Vec::<String>::new(),
+      token_lines: BTreeMap::new(),
       pro_list: BTreeMap::<PHead, PBody>::new(),
+      rules: Vec::new(),
+      syntax_errors: Vec::new(),
+      nullable: BTreeSet::new(),
       first_sets: BTreeMap::<String, BTreeSet<String>>::new(),
+      follow_sets: BTreeMap::new(),
+      precedence: BTreeMap::new(),
+      prec_tokens: BTreeMap::new(),
       file_buff: String::new(),
       start_symbol: START_SYMBOL.get_value().to_string(),
     }
@@ -36,172 +65,154 @@ impl Grammar {
 
   pub fn grammar_load(&mut self, grammar_path: &str) {
     self.file_load(grammar_path);
-    let lines = self.file_buff.lines();
-    for line in lines {
+    self.grammar_parse();
+  }
+
+  // 从字符串读取文法，格式与文法文件相同
+  pub fn grammar_load_text(&mut self, text: &str) {
+    self.file_buff = text.to_string();
+    self.grammar_parse();
+  }
+
+  // 一个产生式从行首的 `左部:` 开始，缩进或以 | 开头的行是上一个产生式的续行；# 之后是注释。
+  // 右部可以使用EBNF，在这里改写为BNF
+  fn grammar_parse(&mut self) {
+    let buff = std::mem::take(&mut self.file_buff);
+    // 行号、左部和右部的文本；先读完全部 %token，右部中的符号才能区分
+    let mut productions: Vec<(usize, String, String)> = Vec::new();
+    for (index, line) in buff.lines().enumerate() {
+      let line_no = index + 1;
       if line.starts_with('{') { continue; }
       if line.starts_with('}') { continue; }
 
-      if line.starts_with("%token") {
-        let tokens = line.split_whitespace().skip(1);
+      // 词法规则由lexgen读取
+      if line.starts_with("%lex ") || line.starts_with("%skip ") { continue; }
+
+      let text = strip_comment(line);
+      if text.trim().is_empty() { continue; }
+
+      if text.starts_with("%token") {
+        let tokens = text.split_whitespace().skip(1);
         for token in tokens {
-          self.token_list.push(token.to_string());
+          self.declare_token(token, line_no);
         }
         continue;
       }
-      {
-        let mut tmp = line.split(':'); // 只有可能是两部分
-        // let p_head = PHead::NotTerminal(tmp.next().unwrap().to_string());
-        // let p_head = Token::new_not_terminal(tmp.next().unwrap().to_string(), None);
-        // let p_head = TokenType::from(tmp.next().unwrap());
-        let p_head = tmp.next().unwrap().to_string();
-        let mut p_body = PBody::new();
-        let items = tmp.next().unwrap().split(" | "); // 拆分右部
-        for item in items {
-          let elements = item.split_whitespace();
-          let mut item = Item::new();
-          for element in elements {
-            item.push(
-              if self.token_list.contains(&element.to_string()) {
-                // Element::Terminal(element.to_string())
-                // Token::new_terminal(element.to_string(), None)
-                // TokenType::from(element)
-                element.to_string()
-              } else {
-                // Element::NotTerminal(element.to_string())
-                // Token::new_not_terminal(element.to_string(), None)
-                // TokenType::from(element)
-                element.to_string()
-              }
-            );
+      // 每行一个优先级，越靠后越高；其中的符号同时声明为终结符
+      let mut words = text.split_whitespace();
+      if let Some(assoc) = words.next().and_then(Assoc::from_directive) {
+        let level = self.precedence.values().map(|p| p.level).max().unwrap_or(0) + 1;
+        for token in words {
+          self.declare_token(token, line_no);
+          if self.precedence.insert(token.to_string(), Precedence { level, assoc }).is_some() {
+            self.syntax_errors.push((line_no, format!("precedence of `{}` is declared again", token)));
           }
-          p_body.push(item);
         }
-        self.pro_list.insert(p_head, p_body);
+        continue;
+      }
+      if text.starts_with(char::is_whitespace) || text.starts_with('|') {
+        match productions.last_mut() {
+          Some((_, _, body)) => {
+            body.push('\n');
+            body.push_str(text);
+          }
+          None => self.syntax_errors.push((line_no, "continuation line without a production".to_string())),
+        }
+        continue;
+      }
+      match text.split_once(':') {
+        Some((head, body)) if !head.trim().is_empty() => {
+          productions.push((line_no, head.trim().to_string(), body.to_string()));
+        }
+        _ => self
+          .syntax_errors
+          .push((line_no, format!("expected `%token ...` or `Head:body`, found `{}`", text.trim()))),
       }
     }
 
-    self.calculate_first_sets();
-  }
-
-  fn first(&mut self, symbol: &str) -> BTreeSet<String> {
-    if let Some(first_set) = self.first_sets.get(symbol) {
-      return first_set.clone();
-    }
-
-    let mut result = BTreeSet::new();
-    if self.token_list.contains(&symbol.to_string()) {
-      result.insert(symbol.to_string());
-    } else {
-      if let Some(productions) = self.pro_list.get(symbol) {
-        let productions: Vec<Vec<_>> = productions.to_vec();
-        for production in productions {
-          let first_symbol = &production[0];
-          if self.token_list.contains(&first_symbol.to_string()) {
-            result.insert(first_symbol.clone());
-          } else {
-            let mut first_set = self.first(first_symbol);
-            let mut i = 1;
-            let empty_symbol = EMPTY_SYMBOL.get_value().to_string();
-            while i < production.len() && first_set.contains(&empty_symbol) {
-              first_set.remove(&empty_symbol);
-              result.extend(first_set);
-              let next_symbol = &production[i];
-              first_set = self.first(next_symbol);
-              i += 1;
-            }
-            result.extend(first_set);
-          }
+    // 只有 %token 声明的符号按终结符读取，带引号的终结符不影响其他地方不带引号的写法
+    let declared = self.token_list.clone();
+    let is_token = |symbol: &str| declared.iter().any(|token| token == symbol);
+    let mut count = 0;
+    for (line_no, head, body) in productions {
+      let (expr, quoted) = match ebnf::parse(&body, &is_token) {
+        Ok(result) => result,
+        Err(message) => {
+          self.syntax_errors.push((line_no, format!("in production of `{}`: {}", head, message)));
+          continue;
+        }
+      };
+      // 带引号的终结符不需要另外声明
+      for token in quoted {
+        self.declare_token(&token, line_no);
+      }
+      let mut desugar = Desugar::new(&head, count);
+      let p_body = desugar.productions(&expr);
+      count = desugar.count;
+      for (item, token) in desugar.precs.drain(..) {
+        if !self.precedence.contains_key(&token) {
+          self.syntax_errors.push((
+            line_no,
+            format!("`%prec {}` names a token without precedence; declare it with %left, %right or %nonassoc", token),
+          ));
         }
+        self.prec_tokens.insert((head.clone(), item), token);
+      }
+      desugar.rules.push((head, p_body));
+      for (p_head, p_body) in desugar.rules {
+        self.rules.push(Rule {
+          head: p_head.clone(),
+          body: p_body.clone(),
+          line: line_no,
+        });
+        self.pro_list.insert(p_head, p_body);
       }
     }
-    // match symbol {
-    //   symbol if self.token_list.contains(&symbol.to_string()) => {
-    //     result.insert(symbol.to_string());
-    //   }
-    //   symbol if !self.token_list.contains(&symbol.to_string()) => {
-    //     if let Some(productions) = self.pro_list.get(symbol) {
-    //       let productions: Vec<Vec<_>> = productions.to_vec();
-    //       for production in productions {
-    //         let first_symbol = &production[0];
-    //         match first_symbol {
-    //           TokenType::Terminal(_) => {
-    //             result.insert(first_symbol.clone());
-    //           }
-    //           TokenType::NotTerminal => {
-    //             let mut first_set = self.first(first_symbol);
-    //             let mut i = 1;
-    //             let empty_symbol = EMPTY_SYMBOL.clone();
-    //             while i < production.len() && first_set.contains(&empty_symbol) {
-    //               first_set.remove(&empty_symbol);
-    //               result.extend(first_set);
-    //               let next_symbol = &production[i];
-    //               first_set = self.first(next_symbol);
-    //               i += 1;
-    //             }
-    //             result.extend(first_set);
-    //           }
-    //         }
-    //       }
-    //     }
-    //   }
-    // }
-
-    self.first_sets.insert(symbol.to_string(), result.clone());
-    result
-  }
+    self.file_buff = buff;
 
-  fn calculate_first_sets(&mut self) {
-    let non_terminals: Vec<_> = self.pro_list.keys().cloned().collect();
+    self.calculate_sets();
+  }
 
-    for non_terminal in non_terminals {
-      self.first(&non_terminal);
+  fn declare_token(&mut self, token: &str, line_no: usize) {
+    if !self.token_list.iter().any(|t| t == token) {
+      self.token_list.push(token.to_string());
+      self.token_lines.insert(token.to_string(), line_no);
     }
   }
 
-  pub(crate) fn first_symbols(&self, symbols: &[String], fallback: &String) -> BTreeSet<String> {
-    let mut result = BTreeSet::new();
-    let mut epsilon = true;
-
-    for symbol in symbols {
-      epsilon = false;
-
-      if self.token_list.contains(symbol) {
-        result.insert(symbol.clone());
-        break;
-      }else {
-        let first_set = self.first_sets.get(symbol).unwrap();
-        let empty_symbol = EMPTY_SYMBOL.get_value().to_string();
-        if first_set.contains(&empty_symbol) {
-          epsilon = true;
-          result.extend(first_set.clone().into_iter().filter(|x| *x != empty_symbol));
-        } else {
-          result.extend(first_set.clone().into_iter());
-          break;
-        }
-      }
-      // match symbol {
-      //   TokenType::Terminal(_) => {
-      //     result.insert(symbol.clone());
-      //     break;
-      //   }
-      //   TokenType::NotTerminal => {
-      //     let first_set = self.first_sets.get(symbol).unwrap();
-      //     let empty_symbol = EMPTY_SYMBOL.clone();
-      //     if first_set.contains(&empty_symbol) {
-      //       epsilon = true;
-      //       result.extend(first_set.clone().into_iter().filter(|x| *x != empty_symbol));
-      //     } else {
-      //       result.extend(first_set.clone().into_iter());
-      //       break;
-      //     }
-      //   }
-      // }
-    }
+  // 分析表缓存的键：缓存格式的版本号和文法文本的FNV-1a散列，不随Rust版本变化
+  pub(crate) fn fingerprint(&self) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+    CACHE_VERSION
+      .to_le_bytes()
+      .iter()
+      .chain(self.file_buff.as_bytes())
+      .fold(OFFSET_BASIS, |hash, &byte| (hash ^ byte as u64).wrapping_mul(PRIME))
+  }
 
-    if epsilon {
+  // 项目 [A → α . B β, a] 求闭包时的向前看符号：FIRST(β a)
+  pub(crate) fn first_symbols(&self, symbols: &[String], fallback: &String) -> BTreeSet<String> {
+    let mut result = self.first_of(symbols);
+    if result.remove(EMPTY_SYMBOL.get_value()) {
       result.insert(fallback.clone());
     }
-
     result
   }
-}
\ No newline at end of file
+}
+
+// 去掉 # 开始的注释，引号中的 # 除外
+fn strip_comment(line: &str) -> &str {
+  let mut quoted = false;
+  let mut escaped = false;
+  for (i, ch) in line.char_indices() {
+    match ch {
+      _ if escaped => escaped = false,
+      '\\' if quoted => escaped = true,
+      '\'' => quoted = !quoted,
+      '#' if !quoted => return &line[..i],
+      _ => {}
+    }
+  }
+  line
+}
@@ -1,5 +1,6 @@
-use crate::token::{Token, Point, TokenType};
-use crate::parser::{Grammar, ACTION_TABLE, DATA_PATH, GOTO_TABLE, LR1_SETS, END_SYMBOL, EMPTY_SYMBOL, ERROR_SYMBOL};
+use crate::token::{Token, Point, Span, TokenType};
+use crate::parser::{cache_dir, Grammar, ACTION_TABLE, CONFLICTS, GOTO_TABLE, LR1_SETS, END_SYMBOL, EMPTY_SYMBOL, ERROR_SYMBOL};
+use crate::parser::conflict::{self, Conflict, ConflictPolicy};
 use serde::{Deserialize, Serialize};
 use std::collections::{BTreeMap, BTreeSet};
 use std::fmt::Display;
@@ -13,26 +14,49 @@ type ErrorList = Vec<ParserError>;
 
 #[derive(Debug, Default)]
 pub struct LR1Parser {
-  tokens: Vec<Token>,
+  // 上一个和当前的输入符号，输入耗尽后current为None
+  last: Option<Token>,
+  current: Option<Token>,
   error_list: ErrorList,
   status: Status,
   status_stack: Vec<(Token, Status)>,
-  pos: usize,
   pub lr1_sets: LR1Sets,
   pub action_table: ActionTable,
   pub goto_table: GotoTable,
+  // 构造分析表时发现并已消解的冲突
+  conflicts: Vec<Conflict>,
+  conflict_policy: ConflictPolicy,
 }
 
 impl LR1Parser {
   pub fn new() -> Self {
     Self::default()
   }
+  pub fn with_conflict_policy(mut self, policy: ConflictPolicy) -> Self {
+    self.conflict_policy = policy;
+    self
+  }
   fn get_last_token(&self) -> &Token {
-    &self.tokens[if self.pos == 0 { 0 } else { self.pos - 1 }]
+    self.last.as_ref().or(self.current.as_ref()).unwrap()
   }
   fn get_current_token(&self) -> &Token {
-    &self.tokens[self.pos]
+    self.current.as_ref().unwrap()
+  }
+  pub fn get_errors(&self) -> &[ParserError] {
+    &self.error_list
+  }
+  pub fn get_conflicts(&self) -> &[Conflict] {
+    &self.conflicts
+  }
+
+  pub fn get_tree(&self) -> Option<&TreeNode> {
+    if self.error_list.is_empty() {
+      self.status.node_stack.last()
+    } else {
+      None
+    }
   }
+
   fn can_process(&self) -> bool {
     self.action_table
       .get(&(
@@ -44,23 +68,19 @@ impl LR1Parser {
 }
 
 impl LR1Parser {
-  pub fn construct_tree(mut self, input: &[Token]) -> Self {
-    self.tokens = input.to_owned();
-    self.tokens.push(END_SYMBOL.clone());
+  pub fn construct_tree(self, input: &[Token]) -> Self {
+    self.parse_stream(input.iter().cloned())
+  }
+
+  // 按需从input中取词素，不需要事先得到全部输入
+  pub fn parse_stream<I: IntoIterator<Item = Token>>(mut self, input: I) -> Self {
+    let mut input = input.into_iter().chain(std::iter::once(END_SYMBOL.clone()));
+    self.current = input.next();
     self.status_stack
       .push((END_SYMBOL.clone(), self.status.clone()));
 
-    loop {
-      if self.pos >= self.tokens.len() {
-        break;
-      }
-
-      if self.pos == 327 {
-        println!("debug");
-      }
-
+    while let Some(symbol) = self.current.clone() {
       let state = self.status.state_stack.last().unwrap().clone();
-      let symbol = self.tokens[self.pos].clone();
 
       let action = self
         .action_table
@@ -74,65 +94,39 @@ impl LR1Parser {
             element: symbol.clone(),
             children: None,
           });
-          self.step_forward();
+          self.step_forward(&mut input);
         }
         Some(Action::Reduce(prod_head, prod_body)) => {
-          let mut children: Vec<TreeNode> = Vec::new();
-          for _ in 0..prod_body.len() {
-            self.status.state_stack.pop();
-            children.push(self.status.node_stack.pop().unwrap());
-          }
-          children.reverse();
-
-          let state = self.status.state_stack.last().unwrap().clone();
-          let state = self
-            .goto_table
-            .get(&(state, prod_head.clone()))
-            .unwrap()
-            .clone();
-
-          self.status.state_stack.push(state);
-          self.status.node_stack.push(TreeNode {
-            element: Token::new_not_terminal(prod_head.clone(), None),
-            children: Some(children),
-          });
+          self.reduce(&prod_head, &prod_body);
         }
         Some(Action::Accept) => {
           break;
         }
         None => {
-          if let Some(Action::Shift(t)) = self
-            .action_table
-            .get(&(state.clone(), EMPTY_SYMBOL.get_value().clone()))
-            .cloned()
-          {
-            self.status.state_stack.push(t);
-            self.status.node_stack.push(TreeNode {
-              element: EMPTY_SYMBOL.clone(),
-              children: None,
-            });
-          } else {
-            self.err_handle();
-          }
+          self.err_handle(&mut input);
         }
       }
     }
     self
   }
 
-  fn err_handle(&mut self) {
+  fn err_handle(&mut self, input: &mut dyn Iterator<Item = Token>) {
     // 发生了错误
     // 首先记录错误信息
     self.error_list.push(ParserError {
       error_type: ErrorType::Unknown(format!(
-        "Unexpected symbol '{:?}' at position {}",
-        self.tokens[self.pos], self.pos
+        "Unexpected symbol '{}'",
+        self.get_current_token().get_value()
       )),
-      error_pos: self.tokens[self.pos].get_pos().unwrap().clone(),
+      error_pos: self.get_current_token().get_pos().unwrap().clone(),
+      error_span: *self.get_current_token().get_span(),
     });
 
-    // 取得上一个能被恢复的状态
-    let (_, restore_status) = self.status_stack.pop().unwrap();
+    // 取得上一个能被恢复的状态；文法中没有err产生式时无法恢复，停止分析
+    let Some((_, restore_status)) = self.status_stack.pop() else {
+      self.current = None;
+      return;
+    };
     // 恢复状态
     self.status = restore_status;
     // 在此状态，根据分析表处理错误状态
@@ -149,43 +143,56 @@ impl LR1Parser {
           element: symbol.clone(),
           children: None,
         });
-        self.step_forward();
+        self.step_forward(input);
       }
 
       Some(Action::Reduce(prod_head, prod_body)) => {
         // 规约
-        let mut children: Vec<TreeNode> = Vec::new();
-        for _ in 0..prod_body.len() {
-          self.status.state_stack.pop();
-          children.push(self.status.node_stack.pop().unwrap());
-        }
-        children.reverse();
-
-        let state = self.status.state_stack.last().unwrap().clone();
-        let state = self
-          .goto_table
-          .get(&(state, prod_head.clone()))
-          .unwrap()
-          .clone();
-
-        self.status.state_stack.push(state);
-        self.status.node_stack.push(TreeNode {
-          element: Token::new_not_terminal(prod_head.clone(), None),
-          children: Some(children),
-        });
+        self.reduce(&prod_head, &prod_body);
       }
-      _ => unreachable!(),
+      _ => {
+        self.current = None;
+        return;
+      }
+    }
+    // 之后，继续处理输入。跳过不能处理的输入符号
+    while self.current.is_some() && !self.can_process() {
+      self.advance(input);
     }
-    // 之后，继续处理输入。将pos移动到能处理的位置
-    while !self.can_process() {
-      self.pos += 1;
-      if self.pos >= self.tokens.len() {
-        break;
+  }
+
+  // 按产生式规约；空产生式不弹出符号，在树中留下一个ε叶子
+  fn reduce(&mut self, prod_head: &str, prod_body: &[String]) {
+    let mut children: Vec<TreeNode> = Vec::new();
+    if is_empty_body(prod_body) {
+      children.push(TreeNode {
+        element: EMPTY_SYMBOL.clone(),
+        children: None,
+      });
+    } else {
+      for _ in 0..prod_body.len() {
+        self.status.state_stack.pop();
+        children.push(self.status.node_stack.pop().unwrap());
       }
+      children.reverse();
     }
+
+    let state = *self.status.state_stack.last().unwrap();
+    let state = self.goto_table[&(state, prod_head.to_string())];
+
+    self.status.state_stack.push(state);
+    self.status.node_stack.push(TreeNode {
+      element: Token::new_not_terminal(prod_head.to_string(), None),
+      children: Some(children),
+    });
+  }
+
+  fn advance(&mut self, input: &mut dyn Iterator<Item = Token>) {
+    self.last = self.current.take();
+    self.current = input.next();
   }
 
-  fn step_forward(&mut self) {
+  fn step_forward(&mut self, input: &mut dyn Iterator<Item = Token>) {
     // 如果当前状态可以用来恢复错误，那么就把当前状态压栈
     // 如何判断当前状态可以用来恢复错误？
     // 查看当前状态的期望符号集，若其中包含err，那么就可以用来恢复错误
@@ -199,7 +206,7 @@ impl LR1Parser {
       self.status_stack
         .push((self.get_last_token().clone(), self.status.clone()));
     }
-    self.pos += 1;
+    self.advance(input);
   }
 }
 
@@ -227,6 +234,8 @@ impl Default for Status {
 pub struct ParserError {
   pub error_type: ErrorType,
   pub error_pos: Point,
+  // 出错的词素的区间，手工构造的词素没有区间
+  pub error_span: Option<Span>,
 }
 
 #[derive(Debug)]
@@ -239,14 +248,52 @@ pub enum ErrorType {
   Unknown(String),
 }
 
+impl Display for ParserError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match &self.error_type {
+      ErrorType::Unknown(message) => write!(f, "{}", message),
+    }
+  }
+}
+
 #[derive(Clone, PartialEq, Eq, Debug, Ord, PartialOrd, Serialize, Deserialize)]
 pub struct LR1Item {
   pub(crate) head: String,
-  body: Vec<String>,
-  dot: usize,
+  pub(crate) body: Vec<String>,
+  pub(crate) dot: usize,
   pub(crate) lookahead: String,
 }
 
+// 右部只有ε的空产生式
+fn is_empty_body(body: &[String]) -> bool {
+  matches!(body, [symbol] if symbol == EMPTY_SYMBOL.get_value())
+}
+
+impl LR1Item {
+  // 点之后的符号；空产生式的项目总是完整的，ε不作为移进的符号
+  fn next_symbol(&self) -> Option<&String> {
+    if is_empty_body(&self.body) {
+      None
+    } else {
+      self.body.get(self.dot)
+    }
+  }
+
+  // 不含向前看符号的核心，形如 A → α · β
+  pub fn core(&self) -> String {
+    let mut symbols: Vec<&str> = self.body.iter().map(String::as_str).collect();
+    let dot = if is_empty_body(&self.body) { 1 } else { self.dot };
+    symbols.insert(dot, "·");
+    format!("{} → {}", self.head, symbols.join(" "))
+  }
+}
+
+impl Display for LR1Item {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{}, {}", self.core(), self.lookahead)
+  }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Action {
   Shift(usize),
@@ -282,65 +329,79 @@ impl Display for LR1Parser {
   }
 }
 
-#[allow(clippy::print_in_format_impl)]
 impl Display for TreeNode {
   fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-    fn print_tree(tree: &TreeNode, depth: usize) {
+    fn print_tree(tree: &TreeNode, depth: usize, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
       let mut indent = String::new();
       for _ in 0..depth {
         indent.push_str("  ");
       }
       if tree.element != *EMPTY_SYMBOL {
-        println!("{}{:?}", indent, tree.element);
+        writeln!(f, "{}{:?}", indent, tree.element)?;
       }
       if let Some(children) = &tree.children {
         for child in children {
-          print_tree(child, depth + 1);
+          print_tree(child, depth + 1, f)?;
         }
       }
+      Ok(())
     }
-    print_tree(self, 0);
-    writeln!(f)
+    print_tree(self, 0, f)
   }
 }
 
 impl LR1Parser {
-  pub fn construct_parsing_table(&mut self, grammar: &Grammar) {
-    if file_exists(ACTION_TABLE) && file_exists(GOTO_TABLE) {
-      let action_file = File::open(ACTION_TABLE).expect("Unable to open action table file");
-      let goto_file = File::open(GOTO_TABLE).expect("Unable to open goto table file");
+  // 分析表总是构造出来；冲突按ConflictPolicy::Error处理时返回全部冲突
+  pub fn construct_parsing_table(&mut self, grammar: &Grammar) -> Result<(), Vec<Conflict>> {
+    let data_path = cache_dir(grammar);
+    let action_path = format!("{}{}", data_path, ACTION_TABLE);
+    let goto_path = format!("{}{}", data_path, GOTO_TABLE);
+    let conflicts_path = format!("{}{}", data_path, CONFLICTS);
+    if file_exists(&action_path) && file_exists(&goto_path) && file_exists(&conflicts_path) {
+      let action_file = File::open(&action_path).expect("Unable to open action table file");
+      let goto_file = File::open(&goto_path).expect("Unable to open goto table file");
+      let conflicts_file = File::open(&conflicts_path).expect("Unable to open conflicts file");
       self.action_table = bincode::deserialize_from(action_file).unwrap();
       self.goto_table = bincode::deserialize_from(goto_file).unwrap();
+      self.conflicts = bincode::deserialize_from(conflicts_file).unwrap();
     } else {
       self.construct_parsing_table_core(grammar);
 
-      create_dir_all(DATA_PATH).expect("Unable to create action table file");
+      create_dir_all(&data_path).expect("Unable to create action table file");
       let mut action_file =
-        File::create(ACTION_TABLE).expect("Unable to create action table file");
-      let mut goto_file = File::create(GOTO_TABLE).expect("Unable to create goto table file");
+        File::create(&action_path).expect("Unable to create action table file");
+      let mut goto_file = File::create(&goto_path).expect("Unable to create goto table file");
+      let mut conflicts_file = File::create(&conflicts_path).expect("Unable to create conflicts file");
       bincode::serialize_into(&mut action_file, &self.action_table)
         .expect("Unable to serialize action table");
       bincode::serialize_into(&mut goto_file, &self.goto_table)
         .expect("Unable to serialize goto table");
+      bincode::serialize_into(&mut conflicts_file, &self.conflicts)
+        .expect("Unable to serialize conflicts");
+    }
+    match self.conflict_policy {
+      ConflictPolicy::Error if !self.conflicts.is_empty() => Err(self.conflicts.clone()),
+      _ => Ok(()),
     }
   }
 
-  // 构建LR1分析表
+  // 构建LR1分析表：先收集每个状态、每个向前看符号上的全部候选动作，再逐一消解冲突
   fn construct_parsing_table_core(&mut self, grammar: &Grammar) {
+    let mut candidates: BTreeMap<(State, String), Vec<(Action, LR1Item)>> = BTreeMap::new();
     for (state, item_set) in self.lr1_sets.iter().enumerate() {
       for item in item_set {
-        let dot_position = item.dot;
-        let next_symbol = item.body.get(dot_position);
-        match next_symbol {
+        match item.next_symbol() {
           Some(token) if grammar.token_list.contains(token) => {
             let goto_set =
               self.goto(grammar, item_set, token);
             let goto_state = self.lr1_sets.iter().position(|x| *x == goto_set).unwrap();
-            self.action_table.insert(
-              (state, token.clone()),
-              Action::Shift(goto_state),
-            );
+            candidates
+              .entry((state, token.clone()))
+              .or_default()
+              .push((Action::Shift(goto_state), item.clone()));
           }
+          // 没有定义的符号不能到达，没有转移
+          Some(token) if !grammar.pro_list.contains_key(token) && !grammar.token_list.contains(token) => {}
           Some(token) if !grammar.token_list.contains(token) => {
             let goto_set =
               self.goto(grammar, item_set, token);
@@ -349,45 +410,43 @@ impl LR1Parser {
               .insert((state, token.clone()), goto_state);
           }
           Some(_) => { unreachable!() }
-          // TODO: 出错检查此处
           None => {
-            if item.head == grammar.start_symbol
+            let action = if item.head == grammar.start_symbol
               && item.lookahead == *END_SYMBOL.get_value()
             {
-              self.action_table.insert(
-                (state, item.lookahead.clone()),
-                Action::Accept,
-              );
+              Action::Accept
             } else {
-              let prod_index = grammar
-                .pro_list
-                .get(&item.head)
-                .unwrap()
-                .iter()
-                .position(|x| *x == item.body)
-                .unwrap();
-              self.action_table.insert(
-                (state, item.lookahead.clone()),
-                Action::Reduce(
-                  item.head.clone(),
-                  grammar.pro_list.get(&item.head).unwrap()[prod_index].clone(),
-                ),
-              );
-            }
+              Action::Reduce(item.head.clone(), item.body.clone())
+            };
+            candidates
+              .entry((state, item.lookahead.clone()))
+              .or_default()
+              .push((action, item.clone()));
           }
         }
       }
     }
+
+    self.conflicts.clear();
+    for ((state, lookahead), candidates) in candidates {
+      let (action, conflict) = conflict::resolve(grammar, state, &lookahead, &candidates);
+      if let Some(action) = action {
+        self.action_table.insert((state, lookahead), action);
+      }
+      self.conflicts.extend(conflict);
+    }
   }
 
   pub fn compute_lr1_item_sets(&mut self, grammar: &Grammar) {
-    if file_exists(LR1_SETS) {
-      let lr1_file = File::open(LR1_SETS).expect("Unable to open action table file");
+    let data_path = cache_dir(grammar);
+    let lr1_path = format!("{}{}", data_path, LR1_SETS);
+    if file_exists(&lr1_path) {
+      let lr1_file = File::open(&lr1_path).expect("Unable to open action table file");
       self.lr1_sets = bincode::deserialize_from(lr1_file).unwrap();
     } else {
       self.compute_lr1_item_sets_core(grammar, &grammar.start_symbol);
-      create_dir_all(DATA_PATH).expect("Unable to create action table file");
-      let mut lr1_file = File::create(LR1_SETS).expect("Unable to create action table file");
+      create_dir_all(&data_path).expect("Unable to create action table file");
+      let mut lr1_file = File::create(&lr1_path).expect("Unable to create action table file");
       bincode::serialize_into(&mut lr1_file, &self.lr1_sets)
         .expect("Unable to serialize action table");
     }
@@ -397,7 +456,12 @@ impl LR1Parser {
   fn compute_lr1_item_sets_core(&mut self, grammar: &Grammar, start_symbol: &str) {
     let mut item_sets = Vec::<BTreeSet<LR1Item>>::new();
     let head = start_symbol.to_string();
-    let body = vec![grammar.pro_list.get(start_symbol).unwrap()[0][0].clone()];
+    // 没有开始产生式时得到空的项目集族，分析时报告语法错误；文法检查会报告缺少开始产生式
+    let Some(first) = grammar.pro_list.get(start_symbol).and_then(|body| body.first()?.first()) else {
+      self.lr1_sets = Vec::new();
+      return;
+    };
+    let body = vec![first.clone()];
     let dot = 0;
     let lookahead = END_SYMBOL.get_value().clone();
     let initial_item = LR1Item {
@@ -492,15 +556,17 @@ impl LR1Parser {
       changed = false;
 
       for item in closure_set.clone() {
-        let dot_position = item.dot;
-        if dot_position < item.body.len() {
-          let next_symbol = &item.body[dot_position];
+        if let Some(next_symbol) = item.next_symbol() {
+          let dot_position = item.dot;
           if grammar.token_list.contains(next_symbol) {
             continue;
           } else {
             let lookahead_symbols = grammar
               .first_symbols(&item.body[(dot_position + 1)..], &item.lookahead);
-            let productions = grammar.pro_list.get(next_symbol).unwrap();
+            // 没有定义的符号不展开，由文法检查报告
+            let Some(productions) = grammar.pro_list.get(next_symbol) else {
+              continue;
+            };
 
             for production in productions {
               for lookahead_symbol in &lookahead_symbols {
@@ -559,7 +625,7 @@ impl LR1Parser {
     let mut goto_set = BTreeSet::new();
 
     for item in item_set {
-      if item.dot < item.body.len() && &item.body[item.dot] == symbol {
+      if item.next_symbol() == Some(symbol) {
         let mut new_item = item.clone();
         new_item.dot += 1;
         goto_set.insert(new_item);
@@ -1,16 +1,27 @@
+mod conflict;
+mod ebnf;
 mod grammar;
 mod lr1_parser;
+mod precedence;
+mod sets;
+mod validate;
 
 use lazy_static::lazy_static;
 use crate::token::{Token, TokenType, TokenKind};
+pub use conflict::{Conflict, ConflictKind, ConflictPolicy};
 pub use grammar::Grammar;
-pub use lr1_parser::{LR1Parser, TreeNode};
+pub use precedence::{Assoc, Precedence};
+pub use lr1_parser::{Action, ErrorType, LR1Item, LR1Parser, ParserError, TreeNode};
+pub use validate::{GrammarIssue, IssueKind};
 use crate::lexer::Point;
 
 const DATA_PATH: &str = "./data/";
-const ACTION_TABLE: &str = "./data/action_table.rcp";
-const GOTO_TABLE: &str = "./data/goto_table.rcp";
-const LR1_SETS: &str = "./data/lr1_sets.rcp";
+const ACTION_TABLE: &str = "action_table.rcp";
+const GOTO_TABLE: &str = "goto_table.rcp";
+const LR1_SETS: &str = "lr1_sets.rcp";
+const CONFLICTS: &str = "conflicts.rcp";
+// 分析表缓存的格式或构造方法改变时加一，旧的缓存随之失效
+const CACHE_VERSION: u32 = 2;
 
 lazy_static!(
   pub static ref START_SYMBOL: Token = Token::new_not_terminal("CompUnit'".to_string(), None);
@@ -20,6 +31,11 @@ lazy_static!(
   pub static ref ERROR_SYMBOL: Token = Token::new_terminal("err".to_string(), None);
 );
 
+// 缓存目录按文法内容区分，避免不同文法共用同一份分析表
+fn cache_dir(grammar: &Grammar) -> String {
+  format!("{}{:016x}/", DATA_PATH, grammar.fingerprint())
+}
+
 // 单元测试
 #[cfg(test)]
 mod tests {
@@ -50,8 +66,368 @@ mod tests {
     grammar.grammar_load("../g.txt");
     let mut lr1_parser = LR1Parser::new();
     lr1_parser.compute_lr1_item_sets(&grammar);
-    lr1_parser.construct_parsing_table(&grammar);
+    lr1_parser.construct_parsing_table(&grammar).expect("conflicts are resolved by default");
     println!("{:?}", lr1_parser.action_table);
     println!("{:?}", lr1_parser.goto_table);
   }
-}
\ No newline at end of file
+
+  // 没有通过文法检查的文法也能构造分析表，不会panic；用到未定义符号的输入报告语法错误
+  #[test]
+  fn test_undefined_symbols() {
+    use crate::lexer::{Cursor, TokenStream};
+
+    for text in ["%token Ident\nCompUnit':S\nS:Ident Missing | Ident\n", "%token Ident\nS:Ident\n"] {
+      let mut grammar = Grammar::new();
+      grammar.grammar_load_text(text);
+      assert!(grammar.validate().iter().any(GrammarIssue::is_error));
+      let mut lr1 = LR1Parser::new();
+      lr1.compute_lr1_item_sets(&grammar);
+      lr1.construct_parsing_table(&grammar).unwrap();
+      let tokens = Cursor::new("a b").map(Result::ok).map(Option::unwrap).skip_trivia();
+      let lr1 = lr1.parse_stream(tokens);
+      assert!(!lr1.get_errors().is_empty() && lr1.get_tree().is_none());
+    }
+  }
+
+  // 缓存的键只取决于缓存格式的版本号和文法文本，不随编译器版本变化
+  #[test]
+  fn test_fingerprint() {
+    let fingerprint = |text: &str| {
+      let mut grammar = Grammar::new();
+      grammar.grammar_load_text(text);
+      grammar.fingerprint()
+    };
+    assert_eq!(CACHE_VERSION, 2);
+    assert_eq!(fingerprint("%token a\nCompUnit':a\n"), 0x0e63_44f3_76f9_a325);
+    assert_ne!(fingerprint("%token a\nCompUnit':a\n"), fingerprint("%token a\nCompUnit': a\n"));
+  }
+
+  // 文法检查报告各类问题及其行号
+  #[test]
+  fn test_validate() {
+    let mut grammar = Grammar::new();
+    grammar.grammar_load_text(
+      "%token a b c ε\n\
+       {\n\
+       CompUnit':S\n\
+       S:A b | Missing\n\
+       A:a | ε\n\
+       A:a a\n\
+       Loop:a Loop\n\
+       what is this\n\
+       E:\n\
+       }\n",
+    );
+    let issues: Vec<_> = grammar.validate().into_iter().map(|issue| (issue.line, issue.kind)).collect();
+    assert_eq!(
+      issues,
+      [
+        (1, IssueKind::UnusedToken("c".to_string())),
+        (4, IssueKind::UndefinedSymbol("Missing".to_string())),
+        (6, IssueKind::DuplicateHead { head: "A".to_string(), first_line: 5 }),
+        (7, IssueKind::Unreachable("Loop".to_string())),
+        (7, IssueKind::Nonproductive("Loop".to_string())),
+        (8, IssueKind::Malformed("expected `%token ...` or `Head:body`, found `what is this`".to_string())),
+        (9, IssueKind::Malformed("empty alternative for `E`, write `ε` instead".to_string())),
+        (9, IssueKind::Unreachable("E".to_string())),
+        (9, IssueKind::Nonproductive("E".to_string())),
+      ]
+    );
+
+    let mut grammar = Grammar::new();
+    grammar.grammar_load_text("%token a\nS:a\n");
+    let issues = grammar.validate();
+    assert_eq!(issues.len(), 1);
+    assert_eq!(issues[0].to_string(), "line 2: missing a production for the start symbol `CompUnit'`");
+    assert!(issues[0].is_error());
+
+    // 随仓库提供的文法没有问题
+    let mut grammar = Grammar::new();
+    grammar.grammar_load("../g3.txt");
+    assert_eq!(grammar.validate(), []);
+  }
+
+  // 注释、跨行的产生式、带引号的终结符和EBNF改写为BNF
+  #[test]
+  fn test_ebnf() {
+    let mut grammar = Grammar::new();
+    grammar.grammar_load_text(
+      "# 表达式文法\n\
+       %token Ident IntConst ε\n\
+       {\n\
+       CompUnit':Exp\n\
+       Exp:Term (('+' | '-') Term)*   # 左结合的加减\n\
+       Term:Factor\n  \
+         | Factor '*' Term\n\
+       Factor:Ident Args? | IntConst | '(' Exp ')'\n\
+       Args:'(' (Exp (',' Exp)*)? ')'\n\
+       List:Ident+\n\
+       }\n",
+    );
+    let rules = |head: &str| -> Vec<String> { grammar.pro_list[head].iter().map(|item| item.join(" ")).collect() };
+    assert_eq!(rules("Exp"), ["Term Exp.rep1"]);
+    assert_eq!(rules("Exp.rep1"), ["Exp.grp2 Term Exp.rep1", "ε"]);
+    assert_eq!(rules("Exp.grp2"), ["+", "-"]);
+    assert_eq!(rules("Term"), ["Factor", "Factor * Term"]);
+    assert_eq!(rules("Factor"), ["Ident Factor.opt3", "IntConst", "( Exp )"]);
+    assert_eq!(rules("Factor.opt3"), ["Args", "ε"]);
+    assert_eq!(rules("Args"), ["( Args.opt4 )"]);
+    assert_eq!(rules("Args.opt4"), ["Exp Args.rep5", "ε"]);
+    assert_eq!(rules("Args.rep5"), [", Exp Args.rep5", "ε"]);
+    assert_eq!(rules("List"), ["Ident List.rep6"]);
+    assert_eq!(rules("List.rep6"), ["Ident List.rep6", "ε"]);
+    assert_eq!(grammar.token_list, ["Ident", "IntConst", "ε", "+", "-", "*", "(", ")", ","]);
+    assert_eq!(grammar.first_sets["Exp"], ["(", "Ident", "IntConst"].map(String::from).into());
+    let issues: Vec<_> = grammar.validate().into_iter().map(|issue| (issue.line, issue.kind)).collect();
+    assert_eq!(issues, [(10, IssueKind::Unreachable("List".to_string()))]);
+
+    let mut grammar = Grammar::new();
+    grammar.grammar_load_text("  | a\n%token a\nCompUnit':( a\nB:a ]\nC:'a\nD:a )\n");
+    let issues: Vec<_> = grammar.validate().into_iter().map(|issue| issue.to_string()).collect();
+    assert_eq!(
+      issues,
+      [
+        "line 1: continuation line without a production",
+        "line 1: missing a production for the start symbol `CompUnit'`",
+        "line 2: token `a` is never used",
+        "line 3: in production of `CompUnit'`: missing `)`",
+        "line 4: in production of `B`: `]` is not a declared token; quote it or add it to %token",
+        "line 5: in production of `C`: unterminated quoted terminal",
+        "line 6: in production of `D`: unmatched `)`",
+      ]
+    );
+  }
+
+  fn set(symbols: &[&str]) -> std::collections::BTreeSet<String> {
+    symbols.iter().map(|s| s.to_string()).collect()
+  }
+
+  // 龙书例4.28：消除左递归后的表达式文法
+  #[test]
+  fn test_first_follow() {
+    let mut grammar = Grammar::new();
+    grammar.grammar_load_text(
+      "%token id ( ) + * ε\n\
+       CompUnit':E\n\
+       E:T E'\n\
+       E':+ T E' | ε\n\
+       T:F T'\n\
+       T':* F T' | ε\n\
+       F:( E ) | id\n",
+    );
+    assert_eq!(grammar.nullable_set(), &set(&["E'", "T'"]));
+    for head in ["E", "T", "F"] {
+      assert_eq!(grammar.first_set(head), set(&["(", "id"]));
+    }
+    assert_eq!(grammar.first_set("E'"), set(&["+", "ε"]));
+    assert_eq!(grammar.first_set("T'"), set(&["*", "ε"]));
+    assert_eq!(grammar.first_set("+"), set(&["+"]));
+    assert_eq!(grammar.follow_set("CompUnit'"), set(&["#"]));
+    assert_eq!(grammar.follow_set("E"), set(&[")", "#"]));
+    assert_eq!(grammar.follow_set("E'"), set(&[")", "#"]));
+    assert_eq!(grammar.follow_set("T"), set(&["+", ")", "#"]));
+    assert_eq!(grammar.follow_set("T'"), set(&["+", ")", "#"]));
+    assert_eq!(grammar.follow_set("F"), set(&["+", "*", ")", "#"]));
+  }
+
+  // 左递归的文法（龙书例4.1）
+  #[test]
+  fn test_left_recursion() {
+    let mut grammar = Grammar::new();
+    grammar.grammar_load_text(
+      "%token id ( ) + *\n\
+       CompUnit':E\n\
+       E:E + T | T\n\
+       T:T * F | F\n\
+       F:( E ) | id\n",
+    );
+    assert!(grammar.nullable_set().is_empty());
+    for head in ["E", "T", "F"] {
+      assert_eq!(grammar.first_set(head), set(&["(", "id"]));
+    }
+    assert_eq!(grammar.follow_set("E"), set(&["+", ")", "#"]));
+    assert_eq!(grammar.follow_set("T"), set(&["+", "*", ")", "#"]));
+    assert_eq!(grammar.follow_set("F"), set(&["+", "*", ")", "#"]));
+  }
+
+  // 相互递归且可空的非终结符，以及符号串的FIRST集合
+  #[test]
+  fn test_nullable_cycle() {
+    let mut grammar = Grammar::new();
+    grammar.grammar_load_text(
+      "%token a b c ε\n\
+       CompUnit':S\n\
+       S:A B | c\n\
+       A:B a | ε\n\
+       B:A b | ε\n",
+    );
+    assert_eq!(grammar.nullable_set(), &set(&["A", "B", "CompUnit'", "S"]));
+    assert!(grammar.is_nullable("ε") && !grammar.is_nullable("a"));
+    assert_eq!(grammar.first_set("A"), set(&["a", "b", "ε"]));
+    assert_eq!(grammar.first_set("B"), set(&["a", "b", "ε"]));
+    assert_eq!(grammar.first_set("S"), set(&["a", "b", "c", "ε"]));
+    assert_eq!(grammar.follow_set("A"), set(&["a", "b", "#"]));
+    assert_eq!(grammar.follow_set("B"), set(&["a", "#"]));
+
+    let symbols = |text: &str| text.split_whitespace().map(String::from).collect::<Vec<_>>();
+    assert_eq!(grammar.first_of(&symbols("A B c")), set(&["a", "b", "c"]));
+    assert_eq!(grammar.first_of(&symbols("A B")), set(&["a", "b", "ε"]));
+    assert_eq!(grammar.first_of(&[]), set(&["ε"]));
+    assert_eq!(grammar.first_symbols(&symbols("A B"), &"#".to_string()), set(&["a", "b", "#"]));
+  }
+
+  // 悬空else的移进/归约冲突和两个相同右部的归约/归约冲突，按惯例消解并全部报告
+  #[test]
+  fn test_conflicts() {
+    let tables = |text: &str, policy: ConflictPolicy| {
+      let mut grammar = Grammar::new();
+      grammar.grammar_load_text(text);
+      let mut lr1 = LR1Parser::new().with_conflict_policy(policy);
+      lr1.compute_lr1_item_sets(&grammar);
+      let result = lr1.construct_parsing_table(&grammar);
+      (lr1, result)
+    };
+
+    let dangling_else = "%token if else x\nCompUnit':S\nS:if S | if S else S | x\n";
+    let (lr1, result) = tables(dangling_else, ConflictPolicy::PreferShift);
+    assert!(result.is_ok());
+    let conflicts = lr1.get_conflicts();
+    assert_eq!(conflicts.len(), 1);
+    assert_eq!(conflicts[0].kind, ConflictKind::ShiftReduce);
+    assert_eq!(conflicts[0].lookahead, "else");
+    assert_eq!(conflicts[0].line, 3);
+    assert!(matches!(conflicts[0].chosen, Action::Shift(_)));
+    assert_eq!(conflicts[0].item_lines(), ["shift  S → if S · else S", "reduce S → if S ·"]);
+    assert!(matches!(lr1.action_table[&(conflicts[0].state, "else".to_string())], Action::Shift(_)));
+
+    let same_body = "%token a\nCompUnit':S\nS:B | A\nB:a\nA:a\n";
+    let (lr1, result) = tables(same_body, ConflictPolicy::Error);
+    let conflicts = result.unwrap_err();
+    assert_eq!(conflicts, lr1.get_conflicts());
+    assert_eq!(conflicts[0].kind, ConflictKind::ReduceReduce);
+    assert_eq!(conflicts[0].lookahead, "#");
+    // 文件中靠前的 B:a 优先，与左部的字母顺序无关
+    assert_eq!(conflicts[0].chosen, Action::Reduce("B".to_string(), vec!["a".to_string()]));
+    assert_eq!(conflicts[0].resolution(), "resolved as reduce by B → a");
+
+    // 空产生式在其向前看符号上归约，与移进的冲突同样报告
+    let empty = "%token a ε\nCompUnit':S\nS:S a | a | ε\n";
+    let (lr1, result) = tables(empty, ConflictPolicy::Error);
+    let conflicts = result.unwrap_err();
+    assert_eq!(conflicts.len(), 1);
+    assert_eq!(conflicts[0].kind, ConflictKind::ShiftReduce);
+    assert_eq!((conflicts[0].state, conflicts[0].lookahead.as_str()), (0, "a"));
+    assert_eq!(conflicts[0].item_lines(), ["shift  S → · a", "reduce S → ε ·"]);
+    assert_eq!(lr1.action_table[&(0, "#".to_string())], Action::Reduce("S".to_string(), vec!["ε".to_string()]));
+    assert!(!lr1.action_table.contains_key(&(0, "ε".to_string())));
+
+    let (lr1, result) = tables("%token x\nCompUnit':S\nS:x\n", ConflictPolicy::Error);
+    assert!(result.is_ok() && lr1.get_conflicts().is_empty());
+  }
+
+  // 用优先级声明消解二义的表达式文法：左结合、右结合、%prec 和不可结合的运算符
+  #[test]
+  fn test_precedence() {
+    use crate::lexer::{Cursor, TokenStream};
+
+    let mut grammar = Grammar::new();
+    grammar.grammar_load_text(
+      "%token Ident ( )\n\
+       %nonassoc <\n\
+       %left + -\n\
+       %left * /\n\
+       %right %\n\
+       %right NEG\n\
+       CompUnit':E\n\
+       E:E + E | E - E | E * E | E / E | E % E | E < E\n\
+       \x20 | - E %prec NEG | ( E ) | Ident\n",
+    );
+    assert!(grammar.validate().is_empty());
+    assert_eq!(grammar.token_precedence("*"), Some(Precedence { level: 3, assoc: Assoc::Left }));
+    let minus = ["-".to_string(), "E".to_string()];
+    assert_eq!(grammar.production_precedence("E", &minus).map(|p| p.level), Some(5));
+    let mut lr1 = LR1Parser::new().with_conflict_policy(ConflictPolicy::Error);
+    lr1.compute_lr1_item_sets(&grammar);
+    lr1.construct_parsing_table(&grammar).unwrap();
+
+    // 把分析树写成完全加括号的表达式
+    fn show(node: &TreeNode) -> String {
+      let children = node.children.as_deref().unwrap_or_default();
+      match children {
+        [] => node.element.get_value().clone(),
+        [only] => show(only),
+        [_, operand] => format!("(-{})", show(operand)),
+        [open, inner, _] if open.element.get_value() == "(" => show(inner),
+        [left, op, right] => format!("({} {} {})", show(left), op.element.get_value(), show(right)),
+        _ => unreachable!(),
+      }
+    }
+    let parse = |source: &str| {
+      let tokens = Cursor::new(source).map(Result::ok).map(Option::unwrap).skip_trivia();
+      let mut parser = LR1Parser::new();
+      parser.action_table = lr1.action_table.clone();
+      parser.goto_table = lr1.goto_table.clone();
+      parser.parse_stream(tokens).get_tree().map(show)
+    };
+    assert_eq!(parse("a - b - c").unwrap(), "((a - b) - c)");
+    assert_eq!(parse("a + b * c - d").unwrap(), "((a + (b * c)) - d)");
+    assert_eq!(parse("a % b % c").unwrap(), "(a % (b % c))");
+    assert_eq!(parse("- a * b").unwrap(), "((-a) * b)");
+    assert_eq!(parse("(a + b) * c < d").unwrap(), "(((a + b) * c) < d)");
+    assert_eq!(parse("a < b < c"), None);
+
+    let mut grammar = Grammar::new();
+    grammar.grammar_load_text(
+      "%token a b\n\
+       %left a\n\
+       %right a\n\
+       CompUnit':S\n\
+       S:a %prec b | a b\n\
+       T:(a %prec a | b)\n",
+    );
+    let malformed: Vec<_> = grammar
+      .validate()
+      .into_iter()
+      .filter_map(|issue| match issue.kind {
+        IssueKind::Malformed(message) => Some((issue.line, message)),
+        _ => None,
+      })
+      .collect();
+    assert_eq!(
+      malformed,
+      [
+        (3, "precedence of `a` is declared again".to_string()),
+        (5, "`%prec b` names a token without precedence; declare it with %left, %right or %nonassoc".to_string()),
+        (6, "in production of `T`: `%prec` must end a top-level alternative".to_string()),
+      ]
+    );
+  }
+
+  // 词素流式输入与事先收集的结果相同，输入提前结束时报告错误
+  #[test]
+  fn test_parse_stream() {
+    use crate::lexer::{Cursor, TokenStream};
+    use crate::test_util::PARSER_LOCK;
+
+    let tables = || {
+      let _guard = PARSER_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+      let mut grammar = Grammar::new();
+      grammar.grammar_load("../g3.txt");
+      let mut lr1 = LR1Parser::new();
+      lr1.compute_lr1_item_sets(&grammar);
+      lr1.construct_parsing_table(&grammar).expect("conflicts are resolved by default");
+      lr1
+    };
+
+    let source = "int main() { int a = 1; // 注释\n return a + 2; }";
+    let tokens: Vec<_> = Cursor::new(source).map(Result::ok).map(Option::unwrap).skip_trivia().collect();
+    let collected = tables().construct_tree(&tokens);
+    let streamed = tables().parse_stream(Cursor::new(source).map(Result::ok).map(Option::unwrap).skip_trivia());
+    assert!(streamed.get_errors().is_empty());
+    assert_eq!(streamed.get_tree(), collected.get_tree());
+
+    let truncated = tables().parse_stream(Cursor::new("int main() {").map(Result::ok).map(Option::unwrap).skip_trivia());
+    assert_eq!(truncated.get_errors().len(), 1);
+    assert!(truncated.get_tree().is_none());
+  }
+}
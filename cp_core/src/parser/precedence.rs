@@ -0,0 +1,74 @@
+use crate::parser::grammar::Grammar;
+
+// yacc式的优先级声明：%left、%right、%nonassoc 每行一个优先级，越靠后的行优先级越高；
+// 产生式的优先级取 %prec 指定的终结符，没有时取右部最后一个有优先级的终结符。
+// 移进/归约冲突中，向前看符号和产生式都有优先级时按优先级和结合性消解，不再作为冲突报告
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Assoc {
+  Left,
+  Right,
+  NonAssoc,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Precedence {
+  pub level: usize,
+  pub assoc: Assoc,
+}
+
+impl Assoc {
+  pub(crate) fn from_directive(directive: &str) -> Option<Self> {
+    match directive {
+      "%left" => Some(Assoc::Left),
+      "%right" => Some(Assoc::Right),
+      "%nonassoc" => Some(Assoc::NonAssoc),
+      _ => None,
+    }
+  }
+}
+
+// 按优先级消解移进/归约冲突的结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Resolution {
+  Shift,
+  Reduce,
+  // %nonassoc 的运算符连用是语法错误，分析表中不填动作
+  Error,
+}
+
+impl Grammar {
+  pub fn token_precedence(&self, token: &str) -> Option<Precedence> {
+    self.precedence.get(token).copied()
+  }
+
+  pub fn production_precedence(&self, head: &str, body: &[String]) -> Option<Precedence> {
+    let key = (head.to_string(), body.to_vec());
+    match self.prec_tokens.get(&key) {
+      Some(token) => self.token_precedence(token),
+      None => body
+        .iter()
+        .rev()
+        .find(|symbol| self.is_terminal(symbol) && self.precedence.contains_key(*symbol))
+        .and_then(|symbol| self.token_precedence(symbol)),
+    }
+  }
+
+  // 向前看符号或产生式没有优先级时返回None，按默认策略处理
+  pub(crate) fn resolve_by_precedence(&self, lookahead: &str, head: &str, body: &[String]) -> Option<Resolution> {
+    let token = self.token_precedence(lookahead)?;
+    let production = self.production_precedence(head, body)?;
+    let resolution = if production.level > token.level {
+      Resolution::Reduce
+    } else if production.level < token.level {
+      Resolution::Shift
+    } else {
+      match token.assoc {
+        Assoc::Left => Resolution::Reduce,
+        Assoc::Right => Resolution::Shift,
+        Assoc::NonAssoc => Resolution::Error,
+      }
+    };
+    Some(resolution)
+  }
+}
@@ -0,0 +1,150 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::parser::grammar::Grammar;
+use crate::parser::{END_SYMBOL, EMPTY_SYMBOL};
+
+// 可空、FIRST和FOLLOW集合，都用不动点迭代计算：反复扫描全部产生式，直到集合不再变化，
+// 因此左递归和相互递归的文法也能得到正确的结果。
+// 与教材一致，FIRST集合中的 ε 表示可以推出空串；FOLLOW集合不含 ε，开始符号的FOLLOW含 #。
+// 没有定义的符号按终结符处理
+
+impl Grammar {
+  pub fn is_terminal(&self, symbol: &str) -> bool {
+    !self.pro_list.contains_key(symbol)
+  }
+
+  // ε 本身和能推出空串的非终结符
+  pub fn is_nullable(&self, symbol: &str) -> bool {
+    symbol == EMPTY_SYMBOL.get_value() || self.nullable.contains(symbol)
+  }
+
+  // 可空的非终结符
+  pub fn nullable_set(&self) -> &BTreeSet<String> {
+    &self.nullable
+  }
+
+  pub fn first_set(&self, symbol: &str) -> BTreeSet<String> {
+    match self.first_sets.get(symbol) {
+      Some(first_set) if !self.is_terminal(symbol) => first_set.clone(),
+      _ => BTreeSet::from([symbol.to_string()]),
+    }
+  }
+
+  // 符号串的FIRST集合，整个串可空时含 ε；空串的FIRST集合是 {ε}
+  pub fn first_of(&self, symbols: &[String]) -> BTreeSet<String> {
+    let empty_symbol = EMPTY_SYMBOL.get_value();
+    let mut result = BTreeSet::new();
+    for symbol in symbols {
+      result.extend(self.first_set(symbol).into_iter().filter(|s| s != empty_symbol));
+      if !self.is_nullable(symbol) {
+        return result;
+      }
+    }
+    result.insert(empty_symbol.clone());
+    result
+  }
+
+  pub fn follow_set(&self, symbol: &str) -> BTreeSet<String> {
+    self.follow_sets.get(symbol).cloned().unwrap_or_default()
+  }
+
+  pub(crate) fn calculate_sets(&mut self) {
+    self.nullable = self.calculate_nullable();
+    self.first_sets = self.calculate_first_sets();
+    self.follow_sets = self.calculate_follow_sets();
+  }
+
+  // 某个候选式的全部符号都可空时左部可空；空的候选式也算可空
+  fn calculate_nullable(&self) -> BTreeSet<String> {
+    let empty_symbol = EMPTY_SYMBOL.get_value();
+    let mut nullable = BTreeSet::new();
+    loop {
+      let mut changed = false;
+      for (head, body) in &self.pro_list {
+        if nullable.contains(head) {
+          continue;
+        }
+        let derives_empty = body
+          .iter()
+          .any(|item| item.iter().all(|symbol| symbol == empty_symbol || nullable.contains(symbol)));
+        if derives_empty {
+          nullable.insert(head.clone());
+          changed = true;
+        }
+      }
+      if !changed {
+        return nullable;
+      }
+    }
+  }
+
+  fn calculate_first_sets(&self) -> BTreeMap<String, BTreeSet<String>> {
+    let mut first_sets: BTreeMap<String, BTreeSet<String>> =
+      self.pro_list.keys().map(|head| (head.clone(), BTreeSet::new())).collect();
+    for head in &self.nullable {
+      first_sets.get_mut(head).unwrap().insert(EMPTY_SYMBOL.get_value().clone());
+    }
+    loop {
+      let mut changed = false;
+      for (head, body) in &self.pro_list {
+        let mut additions = BTreeSet::new();
+        for item in body {
+          for symbol in item {
+            if symbol == EMPTY_SYMBOL.get_value() {
+              continue;
+            }
+            match first_sets.get(symbol) {
+              Some(first_set) => additions.extend(first_set.iter().filter(|s| *s != EMPTY_SYMBOL.get_value()).cloned()),
+              None => {
+                additions.insert(symbol.clone());
+              }
+            }
+            if !self.nullable.contains(symbol) {
+              break;
+            }
+          }
+        }
+        let first_set = first_sets.get_mut(head).unwrap();
+        let count = first_set.len();
+        first_set.extend(additions);
+        changed |= first_set.len() != count;
+      }
+      if !changed {
+        return first_sets;
+      }
+    }
+  }
+
+  // 对每个 A → α B β：FIRST(β) - {ε} 加入FOLLOW(B)，β可空时FOLLOW(A)也加入FOLLOW(B)
+  fn calculate_follow_sets(&self) -> BTreeMap<String, BTreeSet<String>> {
+    let empty_symbol = EMPTY_SYMBOL.get_value();
+    let mut follow_sets: BTreeMap<String, BTreeSet<String>> =
+      self.pro_list.keys().map(|head| (head.clone(), BTreeSet::new())).collect();
+    if let Some(follow) = follow_sets.get_mut(&self.start_symbol) {
+      follow.insert(END_SYMBOL.get_value().clone());
+    }
+    loop {
+      let mut changed = false;
+      for (head, body) in &self.pro_list {
+        for item in body {
+          for (i, symbol) in item.iter().enumerate() {
+            if self.is_terminal(symbol) {
+              continue;
+            }
+            let mut additions = self.first_of(&item[i + 1..]);
+            if additions.remove(empty_symbol) {
+              additions.extend(follow_sets[head].iter().cloned());
+            }
+            let follow = follow_sets.get_mut(symbol).unwrap();
+            let count = follow.len();
+            follow.extend(additions);
+            changed |= follow.len() != count;
+          }
+        }
+      }
+      if !changed {
+        return follow_sets;
+      }
+    }
+  }
+}
@@ -0,0 +1,197 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Display;
+
+use crate::parser::ebnf::Desugar;
+use crate::parser::grammar::Grammar;
+use crate::parser::EMPTY_SYMBOL;
+
+// 文法的检查：在构造分析表之前发现文法文件中的问题，每个问题带文法文件中的行号。
+// 未定义的符号、重复的左部、缺少开始产生式和不能推出终结符串的非终结符是错误，
+// 不可达的非终结符和没有用到的终结符是警告
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IssueKind {
+  // 既不是终结符也不是任何产生式的左部
+  UndefinedSymbol(String),
+  // 同一左部再次出现，之前的产生式被覆盖
+  DuplicateHead { head: String, first_line: usize },
+  // 从开始符号推导不到
+  Unreachable(String),
+  // 推导不出只含终结符的串
+  Nonproductive(String),
+  UnusedToken(String),
+  MissingStart(String),
+  // 无法识别的行，或空的候选式
+  Malformed(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GrammarIssue {
+  pub kind: IssueKind,
+  pub line: usize,
+}
+
+impl GrammarIssue {
+  pub fn is_error(&self) -> bool {
+    !matches!(self.kind, IssueKind::Unreachable(_) | IssueKind::UnusedToken(_))
+  }
+}
+
+impl Display for IssueKind {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      IssueKind::UndefinedSymbol(symbol) => write!(f, "symbol `{}` is neither a token nor defined by a production", symbol),
+      IssueKind::DuplicateHead { head, first_line } => write!(
+        f,
+        "productions for `{}` are defined again and replace those on line {}",
+        head, first_line
+      ),
+      IssueKind::Unreachable(head) => write!(f, "nonterminal `{}` is unreachable from the start symbol", head),
+      IssueKind::Nonproductive(head) => write!(f, "nonterminal `{}` cannot derive any string of tokens", head),
+      IssueKind::UnusedToken(token) => write!(f, "token `{}` is never used", token),
+      IssueKind::MissingStart(start) => write!(f, "missing a production for the start symbol `{}`", start),
+      IssueKind::Malformed(message) => write!(f, "{}", message),
+    }
+  }
+}
+
+impl Display for GrammarIssue {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "line {}: {}", self.line, self.kind)
+  }
+}
+
+impl Grammar {
+  // 按行号排序的全部问题
+  pub fn validate(&self) -> Vec<GrammarIssue> {
+    let mut issues = Vec::new();
+    let is_token = |symbol: &str| self.token_list.iter().any(|token| token == symbol);
+    let empty_symbol = EMPTY_SYMBOL.get_value().to_string();
+
+    for (line, message) in &self.syntax_errors {
+      issues.push(GrammarIssue {
+        kind: IssueKind::Malformed(message.clone()),
+        line: *line,
+      });
+    }
+
+    // 每个左部第一次出现的行号；EBNF改写产生的非终结符随所在的产生式报告，不单独列出
+    let mut first_lines: BTreeMap<&str, usize> = BTreeMap::new();
+    for rule in &self.rules {
+      match first_lines.get(rule.head.as_str()) {
+        Some(&first_line) => issues.push(GrammarIssue {
+          kind: IssueKind::DuplicateHead {
+            head: rule.head.clone(),
+            first_line,
+          },
+          line: rule.line,
+        }),
+        None if Desugar::is_generated(&rule.head) => {}
+        None => {
+          first_lines.insert(&rule.head, rule.line);
+        }
+      }
+      let mut undefined = BTreeSet::new();
+      for item in &rule.body {
+        if item.is_empty() {
+          issues.push(GrammarIssue {
+            kind: IssueKind::Malformed(format!("empty alternative for `{}`, write `{}` instead", rule.head, empty_symbol)),
+            line: rule.line,
+          });
+        }
+        for symbol in item {
+          if !is_token(symbol) && !self.pro_list.contains_key(symbol) && undefined.insert(symbol) {
+            issues.push(GrammarIssue {
+              kind: IssueKind::UndefinedSymbol(symbol.clone()),
+              line: rule.line,
+            });
+          }
+        }
+      }
+    }
+
+    let start = &self.start_symbol;
+    match first_lines.get(start.as_str()) {
+      None => issues.push(GrammarIssue {
+        kind: IssueKind::MissingStart(start.clone()),
+        line: self.rules.first().map_or(1, |rule| rule.line),
+      }),
+      Some(_) => {
+        let reachable = self.reachable();
+        for (head, &line) in &first_lines {
+          if !reachable.contains(*head) {
+            issues.push(GrammarIssue {
+              kind: IssueKind::Unreachable(head.to_string()),
+              line,
+            });
+          }
+        }
+      }
+    }
+
+    let productive = self.productive();
+    for (head, &line) in &first_lines {
+      if !productive.contains(*head) {
+        issues.push(GrammarIssue {
+          kind: IssueKind::Nonproductive(head.to_string()),
+          line,
+        });
+      }
+    }
+
+    // 只在 %prec 中出现的终结符也算用到
+    let used: BTreeSet<&String> = self
+      .rules
+      .iter()
+      .flat_map(|rule| rule.body.iter().flatten())
+      .chain(self.prec_tokens.values())
+      .collect();
+    for token in &self.token_list {
+      if !used.contains(token) {
+        issues.push(GrammarIssue {
+          kind: IssueKind::UnusedToken(token.clone()),
+          line: self.token_lines.get(token).copied().unwrap_or(1),
+        });
+      }
+    }
+
+    issues.sort_by_key(|issue| issue.line);
+    issues
+  }
+
+  // 从开始符号可以到达的非终结符
+  fn reachable(&self) -> BTreeSet<&str> {
+    let mut reachable = BTreeSet::from([self.start_symbol.as_str()]);
+    let mut stack = vec![self.start_symbol.as_str()];
+    while let Some(head) = stack.pop() {
+      for symbol in self.pro_list.get(head).into_iter().flatten().flatten() {
+        if self.pro_list.contains_key(symbol) && reachable.insert(symbol) {
+          stack.push(symbol);
+        }
+      }
+    }
+    reachable
+  }
+
+  // 能推出终结符串的非终结符：反复加入某个候选式全由终结符和已知的非终结符组成的左部，直到不再变化
+  fn productive(&self) -> BTreeSet<&str> {
+    let mut productive = BTreeSet::new();
+    loop {
+      let count = productive.len();
+      for (head, body) in &self.pro_list {
+        let derives = body.iter().any(|item| {
+          !item.is_empty()
+            && item
+              .iter()
+              .all(|symbol| self.token_list.contains(symbol) || productive.contains(symbol.as_str()))
+        });
+        if derives {
+          productive.insert(head.as_str());
+        }
+      }
+      if productive.len() == count {
+        return productive;
+      }
+    }
+  }
+}
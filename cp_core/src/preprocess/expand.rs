@@ -0,0 +1,197 @@
+use crate::preprocess::{Macro, Preprocessor};
+
+// 宏展开：跳过注释、字符串和字符常量，展开其中的标识符。
+// 展开的结果再次扫描，正在展开的宏不再展开，避免无限递归
+
+impl Preprocessor {
+  pub(super) fn expand_line(&self, line: &str, line_no: usize, in_comment: &mut bool) -> Result<String, String> {
+    self.expand(line, line_no, &mut Vec::new(), in_comment)
+  }
+
+  fn expand(&self, text: &str, line_no: usize, disabled: &mut Vec<String>, in_comment: &mut bool) -> Result<String, String> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut out = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+      let ch = chars[i];
+      let next = chars.get(i + 1).copied();
+      if *in_comment {
+        if ch == '*' && next == Some('/') {
+          out.push_str("*/");
+          i += 2;
+          *in_comment = false;
+        } else {
+          out.push(ch);
+          i += 1;
+        }
+        continue;
+      }
+      match ch {
+        '/' if next == Some('/') => {
+          out.extend(&chars[i..]);
+          break;
+        }
+        '/' if next == Some('*') => {
+          out.push_str("/*");
+          i += 2;
+          *in_comment = true;
+        }
+        '"' | '\'' => {
+          let end = literal_end(&chars, i);
+          out.extend(&chars[i..end]);
+          i = end;
+        }
+        // 数中的字母（0x1F、1e5）不是标识符
+        ch if ch.is_ascii_digit() => {
+          let end = word_end(&chars, i, true);
+          out.extend(&chars[i..end]);
+          i = end;
+        }
+        ch if ch == '_' || ch.is_ascii_alphabetic() => {
+          let end = word_end(&chars, i, false);
+          let name: String = chars[i..end].iter().collect();
+          i = self.expand_ident(&name, &chars, end, line_no, disabled, &mut out)?;
+        }
+        _ => {
+          out.push(ch);
+          i += 1;
+        }
+      }
+    }
+    Ok(out)
+  }
+
+  // 标识符后面从i开始是剩余的输入，返回展开后继续读取的位置
+  fn expand_ident(
+    &self,
+    name: &str,
+    chars: &[char],
+    i: usize,
+    line_no: usize,
+    disabled: &mut Vec<String>,
+    out: &mut String,
+  ) -> Result<usize, String> {
+    if name == "__LINE__" {
+      out.push_str(&line_no.to_string());
+      return Ok(i);
+    }
+    let definition = match self.macros.get(name) {
+      Some(definition) if !disabled.iter().any(|d| d == name) => definition,
+      _ => {
+        out.push_str(name);
+        return Ok(i);
+      }
+    };
+
+    let (body, end) = match definition {
+      Macro::Object(body) => (body.clone(), i),
+      Macro::Function { params, body } => {
+        // 函数式宏只有后面跟着括号时才展开
+        let open = (i..chars.len()).find(|&j| !chars[j].is_whitespace());
+        let Some(open) = open.filter(|&j| chars[j] == '(') else {
+          out.push_str(name);
+          return Ok(i);
+        };
+        let (mut args, end) = arguments(chars, open)?;
+        if params.is_empty() && args.len() == 1 && args[0].trim().is_empty() {
+          args.clear();
+        }
+        if args.len() != params.len() {
+          return Err(format!("macro `{}` expects {} arguments, found {}", name, params.len(), args.len()));
+        }
+        // 实参先展开，再代入宏体
+        let mut expanded = Vec::new();
+        for arg in &args {
+          expanded.push(self.expand(arg.trim(), line_no, disabled, &mut false)?);
+        }
+        (substitute(body, params, &expanded), end)
+      }
+    };
+    disabled.push(name.to_string());
+    let result = self.expand(&body, line_no, disabled, &mut false);
+    disabled.pop();
+    out.push_str(&result?);
+    Ok(end)
+  }
+}
+
+// 字符串或字符常量的结束位置（不含），没有闭合时到行尾
+fn literal_end(chars: &[char], start: usize) -> usize {
+  let quote = chars[start];
+  let mut i = start + 1;
+  while i < chars.len() {
+    match chars[i] {
+      '\\' => i += 2,
+      ch if ch == quote => return i + 1,
+      _ => i += 1,
+    }
+  }
+  chars.len()
+}
+
+// 标识符或数的结束位置；数中还可以有小数点
+fn word_end(chars: &[char], start: usize, number: bool) -> usize {
+  (start..chars.len())
+    .find(|&i| {
+      let ch = chars[i];
+      !(ch == '_' || ch.is_ascii_alphanumeric() || (number && ch == '.'))
+    })
+    .unwrap_or(chars.len())
+}
+
+// 按顶层的逗号分隔实参，返回实参和右括号之后的位置
+fn arguments(chars: &[char], open: usize) -> Result<(Vec<String>, usize), String> {
+  let mut args = vec![String::new()];
+  let mut depth = 0;
+  let mut i = open + 1;
+  while i < chars.len() {
+    let ch = chars[i];
+    match ch {
+      '"' | '\'' => {
+        let end = literal_end(chars, i);
+        args.last_mut().unwrap().extend(&chars[i..end]);
+        i = end;
+        continue;
+      }
+      ')' if depth == 0 => return Ok((args, i + 1)),
+      ',' if depth == 0 => args.push(String::new()),
+      _ => {
+        match ch {
+          '(' => depth += 1,
+          ')' => depth -= 1,
+          _ => {}
+        }
+        args.last_mut().unwrap().push(ch);
+      }
+    }
+    i += 1;
+  }
+  Err("unterminated macro arguments".to_string())
+}
+
+// 把宏体中的形参替换为实参
+fn substitute(body: &str, params: &[String], args: &[String]) -> String {
+  let chars: Vec<char> = body.chars().collect();
+  let mut out = String::new();
+  let mut i = 0;
+  while i < chars.len() {
+    let ch = chars[i];
+    if ch == '"' || ch == '\'' {
+      let end = literal_end(&chars, i);
+      out.extend(&chars[i..end]);
+      i = end;
+    } else if ch == '_' || ch.is_ascii_alphanumeric() {
+      let end = word_end(&chars, i, ch.is_ascii_digit());
+      let word: String = chars[i..end].iter().collect();
+      match params.iter().position(|param| *param == word) {
+        Some(index) => out.push_str(&args[index]),
+        None => out.push_str(&word),
+      }
+      i = end;
+    } else {
+      out.push(ch);
+      i += 1;
+    }
+  }
+  out
+}
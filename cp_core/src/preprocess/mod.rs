@@ -0,0 +1,400 @@
+mod expand;
+
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// 在词法分析之前运行的预处理：#include、#define（对象式和函数式）、#undef、
+// #ifdef/#ifndef/#else/#endif，以及__LINE__。
+// 宏展开的结果留在原来的行内，指令行输出为空行，因此每个输出行都能对应回原文件的某一行
+
+// 运行库的计时函数带有调用处的行号
+const SYLIB_H: &str = "\
+#define starttime() _sysy_starttime(__LINE__)
+#define stoptime() _sysy_stoptime(__LINE__)
+";
+
+// 在搜索路径中找不到时使用的内置头文件
+const BUILTIN_HEADERS: &[(&str, &str)] = &[("sylib.h", SYLIB_H)];
+
+// 嵌套包含的最大深度，超过时多半是循环包含
+const MAX_INCLUDE_DEPTH: usize = 64;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Macro {
+  Object(String),
+  Function { params: Vec<String>, body: String },
+}
+
+// 输出行对应的原文件和行号（从1开始）
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Origin {
+  pub file: String,
+  pub line: usize,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LineMap {
+  origins: Vec<Origin>,
+}
+
+impl LineMap {
+  // 输出中第row行（从1开始）的来源
+  pub fn origin(&self, row: usize) -> Option<&Origin> {
+    self.origins.get(row.checked_sub(1)?)
+  }
+
+  pub fn len(&self) -> usize {
+    self.origins.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.origins.is_empty()
+  }
+
+  // 诊断信息中的位置：原文件名、行号和列号；宏展开后的列号是展开结果中的列号
+  pub fn locate(&self, row: usize, col: usize) -> String {
+    match self.origin(row) {
+      Some(origin) => format!("{}:{}:{}", origin.file, origin.line, col),
+      None => format!("?:{}:{}", row, col),
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Preprocessed {
+  pub text: String,
+  pub line_map: LineMap,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PreprocessError {
+  pub file: String,
+  pub line: usize,
+  pub message: String,
+}
+
+impl Display for PreprocessError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{}:{}: error: {}", self.file, self.line, self.message)
+  }
+}
+
+// 条件编译的一层：active为当前分支是否输出，parent_active为外层是否输出
+struct Condition {
+  active: bool,
+  parent_active: bool,
+  seen_else: bool,
+  line: usize,
+}
+
+pub struct Preprocessor {
+  include_dirs: Vec<PathBuf>,
+  macros: BTreeMap<String, Macro>,
+}
+
+impl Default for Preprocessor {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl Preprocessor {
+  // 预先定义运行库的计时宏，不包含sylib.h也可以使用
+  pub fn new() -> Self {
+    let mut preprocessor = Self {
+      include_dirs: Vec::new(),
+      macros: BTreeMap::new(),
+    };
+    for line in SYLIB_H.lines() {
+      let definition = line.strip_prefix("#define ").unwrap();
+      preprocessor.define_line(definition).unwrap();
+    }
+    preprocessor
+  }
+
+  pub fn add_include_dir(&mut self, dir: impl Into<PathBuf>) {
+    self.include_dirs.push(dir.into());
+  }
+
+  // 相当于 #define name body
+  pub fn define(&mut self, name: &str, body: &str) {
+    self.macros.insert(name.to_string(), Macro::Object(body.to_string()));
+  }
+
+  pub fn get_macro(&self, name: &str) -> Option<&Macro> {
+    self.macros.get(name)
+  }
+
+  pub fn process_file(&mut self, path: &Path) -> Result<Preprocessed, PreprocessError> {
+    let name = path.display().to_string();
+    let text = fs::read_to_string(path).map_err(|e| PreprocessError {
+      file: name.clone(),
+      line: 0,
+      message: format!("cannot read file: {}", e),
+    })?;
+    self.process(&name, &text)
+  }
+
+  // name用于诊断信息和查找 #include "..." 的相对路径
+  pub fn process(&mut self, name: &str, text: &str) -> Result<Preprocessed, PreprocessError> {
+    let mut output = Preprocessed {
+      text: String::new(),
+      line_map: LineMap::default(),
+    };
+    self.process_text(name, text, 0, &mut output)?;
+    Ok(output)
+  }
+
+  fn process_text(&mut self, name: &str, text: &str, depth: usize, output: &mut Preprocessed) -> Result<(), PreprocessError> {
+    let mut conditions: Vec<Condition> = Vec::new();
+    // 跨行的块注释中的#不是指令
+    let mut in_comment = false;
+    for (index, line) in text.lines().enumerate() {
+      let line_no = index + 1;
+      let error = |message: String| PreprocessError {
+        file: name.to_string(),
+        line: line_no,
+        message,
+      };
+      let active = conditions.last().is_none_or(|c| c.active);
+      let trimmed = line.trim_start();
+
+      let mut emitted = String::new();
+      if let (false, Some(directive)) = (in_comment, trimmed.strip_prefix('#')) {
+        let directive = directive.trim();
+        let (keyword, rest) = directive
+          .split_once(|ch: char| ch.is_whitespace())
+          .map_or((directive, ""), |(keyword, rest)| (keyword, rest.trim()));
+        match keyword {
+          "ifdef" | "ifndef" => {
+            let name = identifier(rest).ok_or_else(|| error(format!("#{} expects a macro name", keyword)))?;
+            let defined = self.macros.contains_key(name);
+            conditions.push(Condition {
+              active: active && (defined == (keyword == "ifdef")),
+              parent_active: active,
+              seen_else: false,
+              line: line_no,
+            });
+          }
+          "else" => {
+            let condition = conditions.last_mut().ok_or_else(|| error("#else without #ifdef".to_string()))?;
+            if condition.seen_else {
+              return Err(error("duplicate #else".to_string()));
+            }
+            condition.seen_else = true;
+            condition.active = condition.parent_active && !condition.active;
+          }
+          "endif" => {
+            conditions.pop().ok_or_else(|| error("#endif without #ifdef".to_string()))?;
+          }
+          _ if !active => {}
+          "define" => self.define_line(rest).map_err(error)?,
+          "undef" => {
+            let name = identifier(rest).ok_or_else(|| error("#undef expects a macro name".to_string()))?;
+            self.macros.remove(name);
+          }
+          "include" => {
+            // 包含的文件整体插入到这一行的位置，指令行本身不输出
+            let (path, included) = self.find_include(name, rest).map_err(error)?;
+            if depth >= MAX_INCLUDE_DEPTH {
+              return Err(error(format!("#include nested too deeply in `{}`", path)));
+            }
+            self.process_text(&path, &included, depth + 1, output)?;
+            continue;
+          }
+          _ => return Err(error(format!("unknown directive `#{}`", keyword))),
+        }
+      } else if active {
+        emitted = self.expand_line(line, line_no, &mut in_comment).map_err(error)?;
+      }
+
+      output.text.push_str(&emitted);
+      output.text.push('\n');
+      output.line_map.origins.push(Origin {
+        file: name.to_string(),
+        line: line_no,
+      });
+    }
+
+    match conditions.last() {
+      Some(condition) => Err(PreprocessError {
+        file: name.to_string(),
+        line: condition.line,
+        message: "unterminated conditional directive".to_string(),
+      }),
+      None => Ok(()),
+    }
+  }
+
+  // NAME body 或 NAME(a, b) body，名字和左括号之间没有空白时是函数式宏
+  fn define_line(&mut self, rest: &str) -> Result<(), String> {
+    let name = identifier(rest).ok_or_else(|| "#define expects a macro name".to_string())?;
+    let after = &rest[name.len()..];
+    let definition = match after.strip_prefix('(') {
+      Some(params) => {
+        let (params, body) = params
+          .split_once(')')
+          .ok_or_else(|| format!("missing ')' in parameters of `{}`", name))?;
+        let params: Vec<String> = if params.trim().is_empty() {
+          Vec::new()
+        } else {
+          params.split(',').map(|param| param.trim().to_string()).collect()
+        };
+        if let Some(param) = params.iter().find(|param| identifier(param) != Some(param.as_str())) {
+          return Err(format!("invalid parameter `{}` of `{}`", param, name));
+        }
+        Macro::Function {
+          params,
+          body: body.trim().to_string(),
+        }
+      }
+      None => Macro::Object(after.trim().to_string()),
+    };
+    self.macros.insert(name.to_string(), definition);
+    Ok(())
+  }
+
+  // "file" 先在当前文件所在目录中查找，<file> 只在搜索路径中查找，最后查找内置头文件
+  fn find_include(&self, current: &str, rest: &str) -> Result<(String, String), String> {
+    let (file, local) = if let Some(file) = rest.strip_prefix('"').and_then(|r| r.split_once('"')) {
+      (file.0, true)
+    } else if let Some(file) = rest.strip_prefix('<').and_then(|r| r.split_once('>')) {
+      (file.0, false)
+    } else {
+      return Err("#include expects \"file\" or <file>".to_string());
+    };
+
+    let current_dir = Path::new(current).parent().map(Path::to_path_buf);
+    let local_dir = current_dir.filter(|_| local);
+    for dir in local_dir.iter().chain(&self.include_dirs) {
+      let path = dir.join(file);
+      if let Ok(text) = fs::read_to_string(&path) {
+        return Ok((path.display().to_string(), text));
+      }
+    }
+    match BUILTIN_HEADERS.iter().find(|(name, _)| *name == file) {
+      Some((name, text)) => Ok((format!("<{}>", name), text.to_string())),
+      None => Err(format!("cannot find include file `{}`", file)),
+    }
+  }
+}
+
+// 开头的标识符
+fn identifier(text: &str) -> Option<&str> {
+  let text = text.trim_start();
+  let end = text
+    .char_indices()
+    .find(|&(i, ch)| !(ch == '_' || ch.is_ascii_alphabetic() || (i > 0 && ch.is_ascii_digit())))
+    .map_or(text.len(), |(i, _)| i);
+  (end > 0).then(|| &text[..end])
+}
+
+// 单元测试
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn run(text: &str) -> Result<Preprocessed, PreprocessError> {
+    Preprocessor::new().process("main.sy", text)
+  }
+
+  #[test]
+  fn test_macros() {
+    let source = "\
+#define N 10
+#define MAX(a, b) ((a) > (b) ? (a) : (b))
+#define AREA N * N
+int a[N]; // N
+int b = MAX(N, f(1, 2)) + AREA;
+char *s = \"N\";
+/* N
+   N */ int c = N;
+int line = __LINE__;
+";
+    let output = run(source).unwrap();
+    let lines: Vec<_> = output.text.lines().collect();
+    assert_eq!(lines[..3], ["", "", ""]);
+    assert_eq!(lines[3], "int a[10]; // N");
+    assert_eq!(lines[4], "int b = ((10) > (f(1, 2)) ? (10) : (f(1, 2))) + 10 * 10;");
+    assert_eq!(lines[5], "char *s = \"N\";");
+    assert_eq!(lines[6..8], ["/* N", "   N */ int c = 10;"]);
+    assert_eq!(lines[8], "int line = 9;");
+    assert_eq!(output.line_map.len(), 9);
+
+    // 展开不会无限递归
+    let output = run("#define X X + 1\n#define F(x) F(x)\nX F(2)\n").unwrap();
+    assert_eq!(output.text.lines().last(), Some("X + 1 F(2)"));
+    // 函数式宏后面没有括号时不展开
+    let output = run("#define F(x) x\nint F;\n").unwrap();
+    assert_eq!(output.text.lines().last(), Some("int F;"));
+  }
+
+  #[test]
+  fn test_conditionals() {
+    let source = "\
+#define A
+#ifdef A
+a
+#ifndef B
+b
+#else
+not b
+#endif
+#else
+not a
+#endif
+#undef A
+#ifdef A
+hidden
+#endif
+";
+    let output = run(source).unwrap();
+    let lines: Vec<_> = output.text.lines().filter(|line| !line.is_empty()).collect();
+    assert_eq!(lines, ["a", "b"]);
+
+    assert_eq!(run("#ifdef A\n").unwrap_err().to_string(), "main.sy:1: error: unterminated conditional directive");
+    assert_eq!(run("\n#endif\n").unwrap_err().line, 2);
+    assert!(run("#pragma once\n").is_err());
+    // 不输出的分支中的未知指令不报错
+    assert!(run("#ifdef A\n#pragma once\n#endif\n").is_ok());
+  }
+
+  #[test]
+  fn test_include() {
+    let dir = std::env::temp_dir().join(format!("rcp_preprocess_{}", std::process::id()));
+    fs::create_dir_all(dir.join("inc")).unwrap();
+    fs::write(dir.join("local.h"), "int local;\n#define K 3\n").unwrap();
+    fs::write(dir.join("inc/lib.h"), "#ifndef LIB_H\n#define LIB_H\nint lib;\n#endif\n").unwrap();
+    fs::write(dir.join("main.sy"), "#include \"local.h\"\n#include <lib.h>\n#include <lib.h>\nint x = K;\n").unwrap();
+
+    let mut preprocessor = Preprocessor::new();
+    preprocessor.add_include_dir(dir.join("inc"));
+    let output = preprocessor.process_file(&dir.join("main.sy")).unwrap();
+    let lines: Vec<_> = output.text.lines().collect();
+    assert_eq!(lines, ["int local;", "", "", "", "int lib;", "", "", "", "", "", "int x = 3;"]);
+    // 每个输出行都对应回所在的文件和行
+    let origin = output.line_map.origin(5).unwrap();
+    assert!(origin.file.ends_with("lib.h") && origin.line == 3, "{:?}", origin);
+    let origin = output.line_map.origin(11).unwrap();
+    assert!(origin.file.ends_with("main.sy") && origin.line == 4, "{:?}", origin);
+    assert!(output.line_map.locate(1, 5).ends_with("local.h:1:5"));
+
+    let err = Preprocessor::new().process("main.sy", "#include <missing.h>\n").unwrap_err();
+    assert_eq!(err.message, "cannot find include file `missing.h`");
+    fs::write(dir.join("loop.h"), "#include \"loop.h\"\n").unwrap();
+    assert!(Preprocessor::new().process_file(&dir.join("loop.h")).is_err());
+    fs::remove_dir_all(&dir).unwrap();
+  }
+
+  #[test]
+  fn test_timing_macros() {
+    let source = "#include \"sylib.h\"\nint main() {\n  starttime();\n  stoptime();\n  return 0;\n}\n";
+    let output = run(source).unwrap();
+    assert!(output.text.contains("  _sysy_starttime(3);\n  _sysy_stoptime(4);\n"));
+    // 内置的sylib.h
+    assert_eq!(output.line_map.origin(1).unwrap().file, "<sylib.h>");
+    assert_eq!(output.line_map.origin(3).unwrap().line, 2);
+  }
+}
@@ -1,40 +1,15 @@
-use std::fmt::Display;
-
 use logos::Logos;
 
-#[derive(Debug)]
-pub struct Token {
-    row: usize,
-    column: usize,
-    token_kind: TokenKind,
-    value: String,
-}
-
-impl Token {
-    fn new(row: usize, column: usize, token_kind: TokenKind, value: String) -> Self {
-        Self {
-            row,
-            column,
-            token_kind,
-            value,
-        }
-    }
-}
-
-impl Display for Token {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "{}@#{}@#{}@#{:?}",
-            self.row, self.column, self.value, self.token_kind
-        )
-    }
-}
+use crate::error::{LexerError, LexerErrorKind};
+use crate::lexer::parse_number;
+use crate::source::SourceFile;
+use crate::token::{KeyWords, Operators, Span, Token, TokenKind, TokenType};
 
+// 基于logos的词法分析器，产生的词素与Cursor相同，可以互换作为语法分析的输入
 pub struct ReLexer<'a> {
     source_code: &'a str,
     pub tokens: Vec<Token>,
-    pub errors: Vec<Token>,
+    pub errors: Vec<LexerError>,
 }
 
 impl<'a> ReLexer<'a> {
@@ -42,418 +17,123 @@ impl<'a> ReLexer<'a> {
         Self {
             source_code,
             tokens: Vec::<Token>::new(),
-            errors: Vec::<Token>::new(),
+            errors: Vec::<LexerError>::new(),
         }
     }
 
+    // 读取全部词素，包括空白、注释和Err词素
     pub fn generate_token(&mut self) {
-        let mut row = 1;
-        let mut column = 1;
-        let mut lex = TokenKind::lexer(self.source_code);
-        while let Some(token) = lex.next() {
-            match token {
-                TokenKind::Comment => {
-                    // NOTE:忽略注释
-                    // self.tokens.push(Token::new(
-                    //     row.clone(),
-                    //     column.clone(),
-                    //     TokenKind::Comment,
-                    //     lex.slice().to_string(),
-                    // ));
-                    column += lex.span().end - lex.span().start;
-                }
-                TokenKind::Delimiter => {
-                    self.tokens.push(Token::new(
-                        row.clone(),
-                        column.clone(),
-                        TokenKind::Delimiter,
-                        lex.slice().to_string(),
-                    ));
-                    column += lex.span().end - lex.span().start;
-                }
-                TokenKind::IntegerNumber => {
-                    self.tokens.push(Token::new(
-                        row.clone(),
-                        column.clone(),
-                        TokenKind::IntegerNumber,
-                        lex.slice().to_string(),
-                    ));
-                    column += lex.span().end - lex.span().start;
-                }
-                TokenKind::Zero => {
-                    self.tokens.push(Token::new(
-                        row.clone(),
-                        column.clone(),
-                        TokenKind::IntegerNumber,
-                        lex.slice().to_string(),
-                    ));
-                    column += lex.span().end - lex.span().start;
-                }
-                TokenKind::FloatNumber => {
-                    self.tokens.push(Token::new(
-                        row.clone(),
-                        column.clone(),
-                        TokenKind::FloatNumber,
-                        lex.slice().to_string(),
-                    ));
-                    column += lex.span().end - lex.span().start;
-                }
-                TokenKind::ExponentNumber => {
-                    self.tokens.push(Token::new(
-                        row.clone(),
-                        column.clone(),
-                        TokenKind::ExponentNumber,
-                        lex.slice().to_string(),
-                    ));
-                    column += lex.span().end - lex.span().start;
-                }
-                TokenKind::HexNumber => {
-                    self.tokens.push(Token::new(
-                        row.clone(),
-                        column.clone(),
-                        TokenKind::HexNumber,
-                        lex.slice().to_string(),
-                    ));
-                    column += lex.span().end - lex.span().start;
-                }
-                TokenKind::OctNumber => {
-                    self.tokens.push(Token::new(
-                        row.clone(),
-                        column.clone(),
-                        TokenKind::OctNumber,
-                        lex.slice().to_string(),
-                    ));
-                    column += lex.span().end - lex.span().start;
-                }
-                TokenKind::Identifier => {
-                    self.tokens.push(Token::new(
-                        row.clone(),
-                        column.clone(),
-                        TokenKind::Identifier,
-                        lex.slice().to_string(),
-                    ));
-                    column += lex.span().end - lex.span().start;
-                }
-                TokenKind::Char => {
-                    self.tokens.push(Token::new(
-                        row.clone(),
-                        column.clone(),
-                        TokenKind::Char,
-                        lex.slice().to_string(),
-                    ));
-                    column += lex.span().end - lex.span().start;
-                }
-                TokenKind::Int => {
-                    self.tokens.push(Token::new(
-                        row.clone(),
-                        column.clone(),
-                        TokenKind::Int,
-                        lex.slice().to_string(),
-                    ));
-                    column += lex.span().end - lex.span().start;
-                }
-                TokenKind::Float => {
-                    self.tokens.push(Token::new(
-                        row.clone(),
-                        column.clone(),
-                        TokenKind::Float,
-                        lex.slice().to_string(),
-                    ));
-                    column += lex.span().end - lex.span().start;
-                }
-                TokenKind::Break => {
-                    self.tokens.push(Token::new(
-                        row.clone(),
-                        column.clone(),
-                        TokenKind::Float,
-                        lex.slice().to_string(),
-                    ));
-                    column += lex.span().end - lex.span().start;
-                }
-                TokenKind::Const => {
-                    self.tokens.push(Token::new(
-                        row.clone(),
-                        column.clone(),
-                        TokenKind::Const,
-                        lex.slice().to_string(),
-                    ));
-                    column += lex.span().end - lex.span().start;
-                }
-                TokenKind::Return => {
-                    self.tokens.push(Token::new(
-                        row.clone(),
-                        column.clone(),
-                        TokenKind::Return,
-                        lex.slice().to_string(),
-                    ));
-                    column += lex.span().end - lex.span().start;
-                }
-                TokenKind::Void => {
-                    self.tokens.push(Token::new(
-                        row.clone(),
-                        column.clone(),
-                        TokenKind::Void,
-                        lex.slice().to_string(),
-                    ));
-                    column += lex.span().end - lex.span().start;
-                }
-                TokenKind::Continue => {
-                    self.tokens.push(Token::new(
-                        row.clone(),
-                        column.clone(),
-                        TokenKind::Continue,
-                        lex.slice().to_string(),
-                    ));
-                    column += lex.span().end - lex.span().start;
-                }
-                TokenKind::While => {
-                    self.tokens.push(Token::new(
-                        row.clone(),
-                        column.clone(),
-                        TokenKind::While,
-                        lex.slice().to_string(),
-                    ));
-                    column += lex.span().end - lex.span().start;
-                }
-                TokenKind::If => {
-                    self.tokens.push(Token::new(
-                        row.clone(),
-                        column.clone(),
-                        TokenKind::If,
-                        lex.slice().to_string(),
-                    ));
-                    column += lex.span().end - lex.span().start;
-                }
-                TokenKind::Else => {
-                    self.tokens.push(Token::new(
-                        row.clone(),
-                        column.clone(),
-                        TokenKind::Else,
-                        lex.slice().to_string(),
-                    ));
-                    column += lex.span().end - lex.span().start;
-                }
-                TokenKind::For => {
-                    self.tokens.push(Token::new(
-                        row.clone(),
-                        column.clone(),
-                        TokenKind::For,
-                        lex.slice().to_string(),
-                    ));
-                    column += lex.span().end - lex.span().start;
-                }
-                TokenKind::Character => {
-                    self.tokens.push(Token::new(
-                        row.clone(),
-                        column.clone(),
-                        TokenKind::Character,
-                        lex.slice().to_string(),
-                    ));
-                    column += lex.span().end - lex.span().start;
-                }
-                TokenKind::String => {
-                    self.tokens.push(Token::new(
-                        row.clone(),
-                        column.clone(),
-                        TokenKind::String,
-                        lex.slice().to_string(),
-                    ));
-                    column += lex.span().end - lex.span().start;
-                }
-                TokenKind::Add => {
-                    self.tokens.push(Token::new(
-                        row.clone(),
-                        column.clone(),
-                        TokenKind::Add,
-                        lex.slice().to_string(),
-                    ));
-                    column += lex.span().end - lex.span().start;
-                }
-                TokenKind::Minus => {
-                    self.tokens.push(Token::new(
-                        row.clone(),
-                        column.clone(),
-                        TokenKind::Minus,
-                        lex.slice().to_string(),
-                    ));
-                    column += lex.span().end - lex.span().start;
-                }
-                TokenKind::Multiply => {
-                    self.tokens.push(Token::new(
-                        row.clone(),
-                        column.clone(),
-                        TokenKind::Multiply,
-                        lex.slice().to_string(),
-                    ));
-                    column += lex.span().end - lex.span().start;
-                }
-                TokenKind::Divison => {
-                    self.tokens.push(Token::new(
-                        row.clone(),
-                        column.clone(),
-                        TokenKind::Divison,
-                        lex.slice().to_string(),
-                    ));
-                    column += lex.span().end - lex.span().start;
-                }
-                TokenKind::Percent => {
-                    self.tokens.push(Token::new(
-                        row.clone(),
-                        column.clone(),
-                        TokenKind::Percent,
-                        lex.slice().to_string(),
-                    ));
-                    column += lex.span().end - lex.span().start;
-                }
-                TokenKind::DoubleEqual => {
-                    self.tokens.push(Token::new(
-                        row.clone(),
-                        column.clone(),
-                        TokenKind::DoubleEqual,
-                        lex.slice().to_string(),
-                    ));
-                    column += lex.span().end - lex.span().start;
-                }
-                TokenKind::NotEqual => {
-                    self.tokens.push(Token::new(
-                        row.clone(),
-                        column.clone(),
-                        TokenKind::NotEqual,
-                        lex.slice().to_string(),
-                    ));
-                    column += lex.span().end - lex.span().start;
-                }
-                TokenKind::Greater => {
-                    self.tokens.push(Token::new(
-                        row.clone(),
-                        column.clone(),
-                        TokenKind::Greater,
-                        lex.slice().to_string(),
-                    ));
-                    column += lex.span().end - lex.span().start;
-                }
-                TokenKind::Less => {
-                    self.tokens.push(Token::new(
-                        row.clone(),
-                        column.clone(),
-                        TokenKind::Less,
-                        lex.slice().to_string(),
-                    ));
-                    column += lex.span().end - lex.span().start;
-                }
-                TokenKind::GreaterEqual => {
-                    self.tokens.push(Token::new(
-                        row.clone(),
-                        column.clone(),
-                        TokenKind::GreaterEqual,
-                        lex.slice().to_string(),
-                    ));
-                    column += lex.span().end - lex.span().start;
-                }
-                TokenKind::LessEqual => {
-                    self.tokens.push(Token::new(
-                        row.clone(),
-                        column.clone(),
-                        TokenKind::LessEqual,
-                        lex.slice().to_string(),
-                    ));
-                    column += lex.span().end - lex.span().start;
-                }
-                TokenKind::LogicNot => {
-                    self.tokens.push(Token::new(
-                        row.clone(),
-                        column.clone(),
-                        TokenKind::LogicNot,
-                        lex.slice().to_string(),
-                    ));
-                    column += lex.span().end - lex.span().start;
-                }
-                TokenKind::LogicAnd => {
-                    self.tokens.push(Token::new(
-                        row.clone(),
-                        column.clone(),
-                        TokenKind::LogicAnd,
-                        lex.slice().to_string(),
-                    ));
-                    column += lex.span().end - lex.span().start;
-                }
-                TokenKind::LogicOr => {
-                    self.tokens.push(Token::new(
-                        row.clone(),
-                        column.clone(),
-                        TokenKind::LogicOr,
-                        lex.slice().to_string(),
-                    ));
-                    column += lex.span().end - lex.span().start;
-                }
-                TokenKind::OpenParen => {
-                    self.tokens.push(Token::new(
-                        row.clone(),
-                        column.clone(),
-                        TokenKind::OpenParen,
-                        lex.slice().to_string(),
-                    ));
-                    column += lex.span().end - lex.span().start;
-                }
-                TokenKind::CloseParen => {
-                    self.tokens.push(Token::new(
-                        row.clone(),
-                        column.clone(),
-                        TokenKind::CloseParen,
-                        lex.slice().to_string(),
-                    ));
-                    column += lex.span().end - lex.span().start;
-                }
-                TokenKind::OpenBracket => {
-                    self.tokens.push(Token::new(
-                        row.clone(),
-                        column.clone(),
-                        TokenKind::OpenBracket,
-                        lex.slice().to_string(),
-                    ));
-                    column += lex.span().end - lex.span().start;
-                }
-                TokenKind::CloseBracket => {
-                    self.tokens.push(Token::new(
-                        row.clone(),
-                        column.clone(),
-                        TokenKind::CloseBracket,
-                        lex.slice().to_string(),
-                    ));
-                    column += lex.span().end - lex.span().start;
-                }
-                TokenKind::Equal => {
-                    self.tokens.push(Token::new(
-                        row.clone(),
-                        column.clone(),
-                        TokenKind::Equal,
-                        lex.slice().to_string(),
-                    ));
-                    column += lex.span().end - lex.span().start;
-                }
-                TokenKind::Error => {
-                    self.errors.push(Token::new(
-                        row.clone(),
-                        column.clone(),
-                        TokenKind::Error,
-                        lex.slice().to_string(),
-                    ));
-                    column += lex.span().end - lex.span().start;
-                }
-                TokenKind::NewLine => {
-                    row += 1;
-                    column = 1;
-                }
-                TokenKind::WhiteSpace => {
-                    column += lex.span().end - lex.span().start;
-                }
-            }
+        let mut errors = Vec::new();
+        self.tokens = Self::stream(self.source_code, &mut errors).collect();
+        self.errors = errors;
+    }
+
+    // 按需产生词素，诊断信息收集到errors中
+    pub fn stream<'e>(source_code: &'a str, errors: &'e mut Vec<LexerError>) -> ReLexerStream<'a, 'e> {
+        ReLexerStream {
+            lex: RawKind::lexer(source_code),
+            file: SourceFile::new("", source_code),
+            errors,
         }
     }
 }
 
+pub struct ReLexerStream<'a, 'e> {
+    lex: logos::Lexer<'a, RawKind>,
+    // 用于把字节偏移换算成行列号
+    file: SourceFile,
+    errors: &'e mut Vec<LexerError>,
+}
+
+impl Iterator for ReLexerStream<'_, '_> {
+    type Item = Token;
+
+    fn next(&mut self) -> Option<Token> {
+        let raw = self.lex.next()?;
+        let range = self.lex.span();
+        let span = self.file.span(range.start, range.end);
+        let slice = self.lex.slice();
+        let mut literal = None;
+        let kind = match raw {
+            RawKind::Comment => TokenKind::Comment,
+            RawKind::WhiteSpace | RawKind::NewLine => TokenKind::WhiteSpace,
+            RawKind::Delimiter => match slice {
+                "," => TokenKind::Comma,
+                ";" => TokenKind::Semicolon,
+                "{" => TokenKind::OpenBrace,
+                _ => TokenKind::CloseBrace,
+            },
+            RawKind::Identifier => TokenKind::Identifier,
+            RawKind::Char => TokenKind::KeyWord(KeyWords::Char),
+            RawKind::Int => TokenKind::KeyWord(KeyWords::Int),
+            RawKind::Float => TokenKind::KeyWord(KeyWords::Float),
+            RawKind::Break => TokenKind::KeyWord(KeyWords::Break),
+            RawKind::Const => TokenKind::KeyWord(KeyWords::Const),
+            RawKind::Return => TokenKind::KeyWord(KeyWords::Return),
+            RawKind::Void => TokenKind::KeyWord(KeyWords::Void),
+            RawKind::Continue => TokenKind::KeyWord(KeyWords::Continue),
+            RawKind::While => TokenKind::KeyWord(KeyWords::While),
+            RawKind::If => TokenKind::KeyWord(KeyWords::If),
+            RawKind::Else => TokenKind::KeyWord(KeyWords::Else),
+            RawKind::For => TokenKind::KeyWord(KeyWords::For),
+            // 与Cursor共用数值的解析，溢出时同样报错
+            RawKind::HexNumber
+            | RawKind::OctNumber
+            | RawKind::IntegerNumber
+            | RawKind::Zero
+            | RawKind::FloatNumber
+            | RawKind::ExponentNumber
+            | RawKind::HexFloatNumber => match parse_number(slice) {
+                Ok(value) => {
+                    literal = Some(value);
+                    TokenKind::Number(value.kind())
+                }
+                Err(kind) => self.error(kind, span),
+            },
+            RawKind::Character => TokenKind::Character,
+            RawKind::String => TokenKind::String,
+            RawKind::OpenParen => TokenKind::OpenParen,
+            RawKind::CloseParen => TokenKind::CloseParen,
+            RawKind::OpenBracket => TokenKind::Operator(Operators::OpenBracket),
+            RawKind::CloseBracket => TokenKind::Operator(Operators::CloseBracket),
+            RawKind::Equal => TokenKind::Operator(Operators::Eq),
+            RawKind::Add => TokenKind::Operator(Operators::Add),
+            RawKind::Minus => TokenKind::Operator(Operators::Sub),
+            RawKind::Multiply => TokenKind::Operator(Operators::Mul),
+            RawKind::Divison => TokenKind::Operator(Operators::Div),
+            RawKind::Percent => TokenKind::Operator(Operators::Mod),
+            RawKind::DoubleEqual => TokenKind::Operator(Operators::LEq),
+            RawKind::NotEqual => TokenKind::Operator(Operators::NotEq),
+            RawKind::Greater => TokenKind::Operator(Operators::Grt),
+            RawKind::Less => TokenKind::Operator(Operators::Les),
+            RawKind::GreaterEqual => TokenKind::Operator(Operators::GrtEq),
+            RawKind::LessEqual => TokenKind::Operator(Operators::LesEq),
+            RawKind::LogicNot => TokenKind::Operator(Operators::Not),
+            RawKind::LogicAnd => TokenKind::Operator(Operators::LAnd),
+            RawKind::LogicOr => TokenKind::Operator(Operators::LOr),
+            // logos不区分错误的种类
+            RawKind::Error => self.error(LexerErrorKind::UnknownCharacter, span),
+        };
+        let token = Token::new(TokenType::Terminal(kind), slice.to_string(), None).with_span(span);
+        Some(match literal {
+            Some(literal) => token.with_literal(literal),
+            None => token,
+        })
+    }
+}
+
+impl ReLexerStream<'_, '_> {
+    fn error(&mut self, kind: LexerErrorKind, span: Span) -> TokenKind {
+        let start = span.get_start_point();
+        let mut err = LexerError::new(kind, *start.get_row(), *start.get_col());
+        err.span = span;
+        self.errors.push(err);
+        TokenKind::Err
+    }
+}
+
 #[derive(Logos, Debug, PartialEq)]
-enum TokenKind {
+enum RawKind {
     #[regex(r#"(/\*[^*]*\*+([^/*][^*]*\*+)*/)|(//[^\n]*)"#)]
     Comment,
 
@@ -505,7 +185,7 @@ enum TokenKind {
     #[regex(r#"0[xX][0-9a-fA-F]+"#)]
     HexNumber,
 
-    #[regex("0[1-7]+")]
+    #[regex("0[0-7]+")]
     OctNumber,
 
     #[regex(r#"[1-9][0-9]*"#)]
@@ -514,12 +194,15 @@ enum TokenKind {
     #[regex(r#"0"#)]
     Zero,
 
-    #[regex(r#"[1-9]\d*\.\d*|0\.\d*[1-9]\d*"#)]
+    #[regex(r#"([0-9]*\.[0-9]+|[0-9]+\.)([eE][-+]?[0-9]+)?"#)]
     FloatNumber,
 
-    #[regex(r#"[0-9]*\.?[0-9]+([eE][-+]?[0-9]+)"#)]
+    #[regex(r#"[0-9]+[eE][-+]?[0-9]+"#)]
     ExponentNumber,
 
+    #[regex(r#"0[xX]([0-9a-fA-F]*\.[0-9a-fA-F]+|[0-9a-fA-F]+\.?)[pP][-+]?[0-9]+"#)]
+    HexFloatNumber,
+
     #[regex(r#"'[^']'"#)]
     Character,
 
@@ -0,0 +1,306 @@
+use crate::ast::*;
+use crate::semantic::{ErrorType, Param, SemanticError, Symbol, SymbolKind, SymbolTable};
+use crate::token::Span;
+
+pub struct Analyzer {
+  table: SymbolTable,
+  error_list: Vec<SemanticError>,
+  loop_depth: usize,
+}
+
+impl Default for Analyzer {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl Analyzer {
+  pub fn new() -> Self {
+    Self {
+      table: SymbolTable::new(),
+      error_list: Vec::new(),
+      loop_depth: 0,
+    }
+  }
+
+  pub fn get_errors(&self) -> &[SemanticError] {
+    &self.error_list
+  }
+
+  pub fn into_errors(self) -> Vec<SemanticError> {
+    self.error_list
+  }
+
+  fn error(&mut self, error_type: ErrorType, error_span: Span) {
+    self.error_list.push(SemanticError::new(error_type, error_span));
+  }
+
+  fn declare(&mut self, symbol: Symbol, span: Span) {
+    let name = symbol.name.clone();
+    if let Err(previous) = self.table.declare(symbol) {
+      self.error(ErrorType::Redeclared(name, previous.pos), span);
+    }
+  }
+
+  pub fn analyze(&mut self, unit: &CompUnit) {
+    for item in &unit.items {
+      match item {
+        GlobalItem::Decl(decl) => self.decl(decl),
+        GlobalItem::FuncDef(func) => self.func_def(func),
+      }
+    }
+  }
+
+  fn decl(&mut self, decl: &Decl) {
+    for def in &decl.defs {
+      for dim in &def.dims {
+        self.value(dim);
+      }
+      if let Some(init) = &def.init {
+        self.init_val(init);
+      }
+      self.declare(Symbol {
+        name: def.ident.name.clone(),
+        kind: SymbolKind::Var {
+          ty: decl.ty,
+          dims: def.dims.len(),
+          is_const: decl.is_const,
+        },
+        pos: Some(def.ident.pos()),
+      }, def.ident.span);
+    }
+  }
+
+  fn init_val(&mut self, init: &InitVal) {
+    match init {
+      InitVal::Exp(exp) => self.value(exp),
+      InitVal::List(items, _) => {
+        for item in items {
+          self.init_val(item);
+        }
+      }
+    }
+  }
+
+  fn func_def(&mut self, func: &FuncDef) {
+    let params = func
+      .params
+      .iter()
+      .map(|param| Param::new(param.ty, param.dims.as_ref().map_or(0, |dims| dims.len() + 1)))
+      .collect();
+    // 先声明函数本身，以支持递归调用
+    self.declare(Symbol {
+      name: func.ident.name.clone(),
+      kind: SymbolKind::Func {
+        ret: func.func_type,
+        params,
+      },
+      pos: Some(func.ident.pos()),
+    }, func.ident.span);
+
+    // 形参与函数体最外层的块共用一个作用域
+    self.table.push_scope();
+    for param in &func.params {
+      if let Some(dims) = &param.dims {
+        for dim in dims {
+          self.value(dim);
+        }
+      }
+      self.declare(Symbol {
+        name: param.ident.name.clone(),
+        kind: SymbolKind::Var {
+          ty: param.ty,
+          dims: param.dims.as_ref().map_or(0, |dims| dims.len() + 1),
+          is_const: false,
+        },
+        pos: Some(param.ident.pos()),
+      }, param.ident.span);
+    }
+    self.block_items(&func.body);
+    self.table.pop_scope();
+  }
+
+  fn block(&mut self, block: &Block) {
+    self.table.push_scope();
+    self.block_items(block);
+    self.table.pop_scope();
+  }
+
+  fn block_items(&mut self, block: &Block) {
+    for item in &block.items {
+      match item {
+        BlockItem::Decl(decl) => self.decl(decl),
+        BlockItem::Stmt(stmt) => self.stmt(stmt),
+      }
+    }
+  }
+
+  fn stmt(&mut self, stmt: &Stmt) {
+    match stmt {
+      Stmt::Assign(lval, exp) => {
+        if let Some(symbol) = self.lookup_var(&lval.ident) {
+          if let SymbolKind::Var { dims, is_const, .. } = symbol.kind {
+            if is_const {
+              self.error(ErrorType::AssignToConst(lval.ident.name.clone()), lval.ident.span);
+            } else if lval.indices.len() != dims {
+              self.error(
+                ErrorType::DimensionMismatch {
+                  name: lval.ident.name.clone(),
+                  expected: dims,
+                  found: lval.indices.len(),
+                },
+                lval.ident.span,
+              );
+            }
+          }
+        }
+        for index in &lval.indices {
+          self.value(index);
+        }
+        self.value(exp);
+      }
+      Stmt::Exp(Some(exp)) => {
+        self.exp(exp);
+      }
+      Stmt::Exp(None) => {}
+      Stmt::Block(block) => self.block(block),
+      Stmt::If(cond, then, otherwise) => {
+        self.value(cond);
+        self.stmt(then);
+        if let Some(otherwise) = otherwise {
+          self.stmt(otherwise);
+        }
+      }
+      Stmt::While(cond, body) => {
+        self.value(cond);
+        self.loop_depth += 1;
+        self.stmt(body);
+        self.loop_depth -= 1;
+      }
+      Stmt::Break(span) => {
+        if self.loop_depth == 0 {
+          self.error(ErrorType::BreakOutsideLoop, *span);
+        }
+      }
+      Stmt::Continue(span) => {
+        if self.loop_depth == 0 {
+          self.error(ErrorType::ContinueOutsideLoop, *span);
+        }
+      }
+      Stmt::Return(exp, _) => {
+        if let Some(exp) = exp {
+          self.value(exp);
+        }
+      }
+    }
+  }
+
+  // 查找变量，未声明或是函数时报错
+  fn lookup_var(&mut self, ident: &Ident) -> Option<Symbol> {
+    match self.table.lookup(&ident.name).cloned() {
+      None => {
+        self.error(ErrorType::Undeclared(ident.name.clone()), ident.span);
+        None
+      }
+      Some(symbol) if symbol.is_func() => {
+        self.error(ErrorType::NotAVariable(ident.name.clone()), ident.span);
+        None
+      }
+      symbol => symbol,
+    }
+  }
+
+  // 作为数值使用的表达式，结果不能是数组
+  fn value(&mut self, exp: &Exp) {
+    if let Some(dims) = self.exp(exp) {
+      if dims > 0 {
+        let name = match &exp.kind {
+          ExpKind::LVal(lval) => lval.ident.name.clone(),
+          _ => String::new(),
+        };
+        self.error(ErrorType::ArrayAsValue(name), exp.span);
+      }
+    }
+  }
+
+  // 返回表达式结果剩余的数组维数，出错时返回None避免重复报错
+  fn exp(&mut self, exp: &Exp) -> Option<usize> {
+    match &exp.kind {
+      ExpKind::IntConst(_) | ExpKind::FloatConst(_) => Some(0),
+      ExpKind::LVal(lval) => {
+        for index in &lval.indices {
+          self.value(index);
+        }
+        let symbol = self.lookup_var(&lval.ident)?;
+        let SymbolKind::Var { dims, .. } = symbol.kind else {
+          return None;
+        };
+        if lval.indices.len() > dims {
+          self.error(
+            ErrorType::DimensionMismatch {
+              name: lval.ident.name.clone(),
+              expected: dims,
+              found: lval.indices.len(),
+            },
+            lval.ident.span,
+          );
+          return None;
+        }
+        Some(dims - lval.indices.len())
+      }
+      ExpKind::Call(func, args) => self.call(func, args),
+      ExpKind::Unary(_, operand) => {
+        self.value(operand);
+        Some(0)
+      }
+      ExpKind::Binary(_, lhs, rhs) => {
+        self.value(lhs);
+        self.value(rhs);
+        Some(0)
+      }
+      ExpKind::Cast(_, operand) => self.exp(operand),
+    }
+  }
+
+  fn call(&mut self, func: &Ident, args: &[Exp]) -> Option<usize> {
+    let arg_dims: Vec<_> = args.iter().map(|arg| (self.exp(arg), arg.span)).collect();
+    let symbol = match self.table.lookup(&func.name).cloned() {
+      Some(symbol) => symbol,
+      None => {
+        self.error(ErrorType::Undeclared(func.name.clone()), func.span);
+        return None;
+      }
+    };
+    let SymbolKind::Func { params, .. } = symbol.kind else {
+      self.error(ErrorType::NotAFunction(func.name.clone()), func.span);
+      return None;
+    };
+
+    if params.len() != args.len() {
+      self.error(
+        ErrorType::ArgumentCount {
+          name: func.name.clone(),
+          expected: params.len(),
+          found: args.len(),
+        },
+        func.span,
+      );
+      return Some(0);
+    }
+    for (param, (found, span)) in params.iter().zip(arg_dims) {
+      if let Some(found) = found {
+        if found != param.dims {
+          self.error(
+            ErrorType::DimensionMismatch {
+              name: func.name.clone(),
+              expected: param.dims,
+              found,
+            },
+            span,
+          );
+        }
+      }
+    }
+    Some(0)
+  }
+}
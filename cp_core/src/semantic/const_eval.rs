@@ -0,0 +1,443 @@
+use std::collections::BTreeMap;
+
+use crate::ast::*;
+use crate::semantic::{ErrorType, Param, SemanticError};
+use crate::token::Span;
+
+// 编译期常量的值
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConstValue {
+  Int(i32),
+  Float(f32),
+}
+
+impl ConstValue {
+  pub fn zero(ty: BType) -> Self {
+    match ty {
+      BType::Int => ConstValue::Int(0),
+      BType::Float => ConstValue::Float(0.0),
+    }
+  }
+
+  fn as_int(self) -> i32 {
+    match self {
+      ConstValue::Int(v) => v,
+      ConstValue::Float(v) => v as i32,
+    }
+  }
+
+  fn as_float(self) -> f32 {
+    match self {
+      ConstValue::Int(v) => v as f32,
+      ConstValue::Float(v) => v,
+    }
+  }
+
+  fn is_true(self) -> bool {
+    match self {
+      ConstValue::Int(v) => v != 0,
+      ConstValue::Float(v) => v != 0.0,
+    }
+  }
+
+  // 转换为带类型的字面量表达式
+  pub fn to_exp(self, span: Span) -> Exp {
+    match self {
+      ConstValue::Int(v) => Exp {
+        ty: Some(Type::Int),
+        ..Exp::new(ExpKind::IntConst(v), span)
+      },
+      ConstValue::Float(v) => Exp {
+        ty: Some(Type::Float),
+        ..Exp::new(ExpKind::FloatConst(v), span)
+      },
+    }
+  }
+}
+
+// 变量的各维长度，标量的dims为空，数组形参省略的第一维记为0；
+// 常量还有按行优先展开后的全部元素
+#[derive(Debug, Clone)]
+struct VarInfo {
+  dims: Vec<usize>,
+  values: Option<Vec<ConstValue>>,
+}
+
+// 常量求值
+// 在类型检查之后执行：把数组维度折叠为int字面量，把常量、全局变量和数组的初始化列表
+// 按SysY的括号省略规则展开为补齐0的一维列表，并把能在编译期算出的表达式替换为字面量；
+// 维度求出之后再检查数组实参除第一维外各维的长度与形参是否一致
+pub struct ConstEvaluator {
+  // 维度求值失败的变量记为None
+  scopes: Vec<BTreeMap<String, Option<VarInfo>>>,
+  // 形参维度全部求出的函数
+  funcs: BTreeMap<String, Vec<Param>>,
+  error_list: Vec<SemanticError>,
+}
+
+impl Default for ConstEvaluator {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl ConstEvaluator {
+  pub fn new() -> Self {
+    Self {
+      scopes: vec![BTreeMap::new()],
+      funcs: BTreeMap::new(),
+      error_list: Vec::new(),
+    }
+  }
+
+  pub fn into_errors(self) -> Vec<SemanticError> {
+    self.error_list
+  }
+
+  fn error(&mut self, error_type: ErrorType, error_span: Span) {
+    self.error_list.push(SemanticError::new(error_type, error_span));
+  }
+
+  fn declare(&mut self, name: &str, info: Option<VarInfo>) {
+    self.scopes.last_mut().unwrap().insert(name.to_string(), info);
+  }
+
+  fn lookup(&self, name: &str) -> Option<&VarInfo> {
+    self
+      .scopes
+      .iter()
+      .rev()
+      .find_map(|scope| scope.get(name))
+      .and_then(Option::as_ref)
+  }
+
+  pub fn evaluate(&mut self, unit: &mut CompUnit) {
+    for item in &mut unit.items {
+      match item {
+        GlobalItem::Decl(decl) => self.decl(decl, true),
+        GlobalItem::FuncDef(func) => self.func_def(func),
+      }
+    }
+  }
+
+  fn decl(&mut self, decl: &mut Decl, global: bool) {
+    // 常量和全局变量的初始值必须在编译期确定
+    let required = decl.is_const || global;
+    for def in &mut decl.defs {
+      let name = def.ident.name.clone();
+      let Some(dims) = self.dims(&name, &mut def.dims) else {
+        self.declare(&name, None);
+        continue;
+      };
+      let values = match def.init.take() {
+        Some(init) => self.init(&name, decl.ty, &dims, init, required, def),
+        None => None,
+      };
+      let values = values.filter(|_| decl.is_const);
+      self.declare(&name, Some(VarInfo { dims, values }));
+    }
+  }
+
+  // 求出各维长度，并把维度表达式替换为字面量
+  fn dims(&mut self, name: &str, dims: &mut [Exp]) -> Option<Vec<usize>> {
+    let mut result = Vec::new();
+    for dim in dims {
+      match self.fold(dim) {
+        Some(ConstValue::Int(len)) if len > 0 => result.push(len as usize),
+        Some(_) => {
+          self.error(ErrorType::NonPositiveDimension(name.to_string()), dim.span);
+          return None;
+        }
+        None => {
+          self.error(ErrorType::NotConstant, dim.span);
+          return None;
+        }
+      }
+    }
+    Some(result)
+  }
+
+  // 处理初始化，把结果写回def.init，全部元素都是常量时返回它们的值
+  fn init(
+    &mut self,
+    name: &str,
+    ty: BType,
+    dims: &[usize],
+    init: InitVal,
+    required: bool,
+    def: &mut Def,
+  ) -> Option<Vec<ConstValue>> {
+    match (init, dims.is_empty()) {
+      (InitVal::Exp(mut exp), true) => {
+        let value = self.fold(&mut exp);
+        if value.is_none() && required {
+          self.error(ErrorType::NotConstant, exp.span);
+        }
+        def.init = Some(InitVal::Exp(exp));
+        value.map(|value| vec![value])
+      }
+      (InitVal::List(items, span), false) => {
+        let mut elems = vec![None; dims.iter().product()];
+        if let Err((error_type, error_span)) = flatten(name, items, dims, 0, &mut elems) {
+          self.error(error_type, error_span);
+          return None;
+        }
+        let mut values = Some(Vec::with_capacity(elems.len()));
+        let mut list = Vec::with_capacity(elems.len());
+        for elem in elems {
+          let mut exp = elem.unwrap_or_else(|| ConstValue::zero(ty).to_exp(span));
+          match (self.fold(&mut exp), &mut values) {
+            (Some(value), Some(values)) => values.push(value),
+            (Some(_), None) => {}
+            (None, _) => {
+              if required {
+                self.error(ErrorType::NotConstant, exp.span);
+              }
+              values = None;
+            }
+          }
+          list.push(InitVal::Exp(exp));
+        }
+        def.init = Some(InitVal::List(list, span));
+        values
+      }
+      (InitVal::Exp(exp), false) => {
+        self.error(ErrorType::InvalidInitializer(name.to_string()), exp.span);
+        None
+      }
+      (InitVal::List(_, span), true) => {
+        self.error(ErrorType::InvalidInitializer(name.to_string()), span);
+        None
+      }
+    }
+  }
+
+  fn func_def(&mut self, func: &mut FuncDef) {
+    self.scopes.push(BTreeMap::new());
+    let mut params = Vec::new();
+    for param in &mut func.params {
+      let dims = match &mut param.dims {
+        Some(dims) => self.dims(&param.ident.name, dims).map(|dims| [vec![0], dims].concat()),
+        None => Some(Vec::new()),
+      };
+      if let Some(dims) = &dims {
+        params.push(Param {
+          ty: param.ty,
+          dims: dims.len(),
+          extents: dims.iter().skip(1).map(|&len| len as i32).collect(),
+        });
+      }
+      self.declare(&param.ident.name, dims.map(|dims| VarInfo { dims, values: None }));
+    }
+    // 先记录函数本身，以检查递归调用；有形参的维度求值失败时不检查
+    if params.len() == func.params.len() {
+      self.funcs.insert(func.ident.name.clone(), params);
+    }
+    self.block_items(&mut func.body);
+    self.scopes.pop();
+  }
+
+  fn block_items(&mut self, block: &mut Block) {
+    for item in &mut block.items {
+      match item {
+        BlockItem::Decl(decl) => self.decl(decl, false),
+        BlockItem::Stmt(stmt) => self.stmt(stmt),
+      }
+    }
+  }
+
+  fn stmt(&mut self, stmt: &mut Stmt) {
+    match stmt {
+      Stmt::Assign(lval, exp) => {
+        for index in &mut lval.indices {
+          self.fold(index);
+        }
+        self.fold(exp);
+      }
+      Stmt::Exp(Some(exp)) | Stmt::Return(Some(exp), _) => {
+        self.fold(exp);
+      }
+      Stmt::Exp(None) | Stmt::Return(None, _) | Stmt::Break(_) | Stmt::Continue(_) => {}
+      Stmt::Block(block) => {
+        self.scopes.push(BTreeMap::new());
+        self.block_items(block);
+        self.scopes.pop();
+      }
+      Stmt::If(cond, then, otherwise) => {
+        self.fold(cond);
+        self.stmt(then);
+        if let Some(otherwise) = otherwise {
+          self.stmt(otherwise);
+        }
+      }
+      Stmt::While(cond, body) => {
+        self.fold(cond);
+        self.stmt(body);
+      }
+    }
+  }
+
+  // 折叠表达式中所有的常量子表达式，整个表达式是常量时返回其值
+  fn fold(&mut self, exp: &mut Exp) -> Option<ConstValue> {
+    let value = match &mut exp.kind {
+      ExpKind::IntConst(v) => return Some(ConstValue::Int(*v)),
+      ExpKind::FloatConst(v) => return Some(ConstValue::Float(*v)),
+      ExpKind::LVal(lval) => {
+        let mut indices = Vec::new();
+        for index in &mut lval.indices {
+          indices.push(self.fold(index));
+        }
+        let info = self.lookup(&lval.ident.name)?;
+        let values = info.values.as_ref()?;
+        if indices.len() != info.dims.len() {
+          return None;
+        }
+        // 按行优先计算元素的下标，越界时不折叠
+        let mut offset = 0;
+        for (index, len) in indices.into_iter().zip(&info.dims) {
+          let index = usize::try_from(index?.as_int()).ok().filter(|index| index < len)?;
+          offset = offset * len + index;
+        }
+        values[offset]
+      }
+      ExpKind::Call(func, args) => {
+        for arg in args.iter_mut() {
+          self.fold(arg);
+        }
+        self.call(func, args);
+        return None;
+      }
+      ExpKind::Unary(op, operand) => {
+        let value = self.fold(operand)?;
+        match (op, value) {
+          (UnaryOp::Plus, value) => value,
+          (UnaryOp::Minus, ConstValue::Int(v)) => ConstValue::Int(v.wrapping_neg()),
+          (UnaryOp::Minus, ConstValue::Float(v)) => ConstValue::Float(-v),
+          (UnaryOp::Not, value) => ConstValue::Int(!value.is_true() as i32),
+        }
+      }
+      ExpKind::Binary(op, lhs, rhs) => {
+        let lhs = self.fold(lhs);
+        let rhs = self.fold(rhs);
+        binary(*op, lhs?, rhs?)?
+      }
+      ExpKind::Cast(ty, operand) => {
+        let value = self.fold(operand)?;
+        match ty {
+          BType::Int => ConstValue::Int(value.as_int()),
+          BType::Float => ConstValue::Float(value.as_float()),
+        }
+      }
+    };
+    *exp = value.to_exp(exp.span);
+    Some(value)
+  }
+
+  // 数组实参除第一维外的各维长度必须与形参相同，运行库函数的数组形参都是一维的
+  fn call(&mut self, func: &Ident, args: &[Exp]) {
+    let Some(params) = self.funcs.get(&func.name) else {
+      return;
+    };
+    let mut mismatched = Vec::new();
+    for (index, (param, arg)) in params.iter().zip(args).enumerate() {
+      let ExpKind::LVal(lval) = &arg.kind else {
+        continue;
+      };
+      let Some(info) = self.lookup(&lval.ident.name) else {
+        continue;
+      };
+      let extents = info.dims.iter().skip(lval.indices.len() + 1).map(|&len| len as i32);
+      if param.dims > 1 && !extents.eq(param.extents.iter().copied()) {
+        mismatched.push((index, arg.span));
+      }
+    }
+    for (index, span) in mismatched {
+      self.error(
+        ErrorType::ArgumentType {
+          name: func.name.clone(),
+          index: index + 1,
+        },
+        span,
+      );
+    }
+  }
+}
+
+// 两个操作数的类型已由类型检查统一，除以0时不折叠
+fn binary(op: BinaryOp, lhs: ConstValue, rhs: ConstValue) -> Option<ConstValue> {
+  use ConstValue::{Float, Int};
+  let bool_value = |b: bool| Some(Int(b as i32));
+  match op {
+    BinaryOp::And => return bool_value(lhs.is_true() && rhs.is_true()),
+    BinaryOp::Or => return bool_value(lhs.is_true() || rhs.is_true()),
+    _ => {}
+  }
+  match (lhs, rhs) {
+    (Int(a), Int(b)) => match op {
+      BinaryOp::Add => Some(Int(a.wrapping_add(b))),
+      BinaryOp::Sub => Some(Int(a.wrapping_sub(b))),
+      BinaryOp::Mul => Some(Int(a.wrapping_mul(b))),
+      BinaryOp::Div => a.checked_div(b).or_else(|| (b == -1).then(|| a.wrapping_neg())).map(Int),
+      BinaryOp::Mod => a.checked_rem(b).or_else(|| (b == -1).then_some(0)).map(Int),
+      BinaryOp::Lt => bool_value(a < b),
+      BinaryOp::Gt => bool_value(a > b),
+      BinaryOp::Le => bool_value(a <= b),
+      BinaryOp::Ge => bool_value(a >= b),
+      BinaryOp::Eq => bool_value(a == b),
+      BinaryOp::Ne => bool_value(a != b),
+      BinaryOp::And | BinaryOp::Or => unreachable!(),
+    },
+    (lhs, rhs) => {
+      let (a, b) = (lhs.as_float(), rhs.as_float());
+      match op {
+        BinaryOp::Add => Some(Float(a + b)),
+        BinaryOp::Sub => Some(Float(a - b)),
+        BinaryOp::Mul => Some(Float(a * b)),
+        BinaryOp::Div if b != 0.0 => Some(Float(a / b)),
+        BinaryOp::Div | BinaryOp::Mod => None,
+        BinaryOp::Lt => bool_value(a < b),
+        BinaryOp::Gt => bool_value(a > b),
+        BinaryOp::Le => bool_value(a <= b),
+        BinaryOp::Ge => bool_value(a >= b),
+        BinaryOp::Eq => bool_value(a == b),
+        BinaryOp::Ne => bool_value(a != b),
+        BinaryOp::And | BinaryOp::Or => unreachable!(),
+      }
+    }
+  }
+}
+
+// 按括号省略规则把初始化列表展开到elems[base..]
+// 遇到嵌套的列表时，它初始化从当前位置开始、与当前位置对齐的最大子数组
+fn flatten(
+  name: &str,
+  items: Vec<InitVal>,
+  dims: &[usize],
+  base: usize,
+  elems: &mut [Option<Exp>],
+) -> Result<(), (ErrorType, Span)> {
+  let total: usize = dims.iter().product();
+  let mut pos = 0;
+  for item in items {
+    match item {
+      InitVal::Exp(exp) => {
+        if pos >= total {
+          return Err((ErrorType::TooManyInitializers(name.to_string()), exp.span));
+        }
+        elems[base + pos] = Some(exp);
+        pos += 1;
+      }
+      InitVal::List(items, list_span) => {
+        if pos >= total {
+          return Err((ErrorType::TooManyInitializers(name.to_string()), list_span));
+        }
+        let sub = (1..dims.len())
+          .find(|&k| pos % dims[k..].iter().product::<usize>() == 0)
+          .ok_or((ErrorType::InvalidInitializer(name.to_string()), list_span))?;
+        flatten(name, items, &dims[sub..], base + pos, elems)?;
+        pos += dims[sub..].iter().product::<usize>();
+      }
+    }
+  }
+  Ok(())
+}
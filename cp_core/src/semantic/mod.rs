@@ -0,0 +1,375 @@
+mod analyzer;
+mod const_eval;
+mod scope;
+mod type_check;
+
+use std::fmt::Display;
+
+use crate::ast::CompUnit;
+use crate::token::{Point, Span};
+
+pub use analyzer::Analyzer;
+pub use const_eval::{ConstEvaluator, ConstValue};
+pub use scope::{Param, Symbol, SymbolKind, SymbolTable};
+pub use type_check::TypeChecker;
+
+// 语义分析，返回所有发现的错误
+pub fn analyze(unit: &CompUnit) -> Vec<SemanticError> {
+  let mut analyzer = Analyzer::new();
+  analyzer.analyze(unit);
+  analyzer.into_errors()
+}
+
+// 类型检查，为表达式标注类型并插入隐式转换
+// 依赖名字分析的结果，应在analyze没有错误时调用
+pub fn type_check(unit: &mut CompUnit) -> Vec<SemanticError> {
+  let mut checker = TypeChecker::new();
+  checker.check(unit);
+  checker.into_errors()
+}
+
+// 常量求值，折叠数组维度和常量表达式并展开初始化列表
+// 依赖类型检查插入的类型转换，应在type_check没有错误时调用
+pub fn evaluate_consts(unit: &mut CompUnit) -> Vec<SemanticError> {
+  let mut evaluator = ConstEvaluator::new();
+  evaluator.evaluate(unit);
+  evaluator.into_errors()
+}
+
+// 完整的语义检查：名字分析、类型检查和常量求值依次进行，前一步有错误时停止
+pub fn check(unit: &mut CompUnit) -> Vec<SemanticError> {
+  let errors = analyze(unit);
+  if !errors.is_empty() {
+    return errors;
+  }
+  let errors = type_check(unit);
+  if !errors.is_empty() {
+    return errors;
+  }
+  evaluate_consts(unit)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SemanticError {
+  pub error_type: ErrorType,
+  // 出错的词素的区间
+  pub error_span: Span,
+}
+
+impl SemanticError {
+  pub fn new(error_type: ErrorType, error_span: Span) -> Self {
+    Self { error_type, error_span }
+  }
+
+  pub fn pos(&self) -> Point {
+    *self.error_span.get_start_point()
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ErrorType {
+  // 未声明的标识符
+  Undeclared(String),
+  // 同一作用域内重复声明
+  Redeclared(String, Option<Point>),
+  // 调用的不是函数
+  NotAFunction(String),
+  // 函数名被当作变量使用
+  NotAVariable(String),
+  // 实参个数不匹配
+  ArgumentCount { name: String, expected: usize, found: usize },
+  // 给常量赋值
+  AssignToConst(String),
+  // 数组维数不匹配
+  DimensionMismatch { name: String, expected: usize, found: usize },
+  // 数组被当作数值使用
+  ArrayAsValue(String),
+  BreakOutsideLoop,
+  ContinueOutsideLoop,
+  // 对float使用取模运算
+  ModOnFloat,
+  // void函数的返回值被使用
+  VoidValue(String),
+  // void函数返回了值
+  ReturnValueInVoid(String),
+  // 非void函数缺少返回值
+  MissingReturnValue(String),
+  // 数组实参的元素类型或除第一维外的各维长度与形参不同，index从1开始
+  ArgumentType { name: String, index: usize },
+  // 数组下标或维度不是int
+  NonIntegerIndex,
+  // 需要编译期常量的地方出现了非常量表达式
+  NotConstant,
+  // 数组维度不是正数
+  NonPositiveDimension(String),
+  // 初始化列表中的元素多于数组的容量
+  TooManyInitializers(String),
+  // 标量使用了初始化列表，数组使用了单个表达式，或嵌套列表没有对齐到子数组
+  InvalidInitializer(String),
+}
+
+impl Display for SemanticError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match &self.error_type {
+      ErrorType::Undeclared(name) => write!(f, "use of undeclared identifier '{}'", name),
+      ErrorType::Redeclared(name, Some(pos)) => write!(
+        f,
+        "redeclaration of '{}' (previously declared at {}:{})",
+        name,
+        pos.get_row(),
+        pos.get_col()
+      ),
+      ErrorType::Redeclared(name, None) => write!(f, "redeclaration of '{}'", name),
+      ErrorType::NotAFunction(name) => write!(f, "'{}' is not a function", name),
+      ErrorType::NotAVariable(name) => write!(f, "function '{}' used as a variable", name),
+      ErrorType::ArgumentCount { name, expected, found } => write!(
+        f,
+        "function '{}' expects {} argument(s), but {} were given",
+        name, expected, found
+      ),
+      ErrorType::AssignToConst(name) => write!(f, "cannot assign to constant '{}'", name),
+      ErrorType::DimensionMismatch { name, expected, found } => write!(
+        f,
+        "dimension mismatch for '{}': expected {}, found {}",
+        name, expected, found
+      ),
+      ErrorType::ArrayAsValue(name) => write!(f, "array '{}' used as a value", name),
+      ErrorType::BreakOutsideLoop => write!(f, "'break' outside of a loop"),
+      ErrorType::ContinueOutsideLoop => write!(f, "'continue' outside of a loop"),
+      ErrorType::ModOnFloat => write!(f, "invalid operands of type 'float' to '%'"),
+      ErrorType::VoidValue(name) => write!(f, "void value of '{}' used in an expression", name),
+      ErrorType::ReturnValueInVoid(name) => write!(f, "void function '{}' should not return a value", name),
+      ErrorType::MissingReturnValue(name) => write!(f, "non-void function '{}' should return a value", name),
+      ErrorType::ArgumentType { name, index } => write!(
+        f,
+        "argument {} of '{}' has a mismatched array element type",
+        index, name
+      ),
+      ErrorType::NonIntegerIndex => write!(f, "array subscript is not an integer"),
+      ErrorType::NotConstant => write!(f, "expression is not a compile-time constant"),
+      ErrorType::NonPositiveDimension(name) => write!(f, "size of array '{}' is not positive", name),
+      ErrorType::TooManyInitializers(name) => write!(f, "too many initializers for '{}'", name),
+      ErrorType::InvalidInitializer(name) => write!(f, "invalid initializer for '{}'", name),
+    }
+  }
+}
+
+// 单元测试
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::ast::*;
+  use crate::test_util::lower_source;
+
+  fn errors(source: &str) -> Vec<ErrorType> {
+    analyze(&lower_source(source))
+      .into_iter()
+      .map(|e| e.error_type)
+      .collect()
+  }
+
+  #[test]
+  fn test_valid_program() {
+    let source = "
+      const int N = 4;
+      int h[N][2];
+      int sum(int a[][2], int n) {
+        int i = 0, s = 0;
+        while (i < n) { s = s + a[i][0]; i = i + 1; if (s > 10) break; }
+        return s;
+      }
+      int main() {
+        int g = 1;
+        { int g = 2; putint(g); }
+        putint(sum(h, N) + g);
+        return 0;
+      }";
+    assert_eq!(errors(source), vec![]);
+  }
+
+  #[test]
+  fn test_scope_errors() {
+    let errs = errors("int a; int a; int main() { int b; { int b; } int b; return c; }");
+    assert_eq!(errs.len(), 3);
+    assert!(matches!(&errs[0], ErrorType::Redeclared(name, Some(_)) if name == "a"));
+    assert!(matches!(&errs[1], ErrorType::Redeclared(name, Some(_)) if name == "b"));
+    assert_eq!(errs[2], ErrorType::Undeclared("c".to_string()));
+  }
+
+  #[test]
+  fn test_call_errors() {
+    let errs = errors("int f(int x) { return x; } int main() { int v; v(); f(); f; return f(1, 2); }");
+    assert_eq!(
+      errs,
+      vec![
+        ErrorType::NotAFunction("v".to_string()),
+        ErrorType::ArgumentCount { name: "f".to_string(), expected: 1, found: 0 },
+        ErrorType::NotAVariable("f".to_string()),
+        ErrorType::ArgumentCount { name: "f".to_string(), expected: 1, found: 2 },
+      ]
+    );
+  }
+
+  #[test]
+  fn test_const_and_dimension_errors() {
+    let errs = errors(
+      "const int c = 1; int a[2][3]; void f(int p[][3]) {}
+       int main() { c = 2; a[1] = 1; a[1][2][0] = 3; f(a[0]); f(a); return a[0] + 1; }",
+    );
+    assert_eq!(
+      errs,
+      vec![
+        ErrorType::AssignToConst("c".to_string()),
+        ErrorType::DimensionMismatch { name: "a".to_string(), expected: 2, found: 1 },
+        ErrorType::DimensionMismatch { name: "a".to_string(), expected: 2, found: 3 },
+        ErrorType::DimensionMismatch { name: "f".to_string(), expected: 2, found: 1 },
+        ErrorType::ArrayAsValue("a".to_string()),
+      ]
+    );
+  }
+
+  #[test]
+  fn test_loop_errors() {
+    let errs = errors("int main() { break; while (1) { continue; } continue; return 0; }");
+    assert_eq!(errs, vec![ErrorType::BreakOutsideLoop, ErrorType::ContinueOutsideLoop]);
+  }
+
+  fn type_errors(source: &str) -> Vec<ErrorType> {
+    check(&mut lower_source(source))
+      .into_iter()
+      .map(|e| e.error_type)
+      .collect()
+  }
+
+  #[test]
+  fn test_implicit_conversions() {
+    let mut unit = lower_source("float f(float x) { return 1; } int main() { int a = 2.5; float b = a + 1.5; return f(a); }");
+    assert_eq!(analyze(&unit), vec![]);
+    assert_eq!(type_check(&mut unit), vec![]);
+    let GlobalItem::FuncDef(main) = &unit.items[1] else { panic!() };
+    let BlockItem::Decl(a) = &main.body.items[0] else { panic!() };
+    let Some(InitVal::Exp(init)) = &a.defs[0].init else { panic!() };
+    assert!(matches!(init.kind, ExpKind::Cast(BType::Int, _)));
+
+    let BlockItem::Decl(b) = &main.body.items[1] else { panic!() };
+    let Some(InitVal::Exp(init)) = &b.defs[0].init else { panic!() };
+    assert_eq!(init.ty, Some(Type::Float));
+    let ExpKind::Binary(BinaryOp::Add, lhs, _) = &init.kind else { panic!() };
+    assert!(matches!(lhs.kind, ExpKind::Cast(BType::Float, _)));
+
+    let BlockItem::Stmt(stmt) = &main.body.items[2] else { panic!() };
+    let Stmt::Return(Some(ret), _) = stmt.as_ref() else { panic!() };
+    assert!(matches!(ret.kind, ExpKind::Cast(BType::Int, _)));
+    assert_eq!(ret.ty, Some(Type::Int));
+  }
+
+  #[test]
+  fn test_type_errors() {
+    let errs = type_errors(
+      "void g() {} int f(float a[]) { return; }
+       int main() { int a[2]; float x = 1.0; int y = x % 2; y = g() + 1; a[x] = 1; f(a); g(); return 0; }
+       void h() { return 1; }",
+    );
+    assert_eq!(
+      errs,
+      vec![
+        ErrorType::MissingReturnValue("f".to_string()),
+        ErrorType::ModOnFloat,
+        ErrorType::VoidValue("g".to_string()),
+        ErrorType::NonIntegerIndex,
+        ErrorType::ArgumentType { name: "f".to_string(), index: 1 },
+        ErrorType::ReturnValueInVoid("h".to_string()),
+      ]
+    );
+  }
+
+  fn init_values(def: &Def) -> Vec<ExpKind> {
+    match &def.init {
+      Some(InitVal::List(items, _)) => items
+        .iter()
+        .map(|item| match item {
+          InitVal::Exp(exp) => exp.kind.clone(),
+          _ => panic!("initializer is not flattened"),
+        })
+        .collect(),
+      _ => panic!("expected an initializer list"),
+    }
+  }
+
+  #[test]
+  fn test_const_evaluation() {
+    let mut unit = lower_source(
+      "const int N = 3, M = N * 2 - 1;
+       const int c[N][2] = {1, 2, {3}, 4 + N};
+       int a[c[2][0] - M + 1][N] = {{1}, 2, 3, 4, {c[2][0]}};
+       float f[2] = {N / 2};
+       int main() { int x = 1; int b[2] = {x, M}; return c[x][0] + N; }",
+    );
+    assert_eq!(check(&mut unit), vec![]);
+
+    let GlobalItem::Decl(consts) = &unit.items[1] else { panic!() };
+    assert_eq!(consts.defs[0].shape(), vec![3, 2]);
+    use ExpKind::IntConst;
+    assert_eq!(
+      init_values(&consts.defs[0]),
+      vec![IntConst(1), IntConst(2), IntConst(3), IntConst(0), IntConst(7), IntConst(0)]
+    );
+
+    let GlobalItem::Decl(vars) = &unit.items[2] else { panic!() };
+    assert_eq!(vars.defs[0].shape(), vec![3, 3]);
+    assert_eq!(
+      init_values(&vars.defs[0]),
+      [1, 0, 0, 2, 3, 4, 7, 0, 0].map(IntConst).to_vec()
+    );
+
+    let GlobalItem::Decl(floats) = &unit.items[3] else { panic!() };
+    assert_eq!(init_values(&floats.defs[0]), vec![ExpKind::FloatConst(1.0), ExpKind::FloatConst(0.0)]);
+
+    // 局部数组可以用运行时的值初始化，常量仍被折叠
+    let GlobalItem::FuncDef(main) = &unit.items[4] else { panic!() };
+    let BlockItem::Decl(b) = &main.body.items[1] else { panic!() };
+    let values = init_values(&b.defs[0]);
+    assert!(matches!(values[0], ExpKind::LVal(_)));
+    assert_eq!(values[1], IntConst(5));
+    let BlockItem::Stmt(stmt) = &main.body.items[2] else { panic!() };
+    let Stmt::Return(Some(ret), _) = stmt.as_ref() else { panic!() };
+    assert!(matches!(&ret.kind, ExpKind::Binary(BinaryOp::Add, _, rhs) if rhs.kind == IntConst(3)));
+  }
+
+  #[test]
+  fn test_const_errors() {
+    let errs = type_errors(
+      "int g = 1;
+       const int a = g;
+       int b[0];
+       int c[2][2] = {1, 2, 3, 4, 5};
+       int d[2][2] = {1, {2}};
+       int e = {1};
+       int main() { const int n = -1; int f[n]; return 0; }",
+    );
+    assert_eq!(
+      errs,
+      vec![
+        ErrorType::NotConstant,
+        ErrorType::NonPositiveDimension("b".to_string()),
+        ErrorType::TooManyInitializers("c".to_string()),
+        ErrorType::InvalidInitializer("d".to_string()),
+        ErrorType::InvalidInitializer("e".to_string()),
+        ErrorType::NonPositiveDimension("f".to_string()),
+      ]
+    );
+  }
+
+  #[test]
+  fn test_argument_extents() {
+    let source = "
+      const int N = 3;
+      int f(int a[][3]) { return a[0][0]; }
+      int g(int a[][N], int c[][2][N]) { return f(a) + f(c[1]); }
+      int main() { int b[2][4]; int c[2][2][3]; int d[4][2][3]; return f(b) + g(c[0], d) + f(d[1]); }";
+    assert_eq!(
+      type_errors(source),
+      vec![ErrorType::ArgumentType { name: "f".to_string(), index: 1 }]
+    );
+  }
+}
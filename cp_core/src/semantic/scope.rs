@@ -0,0 +1,120 @@
+use std::collections::BTreeMap;
+
+use crate::ast::{BType, FuncType};
+use crate::token::Point;
+
+// 形参的基本类型和数组维数，数组维数包括省略的第一维
+#[derive(Debug, Clone, PartialEq)]
+pub struct Param {
+  pub ty: BType,
+  pub dims: usize,
+  // 除第一维外各维的长度，常量求值之后才填入
+  pub extents: Vec<i32>,
+}
+
+impl Param {
+  pub fn new(ty: BType, dims: usize) -> Self {
+    Self { ty, dims, extents: Vec::new() }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Symbol {
+  pub name: String,
+  pub kind: SymbolKind,
+  // 运行库函数没有位置
+  pub pos: Option<Point>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SymbolKind {
+  // dims为数组的维数，数组形参包括省略的第一维
+  Var { ty: BType, dims: usize, is_const: bool },
+  Func { ret: FuncType, params: Vec<Param> },
+}
+
+impl Symbol {
+  pub fn is_func(&self) -> bool {
+    matches!(self.kind, SymbolKind::Func { .. })
+  }
+}
+
+// 嵌套作用域的符号表
+// 最外层是运行库函数，其次是全局作用域，之后每个块压入一层
+#[derive(Debug)]
+pub struct SymbolTable {
+  scopes: Vec<BTreeMap<String, Symbol>>,
+}
+
+impl Default for SymbolTable {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl SymbolTable {
+  pub fn new() -> Self {
+    let mut table = Self {
+      scopes: vec![BTreeMap::new()],
+    };
+    for (name, ret, params) in runtime_functions() {
+      table.scopes[0].insert(
+        name.to_string(),
+        Symbol {
+          name: name.to_string(),
+          kind: SymbolKind::Func { ret, params },
+          pos: None,
+        },
+      );
+    }
+    table.push_scope();
+    table
+  }
+
+  pub fn push_scope(&mut self) {
+    self.scopes.push(BTreeMap::new());
+  }
+
+  pub fn pop_scope(&mut self) {
+    // 运行库和全局作用域不会被弹出
+    if self.scopes.len() > 2 {
+      self.scopes.pop();
+    }
+  }
+
+  pub fn is_global(&self) -> bool {
+    self.scopes.len() == 2
+  }
+
+  // 在当前作用域声明符号，同一作用域内重复声明时返回之前的符号
+  pub fn declare(&mut self, symbol: Symbol) -> Result<(), Symbol> {
+    let scope = self.scopes.last_mut().unwrap();
+    if let Some(previous) = scope.get(&symbol.name) {
+      return Err(previous.clone());
+    }
+    scope.insert(symbol.name.clone(), symbol);
+    Ok(())
+  }
+
+  pub fn lookup(&self, name: &str) -> Option<&Symbol> {
+    self.scopes.iter().rev().find_map(|scope| scope.get(name))
+  }
+}
+
+// SysY运行库
+fn runtime_functions() -> Vec<(&'static str, FuncType, Vec<Param>)> {
+  vec![
+    ("getint", FuncType::Int, vec![]),
+    ("getch", FuncType::Int, vec![]),
+    ("getfloat", FuncType::Float, vec![]),
+    ("getarray", FuncType::Int, vec![Param::new(BType::Int, 1)]),
+    ("getfarray", FuncType::Int, vec![Param::new(BType::Float, 1)]),
+    ("putint", FuncType::Void, vec![Param::new(BType::Int, 0)]),
+    ("putch", FuncType::Void, vec![Param::new(BType::Int, 0)]),
+    ("putfloat", FuncType::Void, vec![Param::new(BType::Float, 0)]),
+    ("putarray", FuncType::Void, vec![Param::new(BType::Int, 0), Param::new(BType::Int, 1)]),
+    ("putfarray", FuncType::Void, vec![Param::new(BType::Int, 0), Param::new(BType::Float, 1)]),
+    ("_sysy_starttime", FuncType::Void, vec![Param::new(BType::Int, 0)]),
+    ("_sysy_stoptime", FuncType::Void, vec![Param::new(BType::Int, 0)]),
+  ]
+}
@@ -0,0 +1,322 @@
+use crate::ast::*;
+use crate::semantic::{ErrorType, Param, SemanticError, Symbol, SymbolKind, SymbolTable};
+use crate::token::Span;
+
+// 类型检查
+// 在名字分析之后执行，为每个表达式标注类型，并按SysY的规则插入int和float之间的转换：
+// 算术运算提升为float，赋值、初始化、传参和返回时转换为目标类型
+pub struct TypeChecker {
+  table: SymbolTable,
+  error_list: Vec<SemanticError>,
+  ret: FuncType,
+  func_name: String,
+}
+
+impl Default for TypeChecker {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl TypeChecker {
+  pub fn new() -> Self {
+    Self {
+      table: SymbolTable::new(),
+      error_list: Vec::new(),
+      ret: FuncType::Void,
+      func_name: String::new(),
+    }
+  }
+
+  pub fn into_errors(self) -> Vec<SemanticError> {
+    self.error_list
+  }
+
+  fn error(&mut self, error_type: ErrorType, error_span: Span) {
+    self.error_list.push(SemanticError::new(error_type, error_span));
+  }
+
+  pub fn check(&mut self, unit: &mut CompUnit) {
+    for item in &mut unit.items {
+      match item {
+        GlobalItem::Decl(decl) => self.decl(decl),
+        GlobalItem::FuncDef(func) => self.func_def(func),
+      }
+    }
+  }
+
+  fn decl(&mut self, decl: &mut Decl) {
+    for def in &mut decl.defs {
+      for dim in &mut def.dims {
+        self.index(dim);
+      }
+      if let Some(init) = &mut def.init {
+        self.init_val(init, decl.ty);
+      }
+      // 名字分析已经报告过重复声明
+      let _ = self.table.declare(Symbol {
+        name: def.ident.name.clone(),
+        kind: SymbolKind::Var {
+          ty: decl.ty,
+          dims: def.dims.len(),
+          is_const: decl.is_const,
+        },
+        pos: Some(def.ident.pos()),
+      });
+    }
+  }
+
+  fn init_val(&mut self, init: &mut InitVal, ty: BType) {
+    match init {
+      InitVal::Exp(exp) => {
+        self.exp(exp);
+        self.convert(exp, ty);
+      }
+      InitVal::List(items, _) => {
+        for item in items {
+          self.init_val(item, ty);
+        }
+      }
+    }
+  }
+
+  fn func_def(&mut self, func: &mut FuncDef) {
+    let params = func
+      .params
+      .iter()
+      .map(|param| Param::new(param.ty, param.dims.as_ref().map_or(0, |dims| dims.len() + 1)))
+      .collect();
+    let _ = self.table.declare(Symbol {
+      name: func.ident.name.clone(),
+      kind: SymbolKind::Func {
+        ret: func.func_type,
+        params,
+      },
+      pos: Some(func.ident.pos()),
+    });
+
+    self.ret = func.func_type;
+    self.func_name = func.ident.name.clone();
+    self.table.push_scope();
+    for param in &mut func.params {
+      if let Some(dims) = &mut param.dims {
+        for dim in dims.iter_mut() {
+          self.index(dim);
+        }
+      }
+      let _ = self.table.declare(Symbol {
+        name: param.ident.name.clone(),
+        kind: SymbolKind::Var {
+          ty: param.ty,
+          dims: param.dims.as_ref().map_or(0, |dims| dims.len() + 1),
+          is_const: false,
+        },
+        pos: Some(param.ident.pos()),
+      });
+    }
+    self.block_items(&mut func.body);
+    self.table.pop_scope();
+  }
+
+  fn block_items(&mut self, block: &mut Block) {
+    for item in &mut block.items {
+      match item {
+        BlockItem::Decl(decl) => self.decl(decl),
+        BlockItem::Stmt(stmt) => self.stmt(stmt),
+      }
+    }
+  }
+
+  fn stmt(&mut self, stmt: &mut Stmt) {
+    match stmt {
+      Stmt::Assign(lval, exp) => {
+        for index in &mut lval.indices {
+          self.index(index);
+        }
+        self.exp(exp);
+        if let Some(SymbolKind::Var { ty, .. }) = self.table.lookup(&lval.ident.name).map(|s| s.kind.clone()) {
+          self.convert(exp, ty);
+        }
+      }
+      Stmt::Exp(Some(exp)) => {
+        self.exp(exp);
+      }
+      Stmt::Exp(None) | Stmt::Break(_) | Stmt::Continue(_) => {}
+      Stmt::Block(block) => {
+        self.table.push_scope();
+        self.block_items(block);
+        self.table.pop_scope();
+      }
+      Stmt::If(cond, then, otherwise) => {
+        self.cond(cond);
+        self.stmt(then);
+        if let Some(otherwise) = otherwise {
+          self.stmt(otherwise);
+        }
+      }
+      Stmt::While(cond, body) => {
+        self.cond(cond);
+        self.stmt(body);
+      }
+      Stmt::Return(exp, span) => match (exp, self.ret) {
+        (Some(exp), FuncType::Void) => {
+          self.exp(exp);
+          self.error(ErrorType::ReturnValueInVoid(self.func_name.clone()), exp.span);
+        }
+        (Some(exp), ret) => {
+          self.exp(exp);
+          let ty = if ret == FuncType::Int { BType::Int } else { BType::Float };
+          self.convert(exp, ty);
+        }
+        (None, FuncType::Void) => {}
+        (None, _) => self.error(ErrorType::MissingReturnValue(self.func_name.clone()), *span),
+      },
+    }
+  }
+
+  // 条件表达式可以是int或float
+  fn cond(&mut self, exp: &mut Exp) {
+    let ty = self.exp(exp);
+    self.scalar(exp, ty);
+  }
+
+  // 数组下标和维度必须是int
+  fn index(&mut self, exp: &mut Exp) {
+    let ty = self.exp(exp);
+    if ty == Some(Type::Float) {
+      self.error(ErrorType::NonIntegerIndex, exp.span);
+    } else {
+      self.scalar(exp, ty);
+    }
+  }
+
+  // 检查表达式能作为数值使用，返回其基本类型
+  fn scalar(&mut self, exp: &Exp, ty: Option<Type>) -> Option<BType> {
+    match ty? {
+      Type::Void => {
+        let name = match &exp.kind {
+          ExpKind::Call(func, _) => func.name.clone(),
+          _ => String::new(),
+        };
+        self.error(ErrorType::VoidValue(name), exp.span);
+        None
+      }
+      // 数组作为数值的错误已在名字分析中报告
+      Type::Array(..) => None,
+      ty => ty.as_btype(),
+    }
+  }
+
+  // 需要时将表达式包裹一层转换
+  fn convert(&mut self, exp: &mut Exp, to: BType) {
+    let from = exp.ty;
+    if let Some(from) = self.scalar(exp, from) {
+      if from != to {
+        cast(exp, to);
+      }
+    }
+  }
+
+  fn exp(&mut self, exp: &mut Exp) -> Option<Type> {
+    let span = exp.span;
+    let ty = match &mut exp.kind {
+      ExpKind::IntConst(_) => Some(Type::Int),
+      ExpKind::FloatConst(_) => Some(Type::Float),
+      ExpKind::LVal(lval) => {
+        for index in &mut lval.indices {
+          self.index(index);
+        }
+        match self.table.lookup(&lval.ident.name).map(|s| s.kind.clone()) {
+          Some(SymbolKind::Var { ty, dims, .. }) if lval.indices.len() < dims => {
+            Some(Type::Array(ty, dims - lval.indices.len()))
+          }
+          Some(SymbolKind::Var { ty, .. }) => Some(ty.into()),
+          _ => None,
+        }
+      }
+      ExpKind::Call(func, args) => self.call(func, args),
+      ExpKind::Unary(op, operand) => {
+        let ty = self.exp(operand);
+        let ty = self.scalar(operand, ty)?;
+        match op {
+          UnaryOp::Not => Some(Type::Int),
+          _ => Some(ty.into()),
+        }
+      }
+      ExpKind::Binary(op, lhs, rhs) => {
+        let lhs_ty = self.exp(lhs);
+        let rhs_ty = self.exp(rhs);
+        let lhs_ty = self.scalar(lhs, lhs_ty);
+        let rhs_ty = self.scalar(rhs, rhs_ty);
+        let (lhs_ty, rhs_ty) = (lhs_ty?, rhs_ty?);
+        if op.is_logical() {
+          // && 和 || 的操作数各自与0比较，不需要转换
+          Some(Type::Int)
+        } else if *op == BinaryOp::Mod && (lhs_ty == BType::Float || rhs_ty == BType::Float) {
+          self.error(ErrorType::ModOnFloat, span);
+          None
+        } else {
+          let common = if lhs_ty == BType::Float || rhs_ty == BType::Float {
+            BType::Float
+          } else {
+            BType::Int
+          };
+          if lhs_ty != common {
+            cast(lhs, common);
+          }
+          if rhs_ty != common {
+            cast(rhs, common);
+          }
+          if op.is_relational() {
+            Some(Type::Int)
+          } else {
+            Some(common.into())
+          }
+        }
+      }
+      ExpKind::Cast(ty, operand) => {
+        let ty = *ty;
+        self.exp(operand);
+        Some(ty.into())
+      }
+    };
+    exp.ty = ty;
+    ty
+  }
+
+  fn call(&mut self, func: &Ident, args: &mut [Exp]) -> Option<Type> {
+    let arg_types: Vec<_> = args.iter_mut().map(|arg| self.exp(arg)).collect();
+    let Some(SymbolKind::Func { ret, params }) = self.table.lookup(&func.name).map(|s| s.kind.clone()) else {
+      return None;
+    };
+    if params.len() != args.len() {
+      return Some(ret.into());
+    }
+    for (index, ((arg, arg_ty), param)) in args.iter_mut().zip(arg_types).zip(params).enumerate() {
+      match arg_ty {
+        Some(Type::Array(elem, _)) if param.dims > 0 && elem != param.ty => {
+          self.error(
+            ErrorType::ArgumentType {
+              name: func.name.clone(),
+              index: index + 1,
+            },
+            arg.span,
+          );
+        }
+        Some(Type::Array(..)) => {}
+        _ if param.dims == 0 => self.convert(arg, param.ty),
+        _ => {}
+      }
+    }
+    Some(ret.into())
+  }
+}
+
+fn cast(exp: &mut Exp, to: BType) {
+  let span = exp.span;
+  let inner = std::mem::replace(exp, Exp::new(ExpKind::IntConst(0), span));
+  *exp = Exp {
+    ty: Some(to.into()),
+    ..Exp::new(ExpKind::Cast(to, Box::new(inner)), span)
+  };
+}
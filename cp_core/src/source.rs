@@ -0,0 +1,71 @@
+use crate::token::{Point, Span};
+
+// 源文件及其每一行起点的字节偏移，用于在偏移和行列号之间转换
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceFile {
+  name: String,
+  text: String,
+  line_starts: Vec<usize>,
+}
+
+impl SourceFile {
+  pub fn new(name: impl Into<String>, text: impl Into<String>) -> Self {
+    let text = text.into();
+    let line_starts = std::iter::once(0)
+      .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+      .collect();
+    Self {
+      name: name.into(),
+      text,
+      line_starts,
+    }
+  }
+
+  pub fn get_name(&self) -> &str {
+    &self.name
+  }
+
+  pub fn get_text(&self) -> &str {
+    &self.text
+  }
+
+  pub fn line_count(&self) -> usize {
+    self.line_starts.len()
+  }
+
+  // 偏移所在的行号，从1开始；二分查找行首偏移
+  pub fn row(&self, offset: usize) -> usize {
+    self.line_starts.partition_point(|&start| start <= offset)
+  }
+
+  // 偏移对应的行列号，超出文件末尾时取文件末尾
+  pub fn point(&self, offset: usize) -> Point {
+    let offset = self.floor_char_boundary(offset);
+    let row = self.row(offset);
+    let line_start = self.line_starts[row - 1];
+    Point::new(row, self.text[line_start..offset].chars().count() + 1)
+  }
+
+  pub fn span(&self, start: usize, end: usize) -> Span {
+    Span::new(start, end, self.point(start), self.point(end))
+  }
+
+  // 第row行的内容，不含换行符
+  pub fn line(&self, row: usize) -> Option<&str> {
+    let start = *self.line_starts.get(row.checked_sub(1)?)?;
+    let end = self.line_starts.get(row).map_or(self.text.len(), |&next| next - 1);
+    Some(self.text[start..end].trim_end_matches('\r'))
+  }
+
+  pub fn slice(&self, span: &Span) -> &str {
+    &self.text[*span.get_start()..*span.get_end()]
+  }
+
+  fn floor_char_boundary(&self, offset: usize) -> usize {
+    let mut offset = offset.min(self.text.len());
+    while !self.text.is_char_boundary(offset) {
+      offset -= 1;
+    }
+    offset
+  }
+}
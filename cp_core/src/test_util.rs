@@ -0,0 +1,39 @@
+use std::sync::Mutex;
+
+use crate::ast::{self, CompUnit};
+use crate::lexer::{Cursor, TokenStream};
+use crate::parser::{Grammar, LR1Parser, TreeNode};
+use crate::semantic;
+
+// 分析表缓存在磁盘上，并行的测试需要串行地构建
+pub static PARSER_LOCK: Mutex<()> = Mutex::new(());
+
+pub fn parse_source(source: &str) -> TreeNode {
+  let _guard = PARSER_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+  let mut grammar = Grammar::new();
+  grammar.grammar_load("../g3.txt");
+  let mut lr1 = LR1Parser::new();
+  lr1.compute_lr1_item_sets(&grammar);
+  lr1.construct_parsing_table(&grammar).expect("conflicts are resolved by default");
+  let tokens = Cursor::new(source).map(|result| match result {
+    Ok(token) => token,
+    Err(err) => panic!("lexer error at {}:{}: {}", err.row, err.col, err),
+  });
+  let lr1 = lr1.parse_stream(tokens.skip_trivia());
+  if let Some(err) = lr1.get_errors().first() {
+    panic!("syntax error at {:?}: {}", err.error_pos, err);
+  }
+  lr1.get_tree().unwrap().clone()
+}
+
+pub fn lower_source(source: &str) -> CompUnit {
+  ast::lower(&parse_source(source)).unwrap()
+}
+
+// 词法、语法和语义检查都通过的AST
+pub fn check_source(source: &str) -> CompUnit {
+  let mut unit = lower_source(source);
+  let errors = semantic::check(&mut unit);
+  assert!(errors.is_empty(), "semantic errors: {:?}", errors);
+  unit
+}
@@ -6,6 +6,8 @@ pub struct Token {
   token_type: TokenType,
   value: String,
   pos: Option<Point>,
+  span: Option<Span>,
+  literal: Option<Literal>,
 }
 
 impl Token {
@@ -14,9 +16,24 @@ impl Token {
       token_type,
       value,
       pos,
+      span: None,
+      literal: None,
     }
   }
 
+  // 设置词素在源程序中的区间，pos随之改为区间的起点
+  pub fn with_span(mut self, span: Span) -> Self {
+    self.pos = Some(*span.get_start_point());
+    self.span = Some(span);
+    self
+  }
+
+  // 数值常量附带解析好的值
+  pub fn with_literal(mut self, literal: Literal) -> Self {
+    self.literal = Some(literal);
+    self
+  }
+
   pub fn new_terminal(value: String, pos: Option<Point>) -> Self {
     Self::new(TokenType::Terminal(TokenKind::Identifier), value, pos)
   }
@@ -43,6 +60,14 @@ impl Token {
     &self.pos
   }
 
+  pub fn get_span(&self) -> &Option<Span> {
+    &self.span
+  }
+
+  pub fn get_literal(&self) -> Option<Literal> {
+    self.literal
+  }
+
   pub fn get_value(&self) -> &String {
     &self.value
   }
@@ -65,6 +90,13 @@ impl Token {
     false
   }
 
+  pub fn is_error(&self) -> bool {
+    if let TokenType::Terminal(TokenKind::Err) = self.token_type {
+      return true;
+    }
+    false
+  }
+
   pub fn is_comment(&self) -> bool {
     if let TokenType::Terminal(TokenKind::Comment) = self.token_type {
       return true;
@@ -142,6 +174,7 @@ impl TokenType {
       "<=" => TokenType::Terminal(TokenKind::Operator(Operators::LesEq)),
       ">=" => TokenType::Terminal(TokenKind::Operator(Operators::GrtEq)),
       "==" => TokenType::Terminal(TokenKind::Operator(Operators::LEq)),
+      "!=" => TokenType::Terminal(TokenKind::Operator(Operators::NotEq)),
       "&&" => TokenType::Terminal(TokenKind::Operator(Operators::LAnd)),
       "||" => TokenType::Terminal(TokenKind::Operator(Operators::LOr)),
       "ε" => TokenType::Terminal(TokenKind::Empty),
@@ -151,7 +184,7 @@ impl TokenType {
   }
 }
 
-#[derive(Debug, Eq, PartialEq, Clone, Copy, Ord, PartialOrd, Serialize, Deserialize)]
+#[derive(Debug, Default, Eq, PartialEq, Clone, Copy, Ord, PartialOrd, Serialize, Deserialize)]
 pub struct Point {
   row: usize,
   col: usize,
@@ -174,6 +207,86 @@ impl Point {
   }
 }
 
+// 数值常量的值；浮点数按位保存，使词素可以比较和排序
+#[derive(Debug, Eq, PartialEq, Clone, Copy, Ord, PartialOrd, Serialize, Deserialize)]
+pub enum Literal {
+  Int(i32),
+  Float(u32),
+}
+
+impl Literal {
+  pub fn float(value: f32) -> Self {
+    Literal::Float(value.to_bits())
+  }
+
+  // 常量对应的词素类型：IntConst或FloatConst
+  pub fn kind(&self) -> Numbers {
+    match self {
+      Literal::Int(_) => Numbers::Integer,
+      Literal::Float(_) => Numbers::Float,
+    }
+  }
+
+  pub fn as_i32(&self) -> Option<i32> {
+    match self {
+      Literal::Int(value) => Some(*value),
+      Literal::Float(_) => None,
+    }
+  }
+
+  pub fn as_f32(&self) -> Option<f32> {
+    match self {
+      Literal::Int(_) => None,
+      Literal::Float(bits) => Some(f32::from_bits(*bits)),
+    }
+  }
+}
+
+// 源程序中的一段区间：start和end为字节偏移（左闭右开），
+// start_point和end_point为对应的行列号，列号按字符计数
+#[derive(Debug, Default, Eq, PartialEq, Clone, Copy, Ord, PartialOrd, Serialize, Deserialize)]
+pub struct Span {
+  start: usize,
+  end: usize,
+  start_point: Point,
+  end_point: Point,
+}
+
+impl Span {
+  pub fn new(start: usize, end: usize, start_point: Point, end_point: Point) -> Self {
+    Self {
+      start,
+      end,
+      start_point,
+      end_point,
+    }
+  }
+
+  pub fn get_start(&self) -> &usize {
+    &self.start
+  }
+
+  pub fn get_end(&self) -> &usize {
+    &self.end
+  }
+
+  pub fn get_start_point(&self) -> &Point {
+    &self.start_point
+  }
+
+  pub fn get_end_point(&self) -> &Point {
+    &self.end_point
+  }
+
+  pub fn len(&self) -> usize {
+    self.end - self.start
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.start == self.end
+  }
+}
+
 #[derive(Debug, Eq, PartialEq, Clone, Ord, PartialOrd, Serialize, Deserialize)]
 pub enum TokenKind {
   Comment,
@@ -198,6 +311,8 @@ pub enum TokenKind {
   // TODO:设置start
   Start,
   End,
+  // 文法文件中用%lex声明的其他终结符，按名字与文法中的终结符对应
+  Other(String),
 }
 
 impl Display for TokenKind {
@@ -243,6 +358,7 @@ impl Display for TokenKind {
       TokenKind::Operator(Operators::Eq) => write!(f, "="),
       TokenKind::Empty => write!(f, "ε"),
       TokenKind::End => write!(f, "#"),
+      TokenKind::Other(name) => write!(f, "{}", name),
       _ => write!(f, "Unknown"),
     }
   }
@@ -268,7 +384,6 @@ pub enum KeyWords {
 #[derive(Debug, Eq, PartialEq, Clone, Ord, PartialOrd, Serialize, Deserialize)]
 pub enum Numbers {
   Integer,
-  Exponent,
   Float,
 }
 
@@ -1,16 +1,27 @@
 use std::sync::Arc;
 
-use cp_core::lexer::Cursor;
+use cp_core::diagnostics::{Diagnostic, Format, Renderer};
+use cp_core::lexer::{Cursor, LexerError, LexerKind};
 use cp_core::relexer;
 use cp_core::parser;
-use druid::widget::Button;
+use cp_core::source::SourceFile;
+use druid::widget::{Button, Checkbox};
 use druid::{
   widget::{Flex, Padding},
-  Widget,
+  Widget, WidgetExt,
 };
 
 use crate::AppState;
 
+// 错误按诊断格式渲染后显示在信息面板中
+fn log_errors(data: &mut AppState, errors: &[LexerError]) {
+  let file = SourceFile::new("<editor>", data.source_code.as_str());
+  let renderer = Renderer::new(Format::Plain, &file);
+  for err in errors {
+    Arc::make_mut(&mut data.log_info).push(renderer.render(&Diagnostic::from(err)))
+  }
+}
+
 pub fn build() -> impl Widget<AppState> {
   Padding::new(
     1.0,
@@ -20,21 +31,14 @@ pub fn build() -> impl Widget<AppState> {
           data.out_put = Arc::new(Vec::new());
           data.log_info = Arc::new(Vec::new());
 
-          let mut cursor = Cursor::new(&data.source_code);
-          while !cursor.is_eof() {
-            match cursor.advance_token() {
-              Ok(token) => {
-                if !token.is_whitespace() && !token.is_comment() {
-                  // Arc::make_mut(&mut data.out_put).push(format!("{}", token))
-                  println!("{}", token);
-                }
-              }
-              Err(err) => {
-                // Arc::make_mut(&mut data.log_info).push(format!("{}", err.message))
-                println!("{}", err.message);
-              }
+          let (tokens, errors) = Cursor::new(&data.source_code).tokenize();
+          for token in tokens {
+            if !token.is_whitespace() && !token.is_comment() {
+              // Arc::make_mut(&mut data.out_put).push(format!("{}", token))
+              println!("{}", token);
             }
           }
+          log_errors(data, &errors);
         }),
       )
       .with_child(
@@ -45,30 +49,30 @@ pub fn build() -> impl Widget<AppState> {
           let mut re_lexer = relexer::ReLexer::new(&data.source_code);
           re_lexer.generate_token();
           for token in re_lexer.tokens {
-            Arc::make_mut(&mut data.out_put).push(format!("{}", token))
-          }
-          for error in re_lexer.errors {
-            Arc::make_mut(&mut data.log_info).push(format!("{}", error))
+            if !token.is_whitespace() && !token.is_comment() {
+              Arc::make_mut(&mut data.out_put).push(format!("{}", token))
+            }
           }
+          log_errors(data, &re_lexer.errors);
         }),
       )
       .with_child(Button::new("语法分析").on_click(|_, data: &mut AppState, _| {
-        let mut cursor = Cursor::new(&data.source_code);
-        let mut input = vec![];
-        while !cursor.is_eof() {
-          if let Ok(token) = cursor.advance_token() {
-            if !token.is_whitespace() && !token.is_comment() {
-              input.push(token);
-            }
-          }
-        }
+        data.log_info = Arc::new(Vec::new());
+        let lexer = if data.use_relexer { LexerKind::ReLexer } else { LexerKind::Cursor };
+        let (tokens, errors) = lexer.tokenize(&data.source_code);
+        log_errors(data, &errors);
+        // Err词素已经报告过，不交给语法分析
+        let input: Vec<_> = tokens
+          .into_iter()
+          .filter(|t| !t.is_whitespace() && !t.is_comment() && !t.is_error())
+          .collect();
 
         let mut grammar = parser::Grammar::new();
         grammar.grammar_load("./g3.txt");
 
         let mut lr1 = parser::LR1Parser::new();
         lr1.compute_lr1_item_sets(&grammar);
-        lr1.construct_parsing_table(&grammar);
+        lr1.construct_parsing_table(&grammar).expect("conflicts are resolved by default");
 
         for action in &lr1.action_table {
           println!("{:?}", action);
@@ -81,6 +85,7 @@ pub fn build() -> impl Widget<AppState> {
         let tmp = lr1.construct_tree(&input);
         println!("{}", tmp);
       }))
+      .with_child(Checkbox::new("使用ReLexer").lens(AppState::use_relexer))
       .with_child(Button::new("button4"))
       .with_child(Button::new("button5")),
   )
@@ -13,6 +13,8 @@ pub struct AppState {
     source_code: String,
     out_put: Arc<Vec<String>>,
     log_info: Arc<Vec<String>>,
+    // 语法分析使用ReLexer还是Cursor产生词素
+    use_relexer: bool,
 }
 
 impl AppState {
@@ -21,6 +23,7 @@ impl AppState {
             source_code: format!(""),
             out_put: Arc::new(Vec::new()),
             log_info: Arc::new(Vec::new()),
+            use_relexer: false,
         }
     }
 }
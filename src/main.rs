@@ -1,16 +1,608 @@
-use cp_view::*;
-
-fn main() {
-    // let text = "()//dsds\n/*111*/int bcd 123 12e 12.2 12e-2 's' \"sdsd\t\" + - += |";
-    // // let text = "_ab ds";
-    // // lexical_analyzer::analyze(text);
-    //
-    // let mut c = Cursor::new(text);
-    // while !c.is_eof() {
-    //     match c.advance_token() {
-    //         Ok(a) => println!("{}", a),
-    //         Err(a) => println!("ERR: {}", a),
-    //     }
-    // }
-    show();
+use std::fs;
+use std::io::{self, BufReader, BufWriter, IsTerminal, Read};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use cp_core::ast;
+use cp_core::backend::Target;
+use cp_core::conformance::{self, Runner, Summary};
+use cp_core::diagnostics::{Diagnostic, Format, Renderer, Severity};
+use cp_core::differential::{self, Divergence, Generator};
+use cp_core::interpreter;
+use cp_core::ir;
+use cp_core::lexer::{LexerKind, TokenStream};
+use cp_core::parser::{Conflict, ConflictPolicy, Grammar, GrammarIssue, LR1Parser, TreeNode};
+use cp_core::preprocess::{PreprocessError, Preprocessed, Preprocessor};
+use cp_core::semantic;
+use cp_core::source::SourceFile;
+use cp_core::token::Token;
+
+const DEFAULT_GRAMMAR: &str = "./g3.txt";
+
+const USAGE: &str = "\
+Usage: rcp <command> [options] [file]
+
+Commands:
+    preprocess [file]           Print a SysY source after #include and macro expansion
+    lex [file]                  Print the token stream of a SysY source
+    parse [file]                Print the parse tree of a SysY source
+    ast [file]                  Print the abstract syntax tree of a SysY source
+    check [file]                Run semantic analysis and report errors
+    ir [file]                   Print the three-address code of a SysY source
+    run <file>                  Interpret a SysY program; exits with the low 8 bits
+                                of the value returned by main
+    compile [file]              Generate assembly for the selected target
+    test <dir>                  Run every .sy program under dir that has a matching .out
+                                file, feeding the .in file as stdin, and compare the
+                                output and exit code
+    grammar [file]              Check a grammar file (default: the -g grammar) for undefined
+                                symbols, duplicate heads, unreachable or nonproductive
+                                nonterminals, unused tokens and a missing start rule,
+                                then build the parsing tables and report LR conflicts
+    lexdiff [path]              Compare Cursor and ReLexer on every .sy file under path
+                                and on random inputs, printing each divergence together
+                                with a minimized input
+    gui                         Open the graphical front end
+    help                        Print this message
+
+Options:
+    -g, --grammar <path>        Grammar file used for parsing (default: ./g3.txt)
+    -t, --target <name>         Code generation target: riscv64, llvm (default: riscv64)
+    -l, --lexer <name>          Lexer used as the front end: cursor, relexer, or generated
+                                from the %lex rules of the -g grammar (default: cursor)
+    -I <dir>                    Add a directory to the #include search path
+    -e, --error-format <name>   Diagnostic format: plain, color, json
+                                (default: color when stderr is a terminal, else plain)
+    -c, --conflicts <policy>    LR conflicts in the grammar: prefer-shift resolves them as
+                                yacc does (shift, else the earlier production), error
+                                refuses to parse (default: prefer-shift)
+    -o, --output <path>         Write generated code to a file instead of stdout
+    -n, --random <count>        Random inputs tried by lexdiff, seeded 0..count (default: 0)
+
+Reads from stdin when no file (or `-`) is given.";
+
+// 0: 成功, 1: 源程序有错误, 2: 命令行或文件错误
+const EXIT_COMPILE_ERROR: u8 = 1;
+const EXIT_USAGE_ERROR: u8 = 2;
+
+enum Command {
+    Preprocess,
+    Lex,
+    Parse,
+    Ast,
+    Check,
+    Ir,
+    Run,
+    Compile,
+    Test,
+    LexDiff,
+    Grammar,
+    Gui,
+    Help,
+}
+
+struct Options {
+    command: Command,
+    input: Option<String>,
+    grammar: String,
+    target: Target,
+    lexer: LexerKind,
+    include_dirs: Vec<String>,
+    error_format: Format,
+    conflicts: ConflictPolicy,
+    output: Option<String>,
+    random: u64,
+}
+
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let command = match args.first().map(String::as_str) {
+        None | Some("gui") => Command::Gui,
+        Some("preprocess") => Command::Preprocess,
+        Some("lex") => Command::Lex,
+        Some("parse") => Command::Parse,
+        Some("ast") => Command::Ast,
+        Some("check") => Command::Check,
+        Some("ir") => Command::Ir,
+        Some("run") => Command::Run,
+        Some("compile") => Command::Compile,
+        Some("test") => Command::Test,
+        Some("lexdiff") => Command::LexDiff,
+        Some("grammar") => Command::Grammar,
+        Some("help") | Some("-h") | Some("--help") => Command::Help,
+        Some(other) => return Err(format!("unknown command `{}`", other)),
+    };
+
+    let mut options = Options {
+        command,
+        input: None,
+        grammar: DEFAULT_GRAMMAR.to_string(),
+        target: Target::Riscv64,
+        lexer: LexerKind::Cursor,
+        include_dirs: Vec::new(),
+        error_format: if io::stderr().is_terminal() { Format::Color } else { Format::Plain },
+        conflicts: ConflictPolicy::default(),
+        output: None,
+        random: 0,
+    };
+
+    // 生成的词法分析器依赖-g指定的文法，读完全部选项后再构造
+    let mut lexer = None;
+    let mut rest = args.iter().skip(1);
+    while let Some(arg) = rest.next() {
+        match arg.as_str() {
+            "-g" | "--grammar" => match rest.next() {
+                Some(path) => options.grammar = path.clone(),
+                None => return Err(format!("`{}` expects a path", arg)),
+            },
+            "-t" | "--target" => match rest.next() {
+                Some(name) => {
+                    options.target =
+                        Target::from_name(name).ok_or_else(|| format!("unknown target `{}`", name))?
+                }
+                None => return Err(format!("`{}` expects a target name", arg)),
+            },
+            "-l" | "--lexer" => match rest.next() {
+                Some(name) => lexer = Some(name),
+                None => return Err(format!("`{}` expects a lexer name", arg)),
+            },
+            "-I" => match rest.next() {
+                Some(dir) => options.include_dirs.push(dir.clone()),
+                None => return Err(format!("`{}` expects a directory", arg)),
+            },
+            "-e" | "--error-format" => match rest.next() {
+                Some(name) => {
+                    options.error_format =
+                        Format::from_name(name).ok_or_else(|| format!("unknown error format `{}`", name))?
+                }
+                None => return Err(format!("`{}` expects a format name", arg)),
+            },
+            "-c" | "--conflicts" => match rest.next() {
+                Some(name) => {
+                    options.conflicts = ConflictPolicy::from_name(name)
+                        .ok_or_else(|| format!("unknown conflict policy `{}`", name))?
+                }
+                None => return Err(format!("`{}` expects a policy name", arg)),
+            },
+            "-o" | "--output" => match rest.next() {
+                Some(path) => options.output = Some(path.clone()),
+                None => return Err(format!("`{}` expects a path", arg)),
+            },
+            "-n" | "--random" => match rest.next().map(|count| count.parse()) {
+                Some(Ok(count)) => options.random = count,
+                _ => return Err(format!("`{}` expects a count", arg)),
+            },
+            "-h" | "--help" => options.command = Command::Help,
+            "-" => options.input = None,
+            flag if flag.starts_with('-') => return Err(format!("unknown option `{}`", flag)),
+            path => {
+                if options.input.is_some() {
+                    return Err(format!("unexpected argument `{}`", path));
+                }
+                options.input = Some(path.to_string());
+            }
+        }
+    }
+    if let Some(name) = lexer {
+        options.lexer = LexerKind::from_name(name, &options.grammar)?;
+    }
+    Ok(options)
+}
+
+fn read_source(input: &Option<String>) -> Result<String, String> {
+    match input {
+        Some(path) => fs::read_to_string(path).map_err(|e| format!("cannot read `{}`: {}", path, e)),
+        None => {
+            let mut buf = String::new();
+            io::stdin()
+                .read_to_string(&mut buf)
+                .map_err(|e| format!("cannot read stdin: {}", e))?;
+            Ok(buf)
+        }
+    }
+}
+
+// 预处理，包含文件的搜索路径为-I给出的目录；相对路径先在源文件所在目录查找
+fn preprocess_source(options: &Options, source: &str) -> Result<Preprocessed, PreprocessError> {
+    let mut preprocessor = Preprocessor::new();
+    for dir in &options.include_dirs {
+        preprocessor.add_include_dir(dir);
+    }
+    let file_name = options.input.as_deref().unwrap_or("<stdin>");
+    preprocessor.process(file_name, source)
+}
+
+// 诊断信息输出到stderr，位置按行映射还原为预处理之前的文件和行号
+fn report(options: &Options, source: &Preprocessed, diagnostics: impl IntoIterator<Item = Diagnostic>) {
+    let file_name = options.input.as_deref().unwrap_or("<stdin>");
+    let file = SourceFile::new(file_name, source.text.as_str());
+    let renderer = Renderer::new(options.error_format, &file).with_line_map(&source.line_map);
+    for diagnostic in diagnostics {
+        eprint!("{}", renderer.render(&diagnostic));
+    }
+}
+
+// 词法分析，过滤空白和注释，错误输出到stderr
+fn tokenize(options: &Options, source: &Preprocessed) -> Result<Vec<Token>, usize> {
+    let (tokens, errors) = options.lexer.tokenize(&source.text);
+    report(options, source, errors.iter().map(Diagnostic::from));
+    if errors.is_empty() {
+        Ok(tokens.into_iter().filter(|t| !t.is_whitespace() && !t.is_comment()).collect())
+    } else {
+        Err(errors.len())
+    }
+}
+
+fn lex(options: &Options, source: &Preprocessed) -> ExitCode {
+    match tokenize(options, source) {
+        Ok(tokens) => {
+            for token in tokens {
+                let (row, col) = token.get_pos().map_or((0, 0), |p| (*p.get_row(), *p.get_col()));
+                println!("{}:{}\t{}\t{}", row, col, token.get_type(), token.get_value());
+            }
+            ExitCode::SUCCESS
+        }
+        Err(_) => ExitCode::from(EXIT_COMPILE_ERROR),
+    }
+}
+
+// 读取文法文件，返回文法和用于诊断的源文件
+fn load_grammar(path: &str) -> Result<(Grammar, SourceFile), ExitCode> {
+    let Ok(text) = fs::read_to_string(path) else {
+        eprintln!("error: cannot read grammar `{}`", path);
+        return Err(ExitCode::from(EXIT_USAGE_ERROR));
+    };
+    let mut grammar = Grammar::new();
+    grammar.grammar_load_text(&text);
+    Ok((grammar, SourceFile::new(path, text)))
+}
+
+fn report_grammar<'a>(options: &Options, file: &SourceFile, issues: impl IntoIterator<Item = &'a GrammarIssue>) {
+    let renderer = Renderer::new(options.error_format, file);
+    for issue in issues {
+        eprint!("{}", renderer.render(&Diagnostic::from(issue)));
+    }
+}
+
+// 冲突默认已经消解，是警告；--conflicts error时是错误
+fn report_conflicts(options: &Options, file: &SourceFile, conflicts: &[Conflict]) {
+    let renderer = Renderer::new(options.error_format, file);
+    for conflict in conflicts {
+        let mut diagnostic = Diagnostic::from(conflict);
+        if options.conflicts == ConflictPolicy::Error {
+            diagnostic.severity = Severity::Error;
+        }
+        eprint!("{}", renderer.render(&diagnostic));
+    }
+}
+
+// 文法有错误时不构造分析表，只报告错误；警告由grammar命令报告
+fn checked_grammar(options: &Options) -> Result<(Grammar, SourceFile), ExitCode> {
+    let (grammar, file) = load_grammar(&options.grammar)?;
+    let issues = grammar.validate();
+    if issues.iter().any(GrammarIssue::is_error) {
+        report_grammar(options, &file, issues.iter().filter(|issue| issue.is_error()));
+        return Err(ExitCode::from(EXIT_USAGE_ERROR));
+    }
+    Ok((grammar, file))
+}
+
+// 构造分析表，按--conflicts error处理冲突时报告全部冲突
+fn parser_tables(options: &Options) -> Result<LR1Parser, ExitCode> {
+    let (grammar, file) = checked_grammar(options)?;
+    let mut lr1 = LR1Parser::new().with_conflict_policy(options.conflicts);
+    lr1.compute_lr1_item_sets(&grammar);
+    if let Err(conflicts) = lr1.construct_parsing_table(&grammar) {
+        report_conflicts(options, &file, &conflicts);
+        return Err(ExitCode::from(EXIT_USAGE_ERROR));
+    }
+    Ok(lr1)
+}
+
+// 词法分析和语法分析，失败时返回退出码；词素按需流入语法分析器
+fn parse_tree(options: &Options, source: &Preprocessed) -> Result<TreeNode, ExitCode> {
+    let lr1 = parser_tables(options)?;
+
+    let mut lexer_errors = Vec::new();
+    let tokens = options
+        .lexer
+        .stream(&source.text, &mut lexer_errors)
+        .skip_trivia()
+        .filter(|token| !token.is_error());
+    let lr1 = lr1.parse_stream(tokens);
+
+    // 有词法错误时，语法错误多半是连带产生的，不再报告
+    if !lexer_errors.is_empty() {
+        report(options, source, lexer_errors.iter().map(Diagnostic::from));
+        return Err(ExitCode::from(EXIT_COMPILE_ERROR));
+    }
+    report(options, source, lr1.get_errors().iter().map(Diagnostic::from));
+    match lr1.get_tree() {
+        Some(tree) => Ok(tree.clone()),
+        None => Err(ExitCode::from(EXIT_COMPILE_ERROR)),
+    }
+}
+
+fn parse(options: &Options, source: &Preprocessed) -> ExitCode {
+    match parse_tree(options, source) {
+        Ok(tree) => {
+            print!("{}", tree);
+            ExitCode::SUCCESS
+        }
+        Err(code) => code,
+    }
+}
+
+fn lower_ast(options: &Options, source: &Preprocessed) -> Result<ast::CompUnit, ExitCode> {
+    let tree = parse_tree(options, source)?;
+    ast::lower(&tree).map_err(|err| {
+        report(options, source, [Diagnostic::from(&err)]);
+        ExitCode::from(EXIT_COMPILE_ERROR)
+    })
+}
+
+fn print_ast(options: &Options, source: &Preprocessed) -> ExitCode {
+    match lower_ast(options, source) {
+        Ok(unit) => {
+            println!("{:#?}", unit);
+            ExitCode::SUCCESS
+        }
+        Err(code) => code,
+    }
+}
+
+// 语义分析，错误输出到stderr
+fn check_ast(options: &Options, source: &Preprocessed) -> Result<ast::CompUnit, ExitCode> {
+    let mut unit = lower_ast(options, source)?;
+    let errors = semantic::check(&mut unit);
+    report(options, source, errors.iter().map(Diagnostic::from));
+    if errors.is_empty() {
+        Ok(unit)
+    } else {
+        Err(ExitCode::from(EXIT_COMPILE_ERROR))
+    }
+}
+
+fn check(options: &Options, source: &Preprocessed) -> ExitCode {
+    match check_ast(options, source) {
+        Ok(_) => ExitCode::SUCCESS,
+        Err(code) => code,
+    }
+}
+
+fn print_ir(options: &Options, source: &Preprocessed) -> ExitCode {
+    match check_ast(options, source) {
+        Ok(unit) => {
+            print!("{}", ir::generate(&unit));
+            ExitCode::SUCCESS
+        }
+        Err(code) => code,
+    }
+}
+
+// 解释执行，程序的stdin和stdout即rcp的stdin和stdout
+fn run(options: &Options, source: &Preprocessed) -> ExitCode {
+    let unit = match check_ast(options, source) {
+        Ok(unit) => unit,
+        Err(code) => return code,
+    };
+    match interpreter::run(&unit, BufReader::new(io::stdin()), BufWriter::new(io::stdout())) {
+        Ok(ret) => ExitCode::from(ret as u8),
+        Err(err) => {
+            report(options, source, [Diagnostic::from(&err)]);
+            ExitCode::from(EXIT_COMPILE_ERROR)
+        }
+    }
+}
+
+fn compile(options: &Options, source: &Preprocessed) -> ExitCode {
+    let unit = match check_ast(options, source) {
+        Ok(unit) => unit,
+        Err(code) => return code,
+    };
+    let code = options.target.emit(&unit);
+    match &options.output {
+        Some(path) => match fs::write(path, code) {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(e) => {
+                eprintln!("error: cannot write `{}`: {}", path, e);
+                ExitCode::from(EXIT_USAGE_ERROR)
+            }
+        },
+        None => {
+            print!("{}", code);
+            ExitCode::SUCCESS
+        }
+    }
+}
+
+// 检查文法文件，输出全部错误和警告
+fn grammar(options: &Options) -> ExitCode {
+    let path = options.input.as_deref().unwrap_or(&options.grammar);
+    let (grammar, file) = match load_grammar(path) {
+        Ok(grammar) => grammar,
+        Err(code) => return code,
+    };
+    let issues = grammar.validate();
+    report_grammar(options, &file, &issues);
+    let mut errors = issues.iter().filter(|issue| issue.is_error()).count();
+    let mut warnings = issues.len() - errors;
+    // 文法没有错误时才能构造分析表
+    if errors == 0 {
+        let mut lr1 = LR1Parser::new();
+        lr1.compute_lr1_item_sets(&grammar);
+        lr1.construct_parsing_table(&grammar).expect("conflicts are resolved by default");
+        let conflicts = lr1.get_conflicts();
+        report_conflicts(options, &file, conflicts);
+        match options.conflicts {
+            ConflictPolicy::Error => errors += conflicts.len(),
+            ConflictPolicy::PreferShift => warnings += conflicts.len(),
+        }
+    }
+    println!("{}: {} error(s), {} warning(s)", path, errors, warnings);
+    if errors == 0 {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::from(EXIT_COMPILE_ERROR)
+    }
+}
+
+// 运行测试集，逐个输出结果，失败的用例附带差异
+fn test(options: &Options) -> ExitCode {
+    let Some(dir) = &options.input else {
+        eprintln!("error: `test` expects a directory\n\n{}", USAGE);
+        return ExitCode::from(EXIT_USAGE_ERROR);
+    };
+    let cases = match conformance::discover(Path::new(dir)) {
+        Ok(cases) => cases,
+        Err(e) => {
+            eprintln!("error: cannot read `{}`: {}", dir, e);
+            return ExitCode::from(EXIT_USAGE_ERROR);
+        }
+    };
+    // 与其他命令使用同一份分析表、同一个词法分析器
+    let tables = match parser_tables(options) {
+        Ok(tables) => tables,
+        Err(code) => return code,
+    };
+
+    let runner = Runner::new(tables, options.lexer.clone(), options.include_dirs.iter().map(PathBuf::from).collect());
+    let mut summary = Summary::default();
+    for case in &cases {
+        let outcome = runner.run_case(case);
+        if outcome.is_pass() {
+            println!("PASS {}", case.name);
+        } else {
+            println!("FAIL {}: {}", case.name, outcome.to_string().trim_end());
+        }
+        summary.add(&outcome);
+    }
+    println!("\n{}", summary);
+    if summary.failed == 0 {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::from(EXIT_COMPILE_ERROR)
+    }
+}
+
+fn report_divergences(name: &str, source: &str, divergences: &[Divergence]) {
+    for divergence in divergences {
+        println!("DIFF {}: {}", name, divergence);
+    }
+    println!("minimized: {:?}", differential::minimize_divergence(source));
+}
+
+// 差分测试两个词法分析器，有任何分歧时退出码为1
+fn lexdiff(options: &Options) -> ExitCode {
+    let files = match &options.input {
+        Some(path) if Path::new(path).is_dir() => differential::corpus(Path::new(path)),
+        Some(path) => Ok(vec![Path::new(path).to_path_buf()]),
+        None => Ok(Vec::new()),
+    };
+    let files = match files {
+        Ok(files) => files,
+        Err(e) => {
+            eprintln!("error: cannot read `{}`: {}", options.input.as_deref().unwrap_or_default(), e);
+            return ExitCode::from(EXIT_USAGE_ERROR);
+        }
+    };
+
+    let mut summary = Summary::default();
+    for file in &files {
+        let source = match fs::read_to_string(file) {
+            Ok(source) => source,
+            Err(e) => {
+                eprintln!("error: cannot read `{}`: {}", file.display(), e);
+                return ExitCode::from(EXIT_USAGE_ERROR);
+            }
+        };
+        let divergences = differential::compare(&source);
+        if !divergences.is_empty() {
+            report_divergences(&file.display().to_string(), &source, &divergences);
+            summary.failed += 1;
+        } else {
+            summary.passed += 1;
+        }
+    }
+    for seed in 0..options.random {
+        let source = Generator::new(seed).source(30);
+        let divergences = differential::compare(&source);
+        if !divergences.is_empty() {
+            report_divergences(&format!("seed {}", seed), &source, &divergences);
+            summary.failed += 1;
+        } else {
+            summary.passed += 1;
+        }
+    }
+    println!("\n{}", summary);
+    if summary.failed == 0 {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::from(EXIT_COMPILE_ERROR)
+    }
+}
+
+#[cfg(feature = "gui")]
+fn gui() -> ExitCode {
+    cp_view::show();
+    ExitCode::SUCCESS
+}
+
+#[cfg(not(feature = "gui"))]
+fn gui() -> ExitCode {
+    eprintln!("error: rcp was built without the `gui` feature");
+    ExitCode::from(EXIT_USAGE_ERROR)
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let options = match parse_args(&args) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("error: {}\n\n{}", message, USAGE);
+            return ExitCode::from(EXIT_USAGE_ERROR);
+        }
+    };
+
+    match options.command {
+        Command::Gui => gui(),
+        Command::Test => test(&options),
+        Command::LexDiff => lexdiff(&options),
+        Command::Grammar => grammar(&options),
+        Command::Help => {
+            println!("{}", USAGE);
+            ExitCode::SUCCESS
+        }
+        Command::Preprocess
+        | Command::Lex
+        | Command::Parse | Command::Ast | Command::Check | Command::Ir | Command::Run | Command::Compile => {
+            let source = match read_source(&options.input) {
+                Ok(source) => source,
+                Err(message) => {
+                    eprintln!("error: {}", message);
+                    return ExitCode::from(EXIT_USAGE_ERROR);
+                }
+            };
+            let source = match preprocess_source(&options, &source) {
+                Ok(source) => source,
+                Err(err) => {
+                    let original = Preprocessed { text: source, line_map: Default::default() };
+                    report(&options, &original, [Diagnostic::from(&err)]);
+                    return ExitCode::from(EXIT_COMPILE_ERROR);
+                }
+            };
+            match options.command {
+                Command::Preprocess => {
+                    print!("{}", source.text);
+                    ExitCode::SUCCESS
+                }
+                Command::Lex => lex(&options, &source),
+                Command::Parse => parse(&options, &source),
+                Command::Ast => print_ast(&options, &source),
+                Command::Ir => print_ir(&options, &source),
+                Command::Run => run(&options, &source),
+                Command::Compile => compile(&options, &source),
+                _ => check(&options, &source),
+            }
+        }
+    }
 }